@@ -36,6 +36,15 @@ fn main() {
     println!("cargo:rerun-if-changed=desmume/desmume/src/frontend/interface/metal_bootstrap.mm");
 
     let src = env::current_dir().unwrap();
+    if !src
+        .join("desmume/desmume/src/frontend/interface/interface.cpp")
+        .exists()
+    {
+        panic!(
+            "The DeSmuME sources are missing. Run `git submodule update --init` and make sure \
+             the checked out revision exports the functions listed in the README."
+        );
+    }
     let build_dir = TempDir::new().unwrap();
     let build_dir = build_dir.path();
 
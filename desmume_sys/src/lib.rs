@@ -1,4 +1,6 @@
-pub use libc::{c_char, c_int, c_long, c_schar, c_short, c_uchar, c_uint, c_ulong, c_ushort};
+pub use libc::{
+    c_char, c_int, c_long, c_schar, c_short, c_uchar, c_uint, c_ulong, c_ushort, c_void,
+};
 
 #[cfg(target_os = "macos")]
 pub mod metal_resources;
@@ -8,6 +10,158 @@ pub type c_bool = c_int;
 
 pub type MemoryCbFnc = Option<extern "C" fn(addr: c_uint, size: c_int) -> c_bool>;
 
+pub const DESMUME_PROCESSOR_ARM9: c_int = 0;
+pub const DESMUME_PROCESSOR_ARM7: c_int = 1;
+
+/// Details about a memory access, passed to `MemoryCbFncEx` callbacks.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct MemoryAccessInfo {
+    /// The address that was accessed.
+    pub address: c_uint,
+    /// The width of the access in bytes.
+    pub size: c_int,
+    /// For writes, the value before the write. For reads the value read,
+    /// for execution the opcode.
+    pub old_value: u32,
+    /// For writes, the value written. For reads the value read,
+    /// for execution the opcode.
+    pub new_value: u32,
+    /// `DESMUME_PROCESSOR_ARM9` or `DESMUME_PROCESSOR_ARM7`.
+    pub processor: c_int,
+    /// Address of the instruction that did the access.
+    pub pc: u32,
+}
+
+/// Full register file of a processor, see `desmume_cpu_get_state`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CpuState {
+    /// R0-R15 as seen in the current mode.
+    pub r: [u32; 16],
+    pub cpsr: u32,
+    /// SPSR of the current mode. 0 in user and system mode.
+    pub spsr: u32,
+    /// R8-R14 of user and system mode.
+    pub r8_r14_usr: [u32; 7],
+    /// R8-R14 of FIQ mode.
+    pub r8_r14_fiq: [u32; 7],
+    /// R13-R14 of IRQ mode.
+    pub r13_r14_irq: [u32; 2],
+    /// R13-R14 of supervisor mode.
+    pub r13_r14_svc: [u32; 2],
+    /// R13-R14 of abort mode.
+    pub r13_r14_abt: [u32; 2],
+    /// R13-R14 of undefined mode.
+    pub r13_r14_und: [u32; 2],
+    pub spsr_fiq: u32,
+    pub spsr_irq: u32,
+    pub spsr_svc: u32,
+    pub spsr_abt: u32,
+    pub spsr_und: u32,
+    /// Address of the next instruction the processor will execute.
+    pub instruction_address: u32,
+}
+
+/// Raw CP15 registers of the ARM9, see `desmume_cp15_get_state`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Cp15State {
+    /// c1,c0,0: Control register.
+    pub control: u32,
+    /// c2,c0,0: Cachability bits for data.
+    pub dcache_config: u32,
+    /// c2,c0,1: Cachability bits for instructions.
+    pub icache_config: u32,
+    /// c3,c0,0: Write buffer control.
+    pub write_buffer: u32,
+    /// c5,c0,2: Extended access permissions for data.
+    pub data_permissions: u32,
+    /// c5,c0,3: Extended access permissions for instructions.
+    pub instruction_permissions: u32,
+    /// c6,c0-c7,0: Protection region base and size.
+    pub protection_regions: [u32; 8],
+    /// c9,c1,0: DTCM base and size.
+    pub dtcm_region: u32,
+    /// c9,c1,1: ITCM base and size.
+    pub itcm_region: u32,
+}
+
+/// Details about an instruction, passed to `InstructionCbFnc` callbacks.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct InstructionInfo {
+    /// Address of the instruction.
+    pub address: u32,
+    /// The instruction. Only the lower 16 bits are used in Thumb mode.
+    pub opcode: u32,
+    /// `DESMUME_PROCESSOR_ARM9` or `DESMUME_PROCESSOR_ARM7`.
+    pub processor: c_int,
+    /// Whether the processor is in Thumb mode.
+    pub thumb: c_bool,
+    /// Emulated time since the last reset, in ARM9 cycles.
+    pub cycles: u64,
+}
+
+pub type InstructionCbFnc =
+    Option<extern "C" fn(info: *const InstructionInfo, userdata: *mut c_void)>;
+
+/// Details about a software interrupt, passed to `SwiCbFnc` callbacks.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct SwiInfo {
+    /// `DESMUME_PROCESSOR_ARM9` or `DESMUME_PROCESSOR_ARM7`.
+    pub processor: c_int,
+    /// The number of the BIOS function: bits 16-23 of the comment field in ARM mode, bits 0-7
+    /// in Thumb mode.
+    pub number: u32,
+    /// Address of the SWI instruction.
+    pub address: u32,
+    /// Whether the processor is in Thumb mode.
+    pub thumb: c_bool,
+}
+
+pub type SwiCbFnc = Option<extern "C" fn(info: *const SwiInfo, userdata: *mut c_void) -> c_bool>;
+
+pub const DESMUME_EVENT_IRQ_RAISED: c_int = 0;
+pub const DESMUME_EVENT_IRQ_ACKNOWLEDGED: c_int = 1;
+pub const DESMUME_EVENT_DMA_STARTED: c_int = 2;
+pub const DESMUME_EVENT_TIMER_OVERFLOW: c_int = 3;
+pub const DESMUME_EVENT_IPC_FIFO_SEND: c_int = 4;
+pub const DESMUME_EVENT_IPC_FIFO_RECEIVE: c_int = 5;
+pub const DESMUME_EVENT_CPU_EXCEPTION: c_int = 6;
+
+/// Details about a hardware event, passed to `HardwareEventCbFnc` callbacks.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct HardwareEventInfo {
+    /// One of the `DESMUME_EVENT_*` constants.
+    pub kind: c_int,
+    /// The processor the event belongs to. For IPC events the sending or receiving processor.
+    pub processor: c_int,
+    /// The bit of the IRQ source in IE / IF, the DMA channel, the timer or the offset of the
+    /// exception vector (0x04 undefined instruction, 0x0C prefetch abort, 0x10 data abort).
+    pub index: c_int,
+    /// For DMA the DMAxCNT register the transfer was started with, for IPC the value sent or
+    /// received, for exceptions the address of the instruction that caused it.
+    pub value: u32,
+    /// For DMA the source address.
+    pub source: u32,
+    /// For DMA the destination address.
+    pub destination: u32,
+    /// Emulated time since the last reset, in ARM9 cycles.
+    pub cycles: u64,
+}
+
+pub type HardwareEventCbFnc =
+    Option<extern "C" fn(info: *const HardwareEventInfo, userdata: *mut c_void)>;
+
+pub const DESMUME_REGISTER_CPSR: c_int = 16;
+pub const DESMUME_REGISTER_SPSR: c_int = 17;
+
+pub type MemoryCbFncEx =
+    Option<extern "C" fn(info: *const MemoryAccessInfo, userdata: *mut c_void) -> c_bool>;
+
 #[repr(C)]
 pub enum DesmumeAudioCore {
     Dummy = 0,
@@ -136,6 +290,15 @@ extern "C" {
 
     pub fn desmume_savestate_save(file_name: *const c_char) -> c_bool;
 
+    /// Serializes the current state into a newly allocated memory stream.
+    /// On success the size of the buffer is written to `size`. The buffer must be released with
+    /// `desmume_savestate_free_memory`. Returns NULL on failure.
+    pub fn desmume_savestate_save_to_memory(size: *mut usize) -> *mut u8;
+
+    pub fn desmume_savestate_free_memory(buffer: *mut u8);
+
+    pub fn desmume_savestate_load_from_memory(data: *const u8, size: usize) -> c_bool;
+
     pub fn desmume_savestate_scan();
 
     pub fn desmume_savestate_slot_load(index: c_int);
@@ -176,20 +339,168 @@ extern "C" {
 
     pub fn desmume_memory_write_long(address: c_int, value: c_ulong);
 
+    /// Like `desmume_memory_read_byte`, but uses the ARM7 view of the bus.
+    pub fn desmume_memory_read_byte_arm7(address: c_int) -> c_uchar;
+
+    /// Like `desmume_memory_read_byte_signed`, but uses the ARM7 view of the bus.
+    pub fn desmume_memory_read_byte_signed_arm7(address: c_int) -> c_schar;
+
+    /// Like `desmume_memory_read_short`, but uses the ARM7 view of the bus.
+    pub fn desmume_memory_read_short_arm7(address: c_int) -> c_ushort;
+
+    /// Like `desmume_memory_read_short_signed`, but uses the ARM7 view of the bus.
+    pub fn desmume_memory_read_short_signed_arm7(address: c_int) -> c_short;
+
+    /// Like `desmume_memory_read_long`, but uses the ARM7 view of the bus.
+    pub fn desmume_memory_read_long_arm7(address: c_int) -> c_ulong;
+
+    /// Like `desmume_memory_read_long_signed`, but uses the ARM7 view of the bus.
+    pub fn desmume_memory_read_long_signed_arm7(address: c_int) -> c_long;
+
+    /// Like `desmume_memory_write_byte`, but uses the ARM7 view of the bus.
+    pub fn desmume_memory_write_byte_arm7(address: c_int, value: c_uchar);
+
+    /// Like `desmume_memory_write_short`, but uses the ARM7 view of the bus.
+    pub fn desmume_memory_write_short_arm7(address: c_int, value: c_ushort);
+
+    /// Like `desmume_memory_write_long`, but uses the ARM7 view of the bus.
+    pub fn desmume_memory_write_long_arm7(address: c_int, value: c_ulong);
+
+    /// Copies `len` bytes starting at `address` into `buffer`.
+    /// Memory callbacks are not triggered by block accesses.
+    pub fn desmume_memory_read_block(address: c_int, buffer: *mut u8, len: c_int);
+
+    /// Copies `len` bytes from `buffer` into memory starting at `address`.
+    /// Memory callbacks are not triggered by block accesses.
+    pub fn desmume_memory_write_block(address: c_int, buffer: *const u8, len: c_int);
+
+    /// Like `desmume_memory_read_block`, but uses the ARM7 view of the bus.
+    pub fn desmume_memory_read_block_arm7(address: c_int, buffer: *mut u8, len: c_int);
+
+    /// Like `desmume_memory_write_block`, but uses the ARM7 view of the bus.
+    pub fn desmume_memory_write_block_arm7(address: c_int, buffer: *const u8, len: c_int);
+
     pub fn desmume_memory_read_register(register_name: *mut c_char) -> u32;
 
     pub fn desmume_memory_write_register(register_name: *mut c_char, value: u32);
 
+    /// Reads a register of the current mode: 0-15 for R0-R15, `DESMUME_REGISTER_CPSR` or
+    /// `DESMUME_REGISTER_SPSR`.
+    pub fn desmume_cpu_get_register(processor: c_int, index: c_int) -> u32;
+
+    /// Writes a register of the current mode. See `desmume_cpu_get_register`.
+    pub fn desmume_cpu_set_register(processor: c_int, index: c_int, value: u32);
+
+    /// Copies the full register file of the processor into `state`. All banks hold up-to-date
+    /// values, including the bank of the current mode.
+    pub fn desmume_cpu_get_state(processor: c_int, state: *mut CpuState);
+
+    /// Sets a callback that is called before every instruction the processor executes,
+    /// replacing the previous one. Pass a `None` callback to remove it.
+    /// `userdata` is passed to the callback as-is.
+    pub fn desmume_cpu_set_instruction_callback(
+        processor: c_int,
+        cb: InstructionCbFnc,
+        userdata: *mut c_void,
+    );
+
+    /// Sets a callback that is called before the processor executes a SWI instruction, replacing
+    /// the previous one. Pass a `None` callback to remove it. The callback may change registers
+    /// with `desmume_cpu_set_register`. If it returns true, the BIOS function is skipped and
+    /// execution continues after the SWI instruction, as if the function had returned.
+    pub fn desmume_cpu_set_swi_callback(processor: c_int, cb: SwiCbFnc, userdata: *mut c_void);
+
+    /// Sets a callback that is called for IRQs, DMA transfers, timer overflows, IPC FIFO
+    /// transfers, undefined instructions and aborts of both processors, replacing the previous
+    /// one. Pass a `None` callback to remove it. IRQs acknowledged together are reported as one
+    /// event per source. Exceptions are reported before the processor jumps to the vector.
+    pub fn desmume_set_hardware_event_callback(cb: HardwareEventCbFnc, userdata: *mut c_void);
+
+    /// Copies the CP15 registers of the ARM9 into `state`.
+    pub fn desmume_cp15_get_state(state: *mut Cp15State);
+
     pub fn desmume_memory_get_next_instruction() -> u32;
 
     pub fn desmume_memory_set_next_instruction(value: u32);
 
+    /// Returns the address of the next instruction the given processor will execute.
+    pub fn desmume_cpu_get_instruction_address(processor: c_int) -> u32;
+
+    /// Returns the number of frames emulated since the last reset.
+    pub fn desmume_get_frame_count() -> u64;
+
+    /// Returns the emulated time since the last reset, in ARM9 cycles.
+    pub fn desmume_get_cycle_count() -> u64;
+
+    /// Makes the processor continue execution at `address`, like a `bx` to it: bit 0 selects
+    /// Thumb mode. A halted processor is woken up. When called from an exec callback of the
+    /// processor, the instruction the callback was called for is skipped.
+    pub fn desmume_cpu_set_instruction_address(processor: c_int, address: u32);
+
+    /// Returns whether the processor is halted, eg. waiting for an interrupt in `swi IntrWait`.
+    pub fn desmume_cpu_get_halted(processor: c_int) -> c_bool;
+
+    /// Halts the processor until the next interrupt, or wakes it up.
+    pub fn desmume_cpu_set_halted(processor: c_int, halted: c_bool);
+
+    /// Executes exactly one instruction on the given processor. The other processor and the
+    /// rest of the hardware are advanced to the same point in time, so `desmume_cycle`
+    /// continues the current frame from there. If the processor is halted, time is advanced
+    /// until it executes its next instruction.
+    /// Returns the address of the next instruction of the stepped processor.
+    pub fn desmume_cpu_step(processor: c_int) -> u32;
+
+    /// Like `desmume_cpu_step`, but executes `count` instructions.
+    pub fn desmume_cpu_run_instructions(processor: c_int, count: c_uint) -> u32;
+
+    /// Makes a running `desmume_cpu_run_instructions` or `desmume_cycle` return early. When
+    /// called from an exec callback, it returns before that instruction is executed, otherwise
+    /// after the current instruction. An interrupted `desmume_cycle` leaves the frame unfinished,
+    /// the next call continues it.
+    pub fn desmume_cpu_request_stop();
+
     pub fn desmume_memory_register_write(address: c_int, size: c_int, cb: MemoryCbFnc);
 
     pub fn desmume_memory_register_read(address: c_int, size: c_int, cb: MemoryCbFnc);
 
     pub fn desmume_memory_register_exec(address: c_int, size: c_int, cb: MemoryCbFnc);
 
+    pub fn desmume_memory_register_write_arm7(address: c_int, size: c_int, cb: MemoryCbFnc);
+
+    pub fn desmume_memory_register_read_arm7(address: c_int, size: c_int, cb: MemoryCbFnc);
+
+    pub fn desmume_memory_register_exec_arm7(address: c_int, size: c_int, cb: MemoryCbFnc);
+
+    /// Like `desmume_memory_register_write`, but for the given processor and with a callback
+    /// receiving the full access details. `userdata` is passed to the callback as-is.
+    /// Registering replaces any callback registered for this address and processor.
+    /// Pass a `None` callback to unregister.
+    pub fn desmume_memory_register_write_ex(
+        processor: c_int,
+        address: c_uint,
+        size: c_uint,
+        cb: MemoryCbFncEx,
+        userdata: *mut c_void,
+    );
+
+    pub fn desmume_memory_register_read_ex(
+        processor: c_int,
+        address: c_uint,
+        size: c_uint,
+        cb: MemoryCbFncEx,
+        userdata: *mut c_void,
+    );
+
+    /// The callback is called for every instruction that starts within
+    /// `address..address + size`, in ARM and Thumb mode.
+    pub fn desmume_memory_register_exec_ex(
+        processor: c_int,
+        address: c_uint,
+        size: c_uint,
+        cb: MemoryCbFncEx,
+        userdata: *mut c_void,
+    );
+
     pub fn desmume_screenshot(screenshot_buffer: *mut c_char);
 
     pub fn desmume_input_joy_init() -> c_bool;
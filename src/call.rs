@@ -0,0 +1,101 @@
+use crate::mem::{write_block, CpuMode, HookAction, Processor, Register};
+use crate::{DeSmuME, DeSmuMEError};
+use desmume_sys::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// The return address of called functions. Nothing is mapped here, so games never execute it.
+pub const CALL_SENTINEL: u32 = 0x0FFF_FFF0;
+
+/// Functions that run for more instructions than this are aborted.
+const MAX_CALL_INSTRUCTIONS: u32 = 100_000_000;
+/// Number of instructions run between checks whether the function returned.
+const CALL_CHUNK: u32 = 100_000;
+
+impl DeSmuME {
+    /// Call the function at `address` in the emulated game and return its result (R0).
+    ///
+    /// Bit 0 of `address` selects Thumb mode. The first four arguments are passed in R0 - R3,
+    /// the rest on the stack, below the current stack pointer. The return address is
+    /// [`CALL_SENTINEL`]; execution stops when the function returns there. Afterwards the
+    /// registers, the mode and the halt state of the processor are restored, so the game
+    /// continues where it left off. Memory changes made by the function are kept.
+    ///
+    /// The rest of the system keeps running while the function executes, so interrupts are
+    /// handled as usual. Returns [`DeSmuMEError::FunctionCallTimeout`] if the function doesn't
+    /// return within 100 million instructions.
+    ///
+    /// # Usage example
+    /// ```rs
+    /// let random = emu.call_function(Processor::Arm9, 0x02001234, &[])?;
+    /// let size = emu.call_function(Processor::Arm9, 0x02005679, &[src, dst])?;
+    /// ```
+    pub fn call_function(
+        &mut self,
+        processor: Processor,
+        address: u32,
+        args: &[u32],
+    ) -> Result<u32, DeSmuMEError> {
+        let saved = self.memory().cpu_state(processor);
+        let halted = unsafe { desmume_cpu_get_halted(processor.id()) };
+
+        let mem = self.memory_mut();
+        let (register_args, stack_args) = args.split_at(args.len().min(4));
+        let registers = [Register::R0, Register::R1, Register::R2, Register::R3];
+        for (&register, &value) in registers.iter().zip(register_args) {
+            mem.set_reg(processor, register, value);
+        }
+        let mut sp = saved.registers.get(Register::SP);
+        if !stack_args.is_empty() {
+            // The stack is kept 8-byte aligned at calls.
+            sp = sp.wrapping_sub(stack_args.len() as u32 * 4) & !7;
+            let bytes: Vec<u8> = stack_args.iter().flat_map(|a| a.to_le_bytes()).collect();
+            write_block(processor, sp, &bytes);
+        }
+        mem.set_reg(processor, Register::SP, sp);
+        mem.set_reg(processor, Register::LR, CALL_SENTINEL);
+
+        let returned = Arc::new(AtomicBool::new(false));
+        let returned_hook = returned.clone();
+        let hook = mem.cpu_mut(processor).hook_exec(CALL_SENTINEL, move |_| {
+            returned_hook.store(true, Ordering::Relaxed);
+            unsafe { desmume_cpu_request_stop() };
+            HookAction::Continue
+        });
+        unsafe { desmume_cpu_set_instruction_address(processor.id(), address) };
+
+        let mut remaining = MAX_CALL_INSTRUCTIONS;
+        while !returned.load(Ordering::Relaxed) && remaining > 0 {
+            let count = remaining.min(CALL_CHUNK);
+            unsafe { desmume_cpu_run_instructions(processor.id(), count) };
+            remaining -= count;
+        }
+        drop(hook);
+        let result = self.memory().get_reg(processor, Register::R0);
+
+        let mem = self.memory_mut();
+        // Restore the mode first, so the registers of the right bank are written.
+        mem.set_reg(processor, Register::CPSR, saved.cpsr.raw);
+        for (n, &value) in saved.registers.r.iter().enumerate().take(15) {
+            mem.set_reg(processor, Register::try_from(n as u32).unwrap(), value);
+        }
+        // User and system mode have no SPSR.
+        if !matches!(
+            saved.cpsr.mode,
+            Some(CpuMode::User | CpuMode::System) | None
+        ) {
+            mem.set_reg(processor, Register::SPSR, saved.registers.spsr);
+        }
+        let resume = saved.instruction_address | saved.cpsr.thumb as u32;
+        unsafe {
+            desmume_cpu_set_instruction_address(processor.id(), resume);
+            desmume_cpu_set_halted(processor.id(), halted);
+        }
+
+        if returned.load(Ordering::Relaxed) {
+            Ok(result)
+        } else {
+            Err(DeSmuMEError::FunctionCallTimeout)
+        }
+    }
+}
@@ -0,0 +1,369 @@
+use crate::mem::{
+    add_event_hook, add_observer, Backtrace, CpuState, HardwareEvent, HookAction, HookHandle,
+    InstructionEvent, MemoryRegion, ObserverHandle, Processor,
+};
+use crate::{DeSmuMEMemory, TraceEntry};
+use desmume_sys::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ptr::slice_from_raw_parts;
+use std::sync::{Arc, Mutex};
+
+/// Older reports are dropped once this many are collected.
+const MAX_REPORTS: usize = 1000;
+
+/// Configuration of the crash detection. See [`DeSmuMECrashDetector::start`].
+#[derive(Debug, Clone)]
+pub struct CrashConfig {
+    /// Report undefined instructions and prefetch / data aborts.
+    pub exceptions: bool,
+    /// Report jumps into memory that can not contain code.
+    pub unmapped_jumps: bool,
+    /// Report a processor that executed nothing but a tight loop for this many frames in a row.
+    pub tight_loop_frames: Option<u32>,
+    /// The largest loop, in bytes, that is still considered tight.
+    pub tight_loop_size: u32,
+    /// Report if the screens did not change for this many frames in a row.
+    pub unchanged_output_frames: Option<u32>,
+    /// Number of recently executed instructions included in each report.
+    pub trace_length: usize,
+}
+
+impl Default for CrashConfig {
+    fn default() -> Self {
+        Self {
+            exceptions: true,
+            unmapped_jumps: true,
+            tight_loop_frames: Some(300),
+            tight_loop_size: 64,
+            unchanged_output_frames: None,
+            trace_length: 64,
+        }
+    }
+}
+
+/// What went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CrashKind {
+    UndefinedInstruction,
+    PrefetchAbort,
+    DataAbort,
+    /// The processor jumped from `from` into memory that can not contain code.
+    UnmappedJump {
+        from: u32,
+    },
+    /// The processor only executed the instructions from `start` to `end` (inclusive) for
+    /// [`CrashConfig::tight_loop_frames`] frames.
+    TightLoop {
+        start: u32,
+        end: u32,
+    },
+    /// The screens did not change for [`CrashConfig::unchanged_output_frames`] frames. Reported
+    /// for the ARM9.
+    FrozenOutput,
+}
+
+/// A crash or hang of the emulated software. See [`DeSmuMECrashDetector`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct CrashReport {
+    pub kind: CrashKind,
+    pub processor: Processor,
+    /// Address of the instruction that caused the crash, or the current instruction for hangs.
+    pub pc: u32,
+    /// The frame the crash was detected in, see [`crate::DeSmuME::frame_count`].
+    pub frame: u64,
+    /// The registers at the time the crash was detected.
+    pub cpu_state: CpuState,
+    pub backtrace: Backtrace,
+    /// The most recently executed instructions, oldest first.
+    pub recent: Vec<TraceEntry>,
+}
+
+type ReportCallback = Box<dyn FnMut(&CrashReport) + Send>;
+
+#[derive(Default)]
+struct ProcessorState {
+    recent: VecDeque<InstructionEvent>,
+    /// Whether the processor is executing from memory that can not contain code.
+    in_unmapped: bool,
+    /// Lowest and highest address executed in the current frame.
+    frame_range: Option<(u32, u32)>,
+    /// Range executed since the current tight loop started.
+    loop_range: Option<(u32, u32)>,
+    loop_frames: u32,
+}
+
+#[derive(Default)]
+struct Detector {
+    config: CrashConfig,
+    reports: VecDeque<CrashReport>,
+    callback: Option<ReportCallback>,
+    processors: [ProcessorState; 2],
+    last_output: Option<u64>,
+    unchanged_frames: u32,
+}
+
+impl Detector {
+    fn report(&mut self, kind: CrashKind, processor: Processor, pc: u32) {
+        let mem = DeSmuMEMemory(PhantomData);
+        let report = CrashReport {
+            kind,
+            processor,
+            pc,
+            frame: unsafe { desmume_get_frame_count() },
+            cpu_state: mem.cpu_state(processor),
+            backtrace: mem.backtrace(processor),
+            recent: self.processors[processor as usize]
+                .recent
+                .iter()
+                .map(|event| TraceEntry {
+                    processor,
+                    address: event.address,
+                    opcode: event.opcode,
+                    thumb: event.thumb,
+                    cycles: event.cycles,
+                    register_deltas: Vec::new(),
+                })
+                .collect(),
+        };
+        if let Some(callback) = &mut self.callback {
+            callback(&report);
+        }
+        if self.reports.len() == MAX_REPORTS {
+            self.reports.pop_front();
+        }
+        self.reports.push_back(report);
+    }
+
+    fn on_instruction(&mut self, event: &InstructionEvent) {
+        let trace_length = self.config.trace_length;
+        let state = &mut self.processors[event.processor as usize];
+        let from = state.recent.back().map(|e| e.address);
+        if trace_length > 0 {
+            if state.recent.len() >= trace_length {
+                state.recent.pop_front();
+            }
+            state.recent.push_back(*event);
+        }
+        state.frame_range = Some(match state.frame_range {
+            Some((low, high)) => (low.min(event.address), high.max(event.address)),
+            None => (event.address, event.address),
+        });
+        let unmapped = !can_contain_code(event.processor, event.address);
+        // Only the jump into unmapped memory is reported, not every instruction after it.
+        let jumped = unmapped && !state.in_unmapped;
+        state.in_unmapped = unmapped;
+        if jumped && self.config.unmapped_jumps {
+            let kind = CrashKind::UnmappedJump {
+                from: from.unwrap_or(event.address),
+            };
+            self.report(kind, event.processor, event.address);
+        }
+    }
+
+    fn on_exception(&mut self, processor: Processor, vector: u32, address: u32) {
+        let kind = match vector {
+            0x04 => CrashKind::UndefinedInstruction,
+            0x0C => CrashKind::PrefetchAbort,
+            0x10 => CrashKind::DataAbort,
+            _ => return,
+        };
+        if self.config.exceptions {
+            self.report(kind, processor, address);
+        }
+    }
+
+    fn on_frame(&mut self) {
+        if let Some(limit) = self.config.tight_loop_frames {
+            for processor in [Processor::Arm9, Processor::Arm7] {
+                self.check_tight_loop(processor, limit);
+            }
+        }
+        if let Some(limit) = self.config.unchanged_output_frames {
+            let screens = unsafe { &*slice_from_raw_parts(desmume_draw_raw(), FRAMEBUFFER_SIZE) };
+            let mut hasher = DefaultHasher::new();
+            screens.hash(&mut hasher);
+            let hash = hasher.finish();
+            if self.last_output == Some(hash) {
+                self.unchanged_frames += 1;
+                if self.unchanged_frames == limit {
+                    let pc = DeSmuMEMemory(PhantomData)
+                        .cpu_state(Processor::Arm9)
+                        .instruction_address;
+                    self.report(CrashKind::FrozenOutput, Processor::Arm9, pc);
+                }
+            } else {
+                self.unchanged_frames = 0;
+            }
+            self.last_output = Some(hash);
+        }
+    }
+
+    fn check_tight_loop(&mut self, processor: Processor, limit: u32) {
+        let loop_size = self.config.tight_loop_size;
+        let state = &mut self.processors[processor as usize];
+        // A processor that was halted for the whole frame is not looping.
+        let Some((low, high)) = state.frame_range.take() else {
+            state.loop_range = None;
+            state.loop_frames = 0;
+            return;
+        };
+        let merged = state
+            .loop_range
+            .map_or((low, high), |(start, end)| (low.min(start), high.max(end)));
+        if merged.1 - merged.0 < loop_size {
+            state.loop_range = Some(merged);
+            state.loop_frames += 1;
+        } else if high - low < loop_size {
+            // A different loop started.
+            state.loop_range = Some((low, high));
+            state.loop_frames = 1;
+        } else {
+            state.loop_range = None;
+            state.loop_frames = 0;
+            return;
+        }
+        let (start, end) = merged;
+        // Reported once per loop.
+        if state.loop_frames == limit {
+            let pc = state.recent.back().map_or(start, |e| e.address);
+            self.report(CrashKind::TightLoop { start, end }, processor, pc);
+        }
+    }
+}
+
+/// Whether the processor can execute code at `address`.
+fn can_contain_code(processor: Processor, address: u32) -> bool {
+    matches!(
+        MemoryRegion::containing_on(processor, address),
+        Some(
+            MemoryRegion::Itcm
+                | MemoryRegion::MainRam
+                | MemoryRegion::SharedWram
+                | MemoryRegion::Arm7Wram
+                | MemoryRegion::Arm7Vram
+                | MemoryRegion::GbaRom
+                | MemoryRegion::Arm9Bios
+                | MemoryRegion::Arm7Bios
+        )
+    )
+}
+
+struct Running {
+    _observers: Vec<ObserverHandle>,
+    _hooks: Vec<HookHandle>,
+}
+
+/// Detects crashes and hangs of the emulated software: undefined instructions, aborts, jumps
+/// into memory that can not contain code, processors stuck in a tight loop and screens that
+/// stopped changing.
+///
+/// Each detected problem is reported as a [`CrashReport`] with the registers, a backtrace and
+/// the most recently executed instructions, both to the callback and to the reports returned by
+/// [`DeSmuMECrashDetector::take_reports`], eg. after [`crate::DeSmuME::cycle`]. Detection uses
+/// a callback for every executed instruction and slows down emulation.
+///
+/// # Usage example
+/// ```rs
+/// emu.crash_detector_mut().start(CrashConfig::default());
+/// emu.crash_detector_mut().set_callback(|report| {
+///     println!("{:?} at {:#010x}", report.kind, report.pc);
+///     println!("{}", report.backtrace.symbolize(&symbols));
+/// });
+/// emu.cycle();
+/// let reports = emu.crash_detector_mut().take_reports();
+/// ```
+pub struct DeSmuMECrashDetector {
+    detector: Arc<Mutex<Detector>>,
+    running: Option<Running>,
+}
+
+impl DeSmuMECrashDetector {
+    pub(crate) fn new() -> Self {
+        Self {
+            detector: Arc::new(Mutex::new(Detector::default())),
+            running: None,
+        }
+    }
+
+    /// Start detecting crashes. If detection is already running, it is restarted with the new
+    /// configuration.
+    pub fn start(&mut self, config: CrashConfig) {
+        self.running = None;
+        {
+            let mut detector = self.detector.lock().unwrap();
+            detector.processors = Default::default();
+            detector.last_output = None;
+            detector.unchanged_frames = 0;
+            detector.config = config;
+        }
+        let mut observers = Vec::new();
+        let mut hooks = Vec::new();
+        for processor in [Processor::Arm9, Processor::Arm7] {
+            let detector = self.detector.clone();
+            observers.push(add_observer(processor, move |event| {
+                detector.lock().unwrap().on_instruction(event)
+            }));
+            let detector = self.detector.clone();
+            hooks.push(add_event_hook(processor, move |event| {
+                if let HardwareEvent::Exception {
+                    processor,
+                    vector,
+                    address,
+                } = event
+                {
+                    detector
+                        .lock()
+                        .unwrap()
+                        .on_exception(*processor, *vector, *address);
+                }
+                HookAction::Continue
+            }));
+        }
+        self.running = Some(Running {
+            _observers: observers,
+            _hooks: hooks,
+        });
+    }
+
+    /// Stop detecting crashes. Reports collected so far are kept.
+    pub fn stop(&mut self) {
+        self.running = None;
+    }
+
+    /// Whether crashes are being detected.
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Remove and return the collected reports, oldest first. At most the last 1000 reports are
+    /// kept.
+    pub fn take_reports(&mut self) -> Vec<CrashReport> {
+        self.detector.lock().unwrap().reports.drain(..).collect()
+    }
+
+    /// Call `callback` for every report as it is detected, replacing the previous callback.
+    /// Reports are collected as well.
+    pub fn set_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(&CrashReport) + Send + 'static,
+    {
+        self.detector.lock().unwrap().callback = Some(Box::new(callback));
+    }
+
+    /// Remove the callback set with [`DeSmuMECrashDetector::set_callback`].
+    pub fn clear_callback(&mut self) {
+        self.detector.lock().unwrap().callback = None;
+    }
+
+    /// Called by `DeSmuME::cycle` after each frame.
+    pub(crate) fn on_frame(&mut self) {
+        if self.running.is_some() {
+            self.detector.lock().unwrap().on_frame();
+        }
+    }
+}
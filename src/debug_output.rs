@@ -0,0 +1,284 @@
+use crate::mem::{
+    add_observer, read_block, HookAction, HookContext, HookHandle, InstructionEvent,
+    ObserverHandle, Processor,
+};
+use crate::DeSmuMEMemory;
+use desmume_sys::*;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+/// The no$gba debug ports, see [`DeSmuMEDebugOutput`].
+const PORT_STRING: u32 = 0x04FFFA10;
+const PORT_STRING_PARAMS: u32 = 0x04FFFA14;
+const PORT_STRING_PARAMS_LINE: u32 = 0x04FFFA18;
+const PORT_CHAR: u32 = 0x04FFFA1C;
+
+/// Older messages are dropped once this many are collected.
+const MAX_MESSAGES: usize = 10_000;
+/// Longer strings are cut off.
+const MAX_STRING_LENGTH: usize = 4096;
+
+/// A message the game printed. See [`DeSmuMEDebugOutput`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugMessage {
+    pub processor: Processor,
+    /// The frame the message was printed in, see [`crate::DeSmuME::frame_count`].
+    pub frame: u64,
+    /// Address of the instruction that printed the message.
+    pub pc: u32,
+    /// The text, without a trailing linefeed.
+    pub text: String,
+}
+
+type MessageCallback = Box<dyn FnMut(&DebugMessage) + Send>;
+
+#[derive(Default)]
+struct Capture {
+    messages: VecDeque<DebugMessage>,
+    callback: Option<MessageCallback>,
+    /// Characters printed through the char port, until the next linefeed. One per processor.
+    lines: [String; 2],
+    /// Cycle count of the last `%lastclks%` / `%zeroclks%`.
+    last_clocks: u64,
+}
+
+impl Capture {
+    fn emit(&mut self, processor: Processor, pc: u32, text: String) {
+        let message = DebugMessage {
+            processor,
+            frame: unsafe { desmume_get_frame_count() },
+            pc,
+            text,
+        };
+        if let Some(callback) = &mut self.callback {
+            callback(&message);
+        }
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message);
+    }
+
+    fn on_port_write(&mut self, ctx: &HookContext) {
+        let processor = ctx.processor;
+        match ctx.address {
+            PORT_STRING => self.emit(processor, ctx.pc, read_string(processor, ctx.new_value)),
+            PORT_STRING_PARAMS | PORT_STRING_PARAMS_LINE => {
+                let text = self.substitute(processor, &read_string(processor, ctx.new_value));
+                // Without a linefeed the text continues the current line.
+                let line = &mut self.lines[processor as usize];
+                line.push_str(&text);
+                if ctx.address == PORT_STRING_PARAMS_LINE {
+                    let text = std::mem::take(line);
+                    self.emit(processor, ctx.pc, text);
+                }
+            }
+            PORT_CHAR => match ctx.new_value as u8 {
+                b'\n' => {
+                    let text = std::mem::take(&mut self.lines[processor as usize]);
+                    self.emit(processor, ctx.pc, text);
+                }
+                b'\r' => {}
+                c => self.lines[processor as usize].push(c as char),
+            },
+            _ => {}
+        }
+    }
+
+    /// Inline messages start with `mov r12, r12`, followed by a branch over the message, the
+    /// marker 0x6464, a flags halfword and the zero terminated text.
+    fn on_instruction(&mut self, event: &InstructionEvent) {
+        let nop = if event.thumb { 0x46E4 } else { 0xE1A0C00C };
+        if event.opcode != nop {
+            return;
+        }
+        let mut header = [0; 12];
+        read_block(event.processor, event.address, &mut header);
+        let halfword = |offset: usize| u16::from_le_bytes([header[offset], header[offset + 1]]);
+        let (is_branch, marker_offset) = if event.thumb {
+            (halfword(2) & 0xF800 == 0xE000, 4)
+        } else {
+            (header[7] == 0xEA, 8)
+        };
+        if !is_branch || halfword(marker_offset) != 0x6464 {
+            return;
+        }
+        let text = read_string(event.processor, event.address + marker_offset as u32 + 4);
+        let text = self.substitute(event.processor, &text);
+        self.emit(event.processor, event.address, text);
+    }
+
+    /// Replaces the no$gba parameters, eg. `%r0%` or `%frame%`. Registers are printed as 8 hex
+    /// digits, everything else in decimal.
+    fn substitute(&mut self, processor: Processor, text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('%') {
+            result.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let Some(end) = after.find('%') else {
+                rest = &rest[start..];
+                break;
+            };
+            match self.parameter(processor, &after[..end]) {
+                Some(value) => {
+                    result.push_str(&value);
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    result.push('%');
+                    rest = after;
+                }
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+
+    fn parameter(&mut self, processor: Processor, name: &str) -> Option<String> {
+        let mem = DeSmuMEMemory(PhantomData);
+        let register = |n: usize| Some(format!("{:08x}", mem.registers(processor).r[n]));
+        match name {
+            "sp" => register(13),
+            "lr" => register(14),
+            "pc" => register(15),
+            "frame" => Some(unsafe { desmume_get_frame_count() }.to_string()),
+            "scanline" => {
+                let mut vcount = [0; 2];
+                read_block(processor, 0x04000006, &mut vcount);
+                Some(u16::from_le_bytes(vcount).to_string())
+            }
+            "totalclks" => Some(unsafe { desmume_get_cycle_count() }.to_string()),
+            "lastclks" | "zeroclks" => {
+                let now = unsafe { desmume_get_cycle_count() };
+                let elapsed = now.saturating_sub(self.last_clocks);
+                self.last_clocks = now;
+                Some(if name == "lastclks" {
+                    elapsed.to_string()
+                } else {
+                    String::new()
+                })
+            }
+            _ => {
+                let n: usize = name.strip_prefix('r')?.parse().ok()?;
+                (n < 16).then(|| register(n)).flatten()
+            }
+        }
+    }
+}
+
+fn read_string(processor: Processor, address: u32) -> String {
+    let mut bytes = Vec::new();
+    let mut chunk = [0; 64];
+    while bytes.len() < MAX_STRING_LENGTH {
+        read_block(
+            processor,
+            address.wrapping_add(bytes.len() as u32),
+            &mut chunk,
+        );
+        match chunk.iter().position(|&b| b == 0) {
+            Some(end) => {
+                bytes.extend_from_slice(&chunk[..end]);
+                break;
+            }
+            None => bytes.extend_from_slice(&chunk),
+        }
+    }
+    bytes.truncate(MAX_STRING_LENGTH);
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+struct Capturing {
+    _hooks: Vec<HookHandle>,
+    _observers: Vec<ObserverHandle>,
+}
+
+/// Collects the text games print through the no$gba debug facilities.
+///
+/// Supported are the debug message ports at 0x04FFFA10 (string), 0x04FFFA14 (string with
+/// parameters like `%r0%` or `%frame%`), 0x04FFFA18 (the same, followed by a linefeed) and
+/// 0x04FFFA1C (single character), on both processors. Optionally also inline messages embedded
+/// in the code after a `mov r12, r12`, as printed by libnds' `nocashMessage`. Watching for
+/// inline messages uses a callback for every executed instruction and slows down emulation.
+///
+/// # Usage example
+/// ```rs
+/// emu.debug_output_mut().start(false);
+/// emu.debug_output_mut().set_callback(|message| {
+///     println!("[{:?} {}] {}", message.processor, message.frame, message.text);
+/// });
+/// emu.cycle();
+/// let messages = emu.debug_output_mut().take_messages();
+/// ```
+pub struct DeSmuMEDebugOutput {
+    capture: Arc<Mutex<Capture>>,
+    capturing: Option<Capturing>,
+}
+
+impl DeSmuMEDebugOutput {
+    pub(crate) fn new() -> Self {
+        Self {
+            capture: Arc::new(Mutex::new(Capture::default())),
+            capturing: None,
+        }
+    }
+
+    /// Start collecting messages. `inline_messages` also collects messages embedded in code.
+    pub fn start(&mut self, inline_messages: bool) {
+        self.capturing = None;
+        let mut mem = DeSmuMEMemory(PhantomData);
+        let mut hooks = Vec::new();
+        let mut observers = Vec::new();
+        for processor in [Processor::Arm9, Processor::Arm7] {
+            let capture = self.capture.clone();
+            hooks.push(
+                mem.cpu_mut(processor)
+                    .hook_write(PORT_STRING, 0x10, move |ctx| {
+                        capture.lock().unwrap().on_port_write(ctx);
+                        HookAction::Continue
+                    }),
+            );
+            if inline_messages {
+                let capture = self.capture.clone();
+                observers.push(add_observer(processor, move |event| {
+                    capture.lock().unwrap().on_instruction(event)
+                }));
+            }
+        }
+        self.capturing = Some(Capturing {
+            _hooks: hooks,
+            _observers: observers,
+        });
+    }
+
+    /// Stop collecting messages. Messages collected so far are kept.
+    pub fn stop(&mut self) {
+        self.capturing = None;
+    }
+
+    /// Whether messages are being collected.
+    pub fn is_capturing(&self) -> bool {
+        self.capturing.is_some()
+    }
+
+    /// Remove and return the collected messages, oldest first. At most the last 10000 messages
+    /// are kept.
+    pub fn take_messages(&mut self) -> Vec<DebugMessage> {
+        self.capture.lock().unwrap().messages.drain(..).collect()
+    }
+
+    /// Call `callback` for every message as it is printed, replacing the previous callback.
+    /// Messages are collected as well.
+    pub fn set_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(&DebugMessage) + Send + 'static,
+    {
+        self.capture.lock().unwrap().callback = Some(Box::new(callback));
+    }
+
+    /// Remove the callback set with [`DeSmuMEDebugOutput::set_callback`].
+    pub fn clear_callback(&mut self) {
+        self.capture.lock().unwrap().callback = None;
+    }
+}
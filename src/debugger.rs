@@ -0,0 +1,542 @@
+//! Breakpoints and execution control, built on top of the memory hooks of [`DeSmuMEMemory`].
+
+use crate::disasm::Flow;
+use crate::mem::{
+    read_block, DeSmuMEMemory, HookAction, HookContext, HookHandle, HookKind, Processor, Register,
+    Registers,
+};
+use crate::symbols::{Symbol, Symbolize, Symbols};
+use crate::{DeSmuME, DeSmuMEError};
+use desmume_sys::*;
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+/// Identifies a breakpoint registered with the [`Debugger`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BreakpointId(u64);
+
+/// How a value is compared in a [`Condition`]. Values are compared unsigned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn matches(self, lhs: u32, rhs: u32) -> bool {
+        match self {
+            Self::Equal => lhs == rhs,
+            Self::NotEqual => lhs != rhs,
+            Self::Less => lhs < rhs,
+            Self::LessOrEqual => lhs <= rhs,
+            Self::Greater => lhs > rhs,
+            Self::GreaterOrEqual => lhs >= rhs,
+        }
+    }
+}
+
+/// A condition that must hold for a breakpoint to trigger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Condition {
+    /// Compare a register of the processor that hit the breakpoint.
+    Register {
+        register: Register,
+        comparison: Comparison,
+        value: u32,
+    },
+    /// Compare the little-endian value of `size` bytes (1, 2 or 4) at `address`, as seen by the
+    /// processor that hit the breakpoint.
+    Memory {
+        address: u32,
+        size: u8,
+        comparison: Comparison,
+        value: u32,
+    },
+    /// Compare the value that was read or written. For execution breakpoints this is the opcode.
+    AccessValue { comparison: Comparison, value: u32 },
+}
+
+impl Condition {
+    fn matches(&self, ctx: &HookContext) -> bool {
+        match *self {
+            Condition::Register {
+                register,
+                comparison,
+                value,
+            } => {
+                let mem = DeSmuMEMemory(PhantomData);
+                comparison.matches(mem.get_reg(ctx.processor, register), value)
+            }
+            Condition::Memory {
+                address,
+                size,
+                comparison,
+                value,
+            } => {
+                let mut buffer = [0; 4];
+                read_block(ctx.processor, address, &mut buffer[..size.min(4) as usize]);
+                comparison.matches(u32::from_le_bytes(buffer), value)
+            }
+            Condition::AccessValue { comparison, value } => {
+                comparison.matches(ctx.new_value, value)
+            }
+        }
+    }
+}
+
+/// An execution, read or write breakpoint.
+///
+/// # Usage example
+/// ```rs
+/// use rs_desmume::debugger::{Breakpoint, Comparison, Condition};
+/// use rs_desmume::mem::{Processor, Register};
+///
+/// let bp = Breakpoint::exec(Processor::Arm9, 0x2001140)
+///     .with_condition(Condition::Register {
+///         register: Register::R0,
+///         comparison: Comparison::Equal,
+///         value: 3,
+///     })
+///     .after_hits(2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub processor: Processor,
+    pub kind: HookKind,
+    /// First watched address.
+    pub address: u32,
+    /// Number of watched bytes, starting at `address`.
+    pub size: u32,
+    /// All of these must hold for the breakpoint to count as hit.
+    pub conditions: Vec<Condition>,
+    /// Only stop once the breakpoint was hit this many times. 0 and 1 both stop on the first hit.
+    pub hit_count: u32,
+}
+
+impl Breakpoint {
+    /// Break when the instruction at `address` is executed.
+    pub fn exec(processor: Processor, address: u32) -> Self {
+        Self::new(processor, HookKind::Exec, address, 1)
+    }
+
+    /// Break when memory between `address` and `address + size - 1` is read.
+    pub fn read(processor: Processor, address: u32, size: u32) -> Self {
+        Self::new(processor, HookKind::Read, address, size)
+    }
+
+    /// Break when memory between `address` and `address + size - 1` is written.
+    pub fn write(processor: Processor, address: u32, size: u32) -> Self {
+        Self::new(processor, HookKind::Write, address, size)
+    }
+
+    /// Break on an access to a symbol. Execution breakpoints break on the first instruction,
+    /// read and write breakpoints watch the whole symbol, or 4 bytes if its length is unknown.
+    pub fn for_symbol(processor: Processor, kind: HookKind, symbol: &Symbol) -> Self {
+        match kind {
+            HookKind::Exec => Self::exec(processor, symbol.address & !1),
+            _ => Self::new(
+                processor,
+                kind,
+                symbol.address,
+                symbol.length.filter(|&l| l > 0).unwrap_or(4),
+            ),
+        }
+    }
+
+    fn new(processor: Processor, kind: HookKind, address: u32, size: u32) -> Self {
+        Self {
+            processor,
+            kind,
+            address,
+            size,
+            conditions: Vec::new(),
+            hit_count: 0,
+        }
+    }
+
+    pub fn with_condition(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    pub fn after_hits(mut self, hit_count: u32) -> Self {
+        self.hit_count = hit_count;
+        self
+    }
+}
+
+/// Details about a breakpoint that was hit.
+#[derive(Debug, Clone)]
+pub struct BreakpointHit {
+    pub id: BreakpointId,
+    pub kind: HookKind,
+    pub processor: Processor,
+    /// The address that was accessed or executed.
+    pub address: u32,
+    /// The value that was read or written, or the opcode for execution breakpoints.
+    pub value: u32,
+    /// Address of the instruction that triggered the breakpoint.
+    pub pc: u32,
+    /// The registers of the processor at the moment the breakpoint was hit.
+    pub registers: Registers,
+    /// How often the breakpoint was hit so far.
+    pub hits: u32,
+}
+
+/// Prints the hit with addresses as symbols, eg. `breakpoint 0 hit: exec GameMain (hit 1 times)`.
+impl Symbolize for BreakpointHit {
+    fn symbolize(&self, symbols: &Symbols) -> String {
+        let pc = symbols.symbolize(self.pc);
+        let address = symbols.symbolize(self.address);
+        let access = match self.kind {
+            HookKind::Read => format!("read 0x{:08x} from {address} at {pc}", self.value),
+            HookKind::Write => format!("write 0x{:08x} to {address} at {pc}", self.value),
+            HookKind::Exec => format!("exec {address}"),
+        };
+        format!(
+            "breakpoint {} hit: {access} (hit {} times)",
+            self.id.0, self.hits
+        )
+    }
+}
+
+/// Why [`Debugger::run_until_break`] returned.
+#[derive(Debug, Clone)]
+pub enum StopReason {
+    Breakpoint(BreakpointHit),
+    /// The maximum number of frames was emulated without hitting a breakpoint.
+    FrameLimit,
+    /// A step finished. Contains the address of the next instruction.
+    Stepped(u32),
+    /// The maximum number of instructions was executed before the step finished.
+    InstructionLimit,
+}
+
+#[derive(Default)]
+struct BreakState {
+    hits: HashMap<BreakpointId, u32>,
+    enabled: HashMap<BreakpointId, bool>,
+    pending: Option<BreakpointHit>,
+    /// Whether hitting a breakpoint stops a running `desmume_cpu_run_instructions` or
+    /// `desmume_cycle`.
+    stop_on_hit: bool,
+    /// Execution breakpoints at this instruction are counted, but not reported as hit.
+    step_over: Option<(Processor, u32)>,
+}
+
+/// Breakpoints registered with a [`DeSmuME`] instance. Accessed through [`Debugger`].
+pub(crate) struct Breakpoints {
+    next_id: u64,
+    entries: BTreeMap<BreakpointId, (Breakpoint, HookHandle)>,
+    state: Arc<Mutex<BreakState>>,
+}
+
+impl Breakpoints {
+    pub(crate) fn new() -> Self {
+        Self {
+            next_id: 0,
+            entries: BTreeMap::new(),
+            state: Arc::new(Mutex::new(BreakState::default())),
+        }
+    }
+}
+
+/// Manages breakpoints and controls execution. See [`DeSmuME::debugger`].
+///
+/// [`Debugger::run_until_break`] runs frames until a breakpoint is hit and stops the emulation
+/// right there, in the middle of the frame. The registers reported in the [`BreakpointHit`] are
+/// captured at the moment the breakpoint triggered. Use the stepping functions to execute single
+/// instructions.
+pub struct Debugger<'a> {
+    pub(crate) emu: &'a mut DeSmuME,
+}
+
+impl Debugger<'_> {
+    /// Register a breakpoint. It is enabled right away.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> BreakpointId {
+        let breakpoints = &mut self.emu.breakpoints;
+        let id = BreakpointId(breakpoints.next_id);
+        breakpoints.next_id += 1;
+        {
+            let mut state = breakpoints.state.lock().unwrap();
+            state.hits.insert(id, 0);
+            state.enabled.insert(id, true);
+        }
+
+        let state = breakpoints.state.clone();
+        let bp = breakpoint.clone();
+        let callback = move |ctx: &HookContext| {
+            let mut state = state.lock().unwrap();
+            if !state.enabled.get(&id).copied().unwrap_or(false)
+                || !bp.conditions.iter().all(|c| c.matches(ctx))
+            {
+                return HookAction::Continue;
+            }
+            let hits = state.hits.entry(id).or_default();
+            *hits += 1;
+            let hits = *hits;
+            let stepped_over =
+                ctx.kind == HookKind::Exec && state.step_over == Some((ctx.processor, ctx.address));
+            if hits >= bp.hit_count && state.pending.is_none() && !stepped_over {
+                state.pending = Some(BreakpointHit {
+                    id,
+                    kind: ctx.kind,
+                    processor: ctx.processor,
+                    address: ctx.address,
+                    value: ctx.new_value,
+                    pc: ctx.pc,
+                    registers: DeSmuMEMemory(PhantomData).registers(ctx.processor),
+                    hits,
+                });
+                if state.stop_on_hit {
+                    unsafe { desmume_cpu_request_stop() };
+                }
+            }
+            HookAction::Continue
+        };
+
+        let mut mem = self.emu.memory_mut().cpu_mut(breakpoint.processor);
+        let (address, size) = (breakpoint.address, breakpoint.size.max(1));
+        let handle = match breakpoint.kind {
+            HookKind::Read => mem.hook_read(address, size, callback),
+            HookKind::Write => mem.hook_write(address, size, callback),
+            HookKind::Exec => mem.hook_exec_range(address, size, callback),
+        };
+        self.emu
+            .breakpoints
+            .entries
+            .insert(id, (breakpoint, handle));
+        id
+    }
+
+    /// Register a breakpoint on a symbol of [`DeSmuME::symbols`]. See [`Breakpoint::for_symbol`].
+    pub fn add_symbol_breakpoint(
+        &mut self,
+        processor: Processor,
+        kind: HookKind,
+        name: &str,
+    ) -> Result<BreakpointId, DeSmuMEError> {
+        let symbol = self
+            .emu
+            .symbols()
+            .get(name)
+            .ok_or_else(|| DeSmuMEError::UnknownSymbol(name.to_string()))?;
+        let breakpoint = Breakpoint::for_symbol(processor, kind, symbol);
+        Ok(self.add_breakpoint(breakpoint))
+    }
+
+    /// Remove a breakpoint. Returns `false` if no breakpoint with this id exists.
+    pub fn remove_breakpoint(&mut self, id: BreakpointId) -> bool {
+        let breakpoints = &mut self.emu.breakpoints;
+        let mut state = breakpoints.state.lock().unwrap();
+        state.hits.remove(&id);
+        state.enabled.remove(&id);
+        breakpoints.entries.remove(&id).is_some()
+    }
+
+    /// Remove all breakpoints.
+    pub fn clear_breakpoints(&mut self) {
+        let breakpoints = &mut self.emu.breakpoints;
+        *breakpoints.state.lock().unwrap() = BreakState::default();
+        breakpoints.entries.clear();
+    }
+
+    /// Enable or disable a breakpoint without removing it.
+    pub fn set_enabled(&mut self, id: BreakpointId, enabled: bool) {
+        let mut state = self.emu.breakpoints.state.lock().unwrap();
+        if let Some(e) = state.enabled.get_mut(&id) {
+            *e = enabled;
+        }
+    }
+
+    /// The breakpoint with the given id, if it exists.
+    pub fn breakpoint(&self, id: BreakpointId) -> Option<&Breakpoint> {
+        self.emu.breakpoints.entries.get(&id).map(|(bp, _)| bp)
+    }
+
+    /// All registered breakpoints.
+    pub fn breakpoints(&self) -> impl Iterator<Item = (BreakpointId, &Breakpoint)> {
+        self.emu
+            .breakpoints
+            .entries
+            .iter()
+            .map(|(id, (bp, _))| (*id, bp))
+    }
+
+    /// How often the breakpoint was hit (with all conditions met) so far.
+    pub fn hit_count(&self, id: BreakpointId) -> u32 {
+        let state = self.emu.breakpoints.state.lock().unwrap();
+        state.hits.get(&id).copied().unwrap_or(0)
+    }
+
+    /// Emulate frames until a breakpoint is hit, but at most `max_frames` frames.
+    ///
+    /// Execution stops at the breakpoint like with [`Debugger::run_instructions_until_break`],
+    /// leaving the current frame unfinished. The next [`DeSmuME::cycle`] continues it.
+    pub fn run_until_break(&mut self, max_frames: u32) -> StopReason {
+        {
+            let mut state = self.emu.breakpoints.state.lock().unwrap();
+            state.pending = None;
+            state.stop_on_hit = true;
+        }
+        let mut reason = StopReason::FrameLimit;
+        for _ in 0..max_frames {
+            self.emu.emulate_frame();
+            // A hit stops the frame early, it only counts as a frame once `cycle` finished it.
+            if let Some(hit) = self.take_hit() {
+                reason = StopReason::Breakpoint(hit);
+                break;
+            }
+            self.emu.on_frame_end();
+        }
+        self.emu.breakpoints.state.lock().unwrap().stop_on_hit = false;
+        reason
+    }
+
+    /// The address of the next instruction the processor will execute.
+    pub fn instruction_address(&self, processor: Processor) -> u32 {
+        unsafe { desmume_cpu_get_instruction_address(processor.id()) }
+    }
+
+    /// Continue execution at `address`, staying in the current mode (ARM or Thumb).
+    pub fn set_instruction_address(&mut self, processor: Processor, address: u32) {
+        let thumb = self.emu.memory().cpsr(processor).thumb as u32;
+        unsafe { desmume_cpu_set_instruction_address(processor.id(), address & !1 | thumb) };
+    }
+
+    /// Execute exactly one instruction on the given processor and return the address of the
+    /// next one.
+    ///
+    /// The other processor and the rest of the hardware are advanced to the same point in time,
+    /// and [`DeSmuME::cycle`] continues the current frame from there. Breakpoints are counted
+    /// while stepping, but don't stop execution.
+    pub fn step_instruction(&mut self, processor: Processor) -> u32 {
+        let pc = unsafe { desmume_cpu_step(processor.id()) };
+        self.take_hit();
+        pc
+    }
+
+    /// Execute `count` instructions on the given processor and return the address of the next
+    /// one. See [`Debugger::step_instruction`].
+    pub fn run_instructions(&mut self, processor: Processor, count: u32) -> u32 {
+        let pc = unsafe { desmume_cpu_run_instructions(processor.id(), count) };
+        self.take_hit();
+        pc
+    }
+
+    /// Execute instructions on the given processor until a breakpoint is hit, but at most
+    /// `max_instructions` instructions.
+    ///
+    /// Unlike [`Debugger::run_until_break`], this stops right at the breakpoint: for execution
+    /// breakpoints before the instruction is executed, for read and write breakpoints after the
+    /// accessing instruction. The current instruction is always executed first, so running again
+    /// after hitting an execution breakpoint does not hit it again right away. Other breakpoints
+    /// hit by the current instruction are still reported.
+    pub fn run_instructions_until_break(
+        &mut self,
+        processor: Processor,
+        max_instructions: u32,
+    ) -> StopReason {
+        if max_instructions == 0 {
+            return StopReason::InstructionLimit;
+        }
+        {
+            let mut state = self.emu.breakpoints.state.lock().unwrap();
+            state.pending = None;
+            state.step_over = Some((processor, self.instruction_address(processor)));
+        }
+        unsafe { desmume_cpu_step(processor.id()) };
+        self.emu.breakpoints.state.lock().unwrap().step_over = None;
+        if let Some(hit) = self.take_hit() {
+            return StopReason::Breakpoint(hit);
+        }
+        if max_instructions > 1 {
+            self.emu.breakpoints.state.lock().unwrap().stop_on_hit = true;
+            unsafe { desmume_cpu_run_instructions(processor.id(), max_instructions - 1) };
+            self.emu.breakpoints.state.lock().unwrap().stop_on_hit = false;
+        }
+        match self.take_hit() {
+            Some(hit) => StopReason::Breakpoint(hit),
+            None => StopReason::InstructionLimit,
+        }
+    }
+
+    /// Execute the next instruction. If it is a function call, run until the function returned.
+    ///
+    /// Stops early if a breakpoint is hit or after `max_instructions` instructions.
+    pub fn step_over(&mut self, processor: Processor, max_instructions: u32) -> StopReason {
+        self.take_hit();
+        let address = self.instruction_address(processor);
+        let Flow::Call { return_address, .. } = self.flow(processor, address) else {
+            let pc = self.step_instruction(processor);
+            return StopReason::Stepped(pc);
+        };
+        let sp = self.emu.memory().get_reg(processor, Register::SP);
+        for _ in 0..max_instructions {
+            let pc = unsafe { desmume_cpu_step(processor.id()) };
+            if let Some(hit) = self.take_hit() {
+                return StopReason::Breakpoint(hit);
+            }
+            // Checking the stack pointer skips over recursive calls of the same function.
+            if pc == return_address && self.emu.memory().get_reg(processor, Register::SP) >= sp {
+                return StopReason::Stepped(pc);
+            }
+        }
+        StopReason::InstructionLimit
+    }
+
+    /// Run until the current function returned.
+    ///
+    /// Stops early if a breakpoint is hit or after `max_instructions` instructions.
+    pub fn step_out(&mut self, processor: Processor, max_instructions: u32) -> StopReason {
+        self.take_hit();
+        let sp = self.emu.memory().get_reg(processor, Register::SP);
+        let mut depth = 0u32;
+        for _ in 0..max_instructions {
+            let address = self.instruction_address(processor);
+            let flow = self.flow(processor, address);
+            let pc = unsafe { desmume_cpu_step(processor.id()) };
+            if let Some(hit) = self.take_hit() {
+                return StopReason::Breakpoint(hit);
+            }
+            // Conditional instructions that were not executed fall through.
+            let taken = pc != address + flow.length();
+            match flow {
+                Flow::Call { .. } if taken => depth += 1,
+                Flow::Return { .. } if taken => {
+                    if depth == 0 {
+                        if self.emu.memory().get_reg(processor, Register::SP) >= sp {
+                            return StopReason::Stepped(pc);
+                        }
+                    } else {
+                        depth -= 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        StopReason::InstructionLimit
+    }
+
+    fn take_hit(&mut self) -> Option<BreakpointHit> {
+        self.emu.breakpoints.state.lock().unwrap().pending.take()
+    }
+
+    fn flow(&self, processor: Processor, address: u32) -> Flow {
+        if self.emu.memory().cpsr(processor).thumb {
+            let mut opcode = [0; 2];
+            read_block(processor, address, &mut opcode);
+            Flow::thumb(address, u16::from_le_bytes(opcode))
+        } else {
+            let mut opcode = [0; 4];
+            read_block(processor, address, &mut opcode);
+            Flow::arm(address, u32::from_le_bytes(opcode))
+        }
+    }
+}
@@ -0,0 +1,870 @@
+//! Disassembler for the ARMv5TE (ARM9) and ARMv4T (ARM7) instruction sets, in ARM and Thumb mode.
+//!
+//! Use [`crate::DeSmuMEMemory::disassemble`] to disassemble code in the emulated memory, or
+//! [`disassemble`] to disassemble a buffer.
+
+use crate::mem::Processor;
+use crate::symbols::{Symbolize, Symbols};
+use std::fmt::{Display, Formatter};
+
+/// The instruction set the code is decoded as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Arm,
+    Thumb,
+}
+
+/// The architecture version of a processor. Instructions that were added in ARMv5TE are decoded
+/// as undefined for ARMv4T.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Architecture {
+    /// The ARM7TDMI.
+    ArmV4T,
+    /// The ARM946E-S.
+    ArmV5TE,
+}
+
+impl From<Processor> for Architecture {
+    fn from(processor: Processor) -> Self {
+        match processor {
+            Processor::Arm9 => Architecture::ArmV5TE,
+            Processor::Arm7 => Architecture::ArmV4T,
+        }
+    }
+}
+
+/// A decoded instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Instruction {
+    /// Address of the instruction.
+    pub address: u32,
+    /// The raw instruction. For Thumb BL / BLX pairs the first half is in the lower 16 bits.
+    pub opcode: u32,
+    pub mode: Mode,
+    /// Length of the instruction in bytes.
+    pub length: u32,
+    /// The mnemonic including the condition and flag suffixes, eg. `addseq`.
+    pub mnemonic: String,
+    /// The operands, eg. `r0, r1, #0x10`. Empty if the instruction has none.
+    pub operands: String,
+    /// The target of direct branches.
+    pub branch_target: Option<u32>,
+}
+
+impl Instruction {
+    fn new(address: u32, opcode: u32, mode: Mode, length: u32) -> Self {
+        Self {
+            address,
+            opcode,
+            mode,
+            length,
+            mnemonic: String::new(),
+            operands: String::new(),
+            branch_target: None,
+        }
+    }
+
+    fn with(mut self, mnemonic: impl Into<String>, operands: impl Into<String>) -> Self {
+        self.mnemonic = mnemonic.into();
+        self.operands = operands.into();
+        self
+    }
+
+    fn undefined(self) -> Self {
+        self.with("undefined", "")
+    }
+
+    fn branch(mut self, mnemonic: impl Into<String>, target: u32) -> Self {
+        self.mnemonic = mnemonic.into();
+        self.operands = format!("0x{target:08x}");
+        self.branch_target = Some(target);
+        self
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.operands.is_empty() {
+            f.write_str(&self.mnemonic)
+        } else {
+            write!(f, "{} {}", self.mnemonic, self.operands)
+        }
+    }
+}
+
+/// Prints branch targets as symbols, eg. `bl GameMain+0x10`.
+impl Symbolize for Instruction {
+    fn symbolize(&self, symbols: &Symbols) -> String {
+        match self.branch_target {
+            Some(target) if symbols.resolve(target).is_some() => {
+                let operands = self
+                    .operands
+                    .replace(&format!("0x{target:08x}"), &symbols.symbolize(target));
+                format!("{} {}", self.mnemonic, operands)
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// Disassemble the code in `bytes`, which is located at `address`. Stops at the end of the
+/// buffer; a trailing incomplete instruction is ignored.
+pub fn disassemble(bytes: &[u8], address: u32, mode: Mode, arch: Architecture) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    match mode {
+        Mode::Arm => {
+            for chunk in bytes.chunks_exact(4) {
+                let opcode = u32::from_le_bytes(chunk.try_into().unwrap());
+                instructions.push(decode_arm(address.wrapping_add(offset), opcode, arch));
+                offset += 4;
+            }
+        }
+        Mode::Thumb => {
+            let halfwords: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            let mut i = 0;
+            while i < halfwords.len() {
+                let instruction = decode_thumb(
+                    address.wrapping_add(offset),
+                    halfwords[i],
+                    halfwords.get(i + 1).copied(),
+                    arch,
+                );
+                i += instruction.length as usize / 2;
+                offset += instruction.length;
+                instructions.push(instruction);
+            }
+        }
+    }
+    instructions
+}
+
+const REGISTERS: [&str; 16] = [
+    "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11", "r12", "sp", "lr",
+    "pc",
+];
+
+const CONDITIONS: [&str; 16] = [
+    "eq", "ne", "cs", "cc", "mi", "pl", "vs", "vc", "hi", "ls", "ge", "lt", "gt", "le", "", "nv",
+];
+
+const DATA_PROCESSING: [&str; 16] = [
+    "and", "eor", "sub", "rsb", "add", "adc", "sbc", "rsc", "tst", "teq", "cmp", "cmn", "orr",
+    "mov", "bic", "mvn",
+];
+
+const SHIFTS: [&str; 4] = ["lsl", "lsr", "asr", "ror"];
+
+fn reg(n: u32) -> &'static str {
+    REGISTERS[(n & 0xF) as usize]
+}
+
+fn bit(opcode: u32, n: u32) -> bool {
+    opcode & (1 << n) != 0
+}
+
+fn bits(opcode: u32, low: u32, count: u32) -> u32 {
+    (opcode >> low) & ((1 << count) - 1)
+}
+
+fn sign_extend(value: u32, bits: u32) -> u32 {
+    let shift = 32 - bits;
+    (((value << shift) as i32) >> shift) as u32
+}
+
+fn imm(value: u32) -> String {
+    if value < 10 {
+        format!("#{value}")
+    } else {
+        format!("#0x{value:x}")
+    }
+}
+
+fn offset_imm(value: u32, up: bool) -> String {
+    if up {
+        imm(value)
+    } else if value < 10 {
+        format!("#-{value}")
+    } else {
+        format!("#-0x{value:x}")
+    }
+}
+
+fn reg_list(list: u32) -> String {
+    let mut parts = Vec::new();
+    let mut n = 0;
+    while n < 16 {
+        if !bit(list, n) {
+            n += 1;
+            continue;
+        }
+        let start = n;
+        while n + 1 < 16 && bit(list, n + 1) {
+            n += 1;
+        }
+        match n - start {
+            0 => parts.push(reg(start).to_string()),
+            1 => parts.push(format!("{}, {}", reg(start), reg(n))),
+            _ => parts.push(format!("{}-{}", reg(start), reg(n))),
+        }
+        n += 1;
+    }
+    format!("{{{}}}", parts.join(", "))
+}
+
+/// `[rn, offset]{!}` or `[rn], offset`
+fn address_mode(rn: u32, offset: Option<String>, pre: bool, writeback: bool) -> String {
+    match (offset, pre) {
+        (None, _) => format!("[{}]", reg(rn)),
+        (Some(offset), true) => {
+            format!(
+                "[{}, {offset}]{}",
+                reg(rn),
+                if writeback { "!" } else { "" }
+            )
+        }
+        (Some(offset), false) => format!("[{}], {offset}", reg(rn)),
+    }
+}
+
+/// Formats a register shifted by an immediate, as used by data processing and single data
+/// transfer instructions.
+fn shifted_reg(opcode: u32) -> String {
+    let rm = reg(bits(opcode, 0, 4));
+    let kind = bits(opcode, 5, 2);
+    let amount = bits(opcode, 7, 5);
+    match (kind, amount) {
+        (0, 0) => rm.to_string(),
+        (3, 0) => format!("{rm}, rrx"),
+        (1 | 2, 0) => format!("{rm}, {} #32", SHIFTS[kind as usize]),
+        _ => format!("{rm}, {} #{amount}", SHIFTS[kind as usize]),
+    }
+}
+
+/// Decode an ARM instruction.
+pub fn decode_arm(address: u32, opcode: u32, arch: Architecture) -> Instruction {
+    let inst = Instruction::new(address, opcode, Mode::Arm, 4);
+    let v5 = arch == Architecture::ArmV5TE;
+    let cond_bits = bits(opcode, 28, 4);
+    let cond = CONDITIONS[cond_bits as usize];
+
+    if cond_bits == 0xF {
+        if !v5 {
+            return inst.undefined();
+        }
+        if bits(opcode, 25, 3) == 0b101 {
+            let offset = sign_extend(bits(opcode, 0, 24), 24) << 2;
+            let h = (opcode >> 23) & 2;
+            return inst.branch("blx", address.wrapping_add(8).wrapping_add(offset) | h);
+        }
+        if opcode & 0x0D70_F000 == 0x0550_F000 {
+            return inst.with("pld", single_transfer_address(opcode));
+        }
+        return inst.undefined();
+    }
+
+    match bits(opcode, 25, 3) {
+        0b000 => {
+            if bit(opcode, 4) && bit(opcode, 7) {
+                if bits(opcode, 5, 2) == 0 {
+                    decode_arm_multiply(inst, opcode, cond)
+                } else {
+                    decode_arm_extra_transfer(inst, opcode, cond, v5)
+                }
+            } else if bits(opcode, 23, 2) == 0b10 && !bit(opcode, 20) {
+                decode_arm_misc(inst, opcode, cond, v5)
+            } else {
+                decode_arm_data_processing(inst, opcode, cond)
+            }
+        }
+        0b001 => {
+            if bits(opcode, 23, 2) == 0b10 && !bit(opcode, 20) {
+                if bit(opcode, 21) {
+                    decode_arm_msr(inst, opcode, cond)
+                } else {
+                    inst.undefined()
+                }
+            } else {
+                decode_arm_data_processing(inst, opcode, cond)
+            }
+        }
+        0b010 | 0b011 => {
+            if bit(opcode, 25) && bit(opcode, 4) {
+                return inst.undefined();
+            }
+            let load = bit(opcode, 20);
+            let byte = bit(opcode, 22);
+            let translate = !bit(opcode, 24) && bit(opcode, 21);
+            let mnemonic = format!(
+                "{}{}{}{cond}",
+                if load { "ldr" } else { "str" },
+                if byte { "b" } else { "" },
+                if translate { "t" } else { "" }
+            );
+            let operands = format!(
+                "{}, {}",
+                reg(bits(opcode, 12, 4)),
+                single_transfer_address(opcode)
+            );
+            inst.with(mnemonic, operands)
+        }
+        0b100 => {
+            let rn = bits(opcode, 16, 4);
+            let pre = bit(opcode, 24);
+            let up = bit(opcode, 23);
+            let user = bit(opcode, 22);
+            let writeback = bit(opcode, 21);
+            let load = bit(opcode, 20);
+            let list = reg_list(bits(opcode, 0, 16));
+            let caret = if user { "^" } else { "" };
+            if rn == 13 && writeback && !user {
+                if !load && pre && !up {
+                    return inst.with(format!("push{cond}"), list);
+                }
+                if load && !pre && up {
+                    return inst.with(format!("pop{cond}"), list);
+                }
+            }
+            let mode = match (pre, up) {
+                (false, true) => "ia",
+                (true, true) => "ib",
+                (false, false) => "da",
+                (true, false) => "db",
+            };
+            let mnemonic = format!("{}{mode}{cond}", if load { "ldm" } else { "stm" });
+            let operands = format!(
+                "{}{}, {list}{caret}",
+                reg(rn),
+                if writeback { "!" } else { "" }
+            );
+            inst.with(mnemonic, operands)
+        }
+        0b101 => {
+            let offset = sign_extend(bits(opcode, 0, 24), 24) << 2;
+            let target = address.wrapping_add(8).wrapping_add(offset);
+            let link = if bit(opcode, 24) { "l" } else { "" };
+            inst.branch(format!("b{link}{cond}"), target)
+        }
+        0b110 => {
+            if bits(opcode, 21, 4) == 0b0010 {
+                // MCRR / MRRC
+                if !v5 {
+                    return inst.undefined();
+                }
+                let mnemonic = if bit(opcode, 20) { "mrrc" } else { "mcrr" };
+                let operands = format!(
+                    "p{}, {}, {}, {}, c{}",
+                    bits(opcode, 8, 4),
+                    bits(opcode, 4, 4),
+                    reg(bits(opcode, 12, 4)),
+                    reg(bits(opcode, 16, 4)),
+                    bits(opcode, 0, 4)
+                );
+                return inst.with(format!("{mnemonic}{cond}"), operands);
+            }
+            let pre = bit(opcode, 24);
+            let offset = offset_imm(bits(opcode, 0, 8) * 4, bit(opcode, 23));
+            let mnemonic = format!(
+                "{}{}{cond}",
+                if bit(opcode, 20) { "ldc" } else { "stc" },
+                if bit(opcode, 22) { "l" } else { "" }
+            );
+            let operands = format!(
+                "p{}, c{}, {}",
+                bits(opcode, 8, 4),
+                bits(opcode, 12, 4),
+                address_mode(bits(opcode, 16, 4), Some(offset), pre, bit(opcode, 21))
+            );
+            inst.with(mnemonic, operands)
+        }
+        _ => {
+            if bit(opcode, 24) {
+                return inst.with(format!("swi{cond}"), imm(bits(opcode, 0, 24)));
+            }
+            let cp = bits(opcode, 8, 4);
+            let crn = bits(opcode, 16, 4);
+            let crm = bits(opcode, 0, 4);
+            let op2 = bits(opcode, 5, 3);
+            if bit(opcode, 4) {
+                let mnemonic = if bit(opcode, 20) { "mrc" } else { "mcr" };
+                let operands = format!(
+                    "p{cp}, {}, {}, c{crn}, c{crm}, {op2}",
+                    bits(opcode, 21, 3),
+                    reg(bits(opcode, 12, 4))
+                );
+                inst.with(format!("{mnemonic}{cond}"), operands)
+            } else {
+                let operands = format!(
+                    "p{cp}, {}, c{}, c{crn}, c{crm}, {op2}",
+                    bits(opcode, 20, 4),
+                    bits(opcode, 12, 4)
+                );
+                inst.with(format!("cdp{cond}"), operands)
+            }
+        }
+    }
+}
+
+/// The address operand of LDR / STR / PLD.
+fn single_transfer_address(opcode: u32) -> String {
+    let up = bit(opcode, 23);
+    let offset = if bit(opcode, 25) {
+        format!("{}{}", if up { "" } else { "-" }, shifted_reg(opcode))
+    } else {
+        let value = bits(opcode, 0, 12);
+        if value == 0 && up {
+            return address_mode(bits(opcode, 16, 4), None, true, bit(opcode, 21));
+        }
+        offset_imm(value, up)
+    };
+    address_mode(
+        bits(opcode, 16, 4),
+        Some(offset),
+        bit(opcode, 24),
+        bit(opcode, 21),
+    )
+}
+
+fn decode_arm_data_processing(inst: Instruction, opcode: u32, cond: &str) -> Instruction {
+    let op = bits(opcode, 21, 4);
+    let set_flags = bit(opcode, 20);
+    let rn = reg(bits(opcode, 16, 4));
+    let rd = reg(bits(opcode, 12, 4));
+    let operand2 = if bit(opcode, 25) {
+        let rotate = bits(opcode, 8, 4) * 2;
+        imm(bits(opcode, 0, 8).rotate_right(rotate))
+    } else if bit(opcode, 4) {
+        format!(
+            "{}, {} {}",
+            reg(bits(opcode, 0, 4)),
+            SHIFTS[bits(opcode, 5, 2) as usize],
+            reg(bits(opcode, 8, 4))
+        )
+    } else {
+        shifted_reg(opcode)
+    };
+    let name = DATA_PROCESSING[op as usize];
+    match op {
+        // TST, TEQ, CMP, CMN
+        0x8..=0xB => inst.with(format!("{name}{cond}"), format!("{rn}, {operand2}")),
+        // MOV, MVN
+        0xD | 0xF => {
+            let s = if set_flags { "s" } else { "" };
+            inst.with(format!("{name}{s}{cond}"), format!("{rd}, {operand2}"))
+        }
+        _ => {
+            let s = if set_flags { "s" } else { "" };
+            inst.with(
+                format!("{name}{s}{cond}"),
+                format!("{rd}, {rn}, {operand2}"),
+            )
+        }
+    }
+}
+
+fn psr_fields(opcode: u32) -> String {
+    let psr = if bit(opcode, 22) { "spsr" } else { "cpsr" };
+    let mut fields = String::new();
+    for (n, c) in [(16, 'c'), (17, 'x'), (18, 's'), (19, 'f')] {
+        if bit(opcode, n) {
+            fields.push(c);
+        }
+    }
+    format!("{psr}_{fields}")
+}
+
+fn decode_arm_msr(inst: Instruction, opcode: u32, cond: &str) -> Instruction {
+    let source = if bit(opcode, 25) {
+        imm(bits(opcode, 0, 8).rotate_right(bits(opcode, 8, 4) * 2))
+    } else {
+        reg(bits(opcode, 0, 4)).to_string()
+    };
+    inst.with(
+        format!("msr{cond}"),
+        format!("{}, {source}", psr_fields(opcode)),
+    )
+}
+
+/// MRS, MSR, BX, BLX, CLZ, the saturating arithmetic, BKPT and the signed halfword multiplies.
+fn decode_arm_misc(inst: Instruction, opcode: u32, cond: &str, v5: bool) -> Instruction {
+    let rd = bits(opcode, 12, 4);
+    let rn = bits(opcode, 16, 4);
+    let rs = bits(opcode, 8, 4);
+    let rm = bits(opcode, 0, 4);
+    if opcode & 0x0FBF_0FFF == 0x010F_0000 {
+        let psr = if bit(opcode, 22) { "spsr" } else { "cpsr" };
+        return inst.with(format!("mrs{cond}"), format!("{}, {psr}", reg(rd)));
+    }
+    if opcode & 0x0FB0_FFF0 == 0x0120_F000 {
+        return decode_arm_msr(inst, opcode, cond);
+    }
+    if opcode & 0x0FFF_FFF0 == 0x012F_FF10 {
+        return inst.with(format!("bx{cond}"), reg(rm));
+    }
+    if !v5 {
+        return inst.undefined();
+    }
+    if opcode & 0x0FFF_FFF0 == 0x012F_FF30 {
+        return inst.with(format!("blx{cond}"), reg(rm));
+    }
+    if opcode & 0x0FFF_0FF0 == 0x016F_0F10 {
+        return inst.with(format!("clz{cond}"), format!("{}, {}", reg(rd), reg(rm)));
+    }
+    if opcode & 0x0F90_0FF0 == 0x0100_0050 {
+        let name = ["qadd", "qsub", "qdadd", "qdsub"][bits(opcode, 21, 2) as usize];
+        return inst.with(
+            format!("{name}{cond}"),
+            format!("{}, {}, {}", reg(rd), reg(rm), reg(rn)),
+        );
+    }
+    if opcode & 0xFFF0_00F0 == 0xE120_0070 {
+        return inst.with("bkpt", imm((bits(opcode, 8, 12) << 4) | rm));
+    }
+    if opcode & 0x0F90_0090 == 0x0100_0080 {
+        let x = if bit(opcode, 5) { "t" } else { "b" };
+        let y = if bit(opcode, 6) { "t" } else { "b" };
+        return match bits(opcode, 21, 2) {
+            0 => inst.with(
+                format!("smla{x}{y}{cond}"),
+                format!("{}, {}, {}, {}", reg(rn), reg(rm), reg(rs), reg(rd)),
+            ),
+            1 if !bit(opcode, 5) => inst.with(
+                format!("smlaw{y}{cond}"),
+                format!("{}, {}, {}, {}", reg(rn), reg(rm), reg(rs), reg(rd)),
+            ),
+            1 => inst.with(
+                format!("smulw{y}{cond}"),
+                format!("{}, {}, {}", reg(rn), reg(rm), reg(rs)),
+            ),
+            2 => inst.with(
+                format!("smlal{x}{y}{cond}"),
+                format!("{}, {}, {}, {}", reg(rd), reg(rn), reg(rm), reg(rs)),
+            ),
+            _ => inst.with(
+                format!("smul{x}{y}{cond}"),
+                format!("{}, {}, {}", reg(rn), reg(rm), reg(rs)),
+            ),
+        };
+    }
+    inst.undefined()
+}
+
+fn decode_arm_multiply(inst: Instruction, opcode: u32, cond: &str) -> Instruction {
+    let rd = bits(opcode, 16, 4);
+    let rn = bits(opcode, 12, 4);
+    let rs = bits(opcode, 8, 4);
+    let rm = bits(opcode, 0, 4);
+    let s = if bit(opcode, 20) { "s" } else { "" };
+    match bits(opcode, 23, 5) {
+        0b00000 => {
+            if bit(opcode, 21) {
+                inst.with(
+                    format!("mla{s}{cond}"),
+                    format!("{}, {}, {}, {}", reg(rd), reg(rm), reg(rs), reg(rn)),
+                )
+            } else {
+                inst.with(
+                    format!("mul{s}{cond}"),
+                    format!("{}, {}, {}", reg(rd), reg(rm), reg(rs)),
+                )
+            }
+        }
+        0b00001 => {
+            let sign = if bit(opcode, 22) { "s" } else { "u" };
+            let op = if bit(opcode, 21) { "mlal" } else { "mull" };
+            inst.with(
+                format!("{sign}{op}{s}{cond}"),
+                format!("{}, {}, {}, {}", reg(rn), reg(rd), reg(rm), reg(rs)),
+            )
+        }
+        0b00010 if bits(opcode, 20, 2) == 0 && bits(opcode, 8, 4) == 0 => {
+            let b = if bit(opcode, 22) { "b" } else { "" };
+            inst.with(
+                format!("swp{b}{cond}"),
+                format!("{}, {}, [{}]", reg(rn), reg(rm), reg(rd)),
+            )
+        }
+        _ => inst.undefined(),
+    }
+}
+
+/// LDRH, STRH, LDRSB, LDRSH, LDRD and STRD.
+fn decode_arm_extra_transfer(inst: Instruction, opcode: u32, cond: &str, v5: bool) -> Instruction {
+    let load = bit(opcode, 20);
+    let name = match (load, bits(opcode, 5, 2)) {
+        (true, 1) => "ldrh",
+        (true, 2) => "ldrsb",
+        (true, _) => "ldrsh",
+        (false, 1) => "strh",
+        (false, 2) if v5 => "ldrd",
+        (false, 3) if v5 => "strd",
+        _ => return inst.undefined(),
+    };
+    let up = bit(opcode, 23);
+    let offset = if bit(opcode, 22) {
+        let value = (bits(opcode, 8, 4) << 4) | bits(opcode, 0, 4);
+        (value != 0 || !up).then(|| offset_imm(value, up))
+    } else {
+        Some(format!(
+            "{}{}",
+            if up { "" } else { "-" },
+            reg(bits(opcode, 0, 4))
+        ))
+    };
+    let rd = bits(opcode, 12, 4);
+    let target = if name.ends_with('d') {
+        format!("{}, {}", reg(rd), reg(rd + 1))
+    } else {
+        reg(rd).to_string()
+    };
+    let address = address_mode(
+        bits(opcode, 16, 4),
+        offset,
+        bit(opcode, 24),
+        bit(opcode, 21),
+    );
+    inst.with(format!("{name}{cond}"), format!("{target}, {address}"))
+}
+
+/// Decode a Thumb instruction. `next` is the following halfword, which is needed to decode
+/// BL / BLX pairs as a single instruction.
+pub fn decode_thumb(
+    address: u32,
+    opcode: u16,
+    next: Option<u16>,
+    arch: Architecture,
+) -> Instruction {
+    let v5 = arch == Architecture::ArmV5TE;
+    let op = opcode as u32;
+    let inst = Instruction::new(address, op, Mode::Thumb, 2);
+    let low = |n: u32| reg(bits(op, n, 3));
+
+    match bits(op, 11, 5) {
+        0b00000..=0b00010 => {
+            let kind = bits(op, 11, 2);
+            let mut amount = bits(op, 6, 5);
+            if kind != 0 && amount == 0 {
+                amount = 32;
+            }
+            inst.with(
+                SHIFTS[kind as usize],
+                format!("{}, {}, #{amount}", low(0), low(3)),
+            )
+        }
+        0b00011 => {
+            let name = if bit(op, 9) { "sub" } else { "add" };
+            let operand = if bit(op, 10) {
+                imm(bits(op, 6, 3))
+            } else {
+                low(6).to_string()
+            };
+            inst.with(name, format!("{}, {}, {operand}", low(0), low(3)))
+        }
+        0b00100..=0b00111 => {
+            let name = ["mov", "cmp", "add", "sub"][bits(op, 11, 2) as usize];
+            inst.with(name, format!("{}, {}", low(8), imm(bits(op, 0, 8))))
+        }
+        0b01000 => {
+            if !bit(op, 10) {
+                let name = [
+                    "and", "eor", "lsl", "lsr", "asr", "adc", "sbc", "ror", "tst", "neg", "cmp",
+                    "cmn", "orr", "mul", "bic", "mvn",
+                ][bits(op, 6, 4) as usize];
+                return inst.with(name, format!("{}, {}", low(0), low(3)));
+            }
+            let rd = bits(op, 0, 3) | (bits(op, 7, 1) << 3);
+            let rm = reg(bits(op, 3, 4));
+            match bits(op, 8, 2) {
+                0 => inst.with("add", format!("{}, {rm}", reg(rd))),
+                1 => inst.with("cmp", format!("{}, {rm}", reg(rd))),
+                2 => inst.with("mov", format!("{}, {rm}", reg(rd))),
+                _ if bit(op, 7) => {
+                    if v5 {
+                        inst.with("blx", rm)
+                    } else {
+                        inst.undefined()
+                    }
+                }
+                _ => inst.with("bx", rm),
+            }
+        }
+        0b01001 => inst.with(
+            "ldr",
+            format!("{}, [pc, {}]", low(8), imm(bits(op, 0, 8) * 4)),
+        ),
+        0b01010 | 0b01011 => {
+            let name = [
+                "str", "strh", "strb", "ldrsb", "ldr", "ldrh", "ldrb", "ldrsh",
+            ][bits(op, 9, 3) as usize];
+            inst.with(name, format!("{}, [{}, {}]", low(0), low(3), low(6)))
+        }
+        0b01100..=0b01111 => {
+            let byte = bit(op, 12);
+            let name = match (bit(op, 11), byte) {
+                (false, false) => "str",
+                (false, true) => "strb",
+                (true, false) => "ldr",
+                (true, true) => "ldrb",
+            };
+            let offset = bits(op, 6, 5) * if byte { 1 } else { 4 };
+            inst.with(name, thumb_imm_address(low(0), low(3), offset))
+        }
+        0b10000 | 0b10001 => {
+            let name = if bit(op, 11) { "ldrh" } else { "strh" };
+            inst.with(name, thumb_imm_address(low(0), low(3), bits(op, 6, 5) * 2))
+        }
+        0b10010 | 0b10011 => {
+            let name = if bit(op, 11) { "ldr" } else { "str" };
+            inst.with(name, thumb_imm_address(low(8), "sp", bits(op, 0, 8) * 4))
+        }
+        0b10100 | 0b10101 => {
+            let base = if bit(op, 11) { "sp" } else { "pc" };
+            inst.with(
+                "add",
+                format!("{}, {base}, {}", low(8), imm(bits(op, 0, 8) * 4)),
+            )
+        }
+        0b10110 | 0b10111 => match bits(op, 8, 4) {
+            0b0000 => {
+                let name = if bit(op, 7) { "sub" } else { "add" };
+                inst.with(name, format!("sp, {}", imm(bits(op, 0, 7) * 4)))
+            }
+            0b0100 | 0b0101 => {
+                let lr = (bits(op, 8, 1)) << 14;
+                inst.with("push", reg_list(bits(op, 0, 8) | lr))
+            }
+            0b1100 | 0b1101 => {
+                let pc = (bits(op, 8, 1)) << 15;
+                inst.with("pop", reg_list(bits(op, 0, 8) | pc))
+            }
+            0b1110 if v5 => inst.with("bkpt", imm(bits(op, 0, 8))),
+            _ => inst.undefined(),
+        },
+        0b11000 | 0b11001 => {
+            let name = if bit(op, 11) { "ldmia" } else { "stmia" };
+            inst.with(name, format!("{}!, {}", low(8), reg_list(bits(op, 0, 8))))
+        }
+        0b11010 | 0b11011 => match bits(op, 8, 4) {
+            0b1111 => inst.with("swi", imm(bits(op, 0, 8))),
+            0b1110 => inst.undefined(),
+            cond => {
+                let offset = sign_extend(bits(op, 0, 8), 8) << 1;
+                let target = address.wrapping_add(4).wrapping_add(offset);
+                inst.branch(format!("b{}", CONDITIONS[cond as usize]), target)
+            }
+        },
+        0b11100 => {
+            let offset = sign_extend(bits(op, 0, 11), 11) << 1;
+            inst.branch("b", address.wrapping_add(4).wrapping_add(offset))
+        }
+        0b11110 => {
+            let high = sign_extend(bits(op, 0, 11), 11) << 12;
+            match next.map(u32::from) {
+                Some(second) if bits(second, 11, 5) == 0b11111 => {
+                    let inst = Instruction::new(address, op | (second << 16), Mode::Thumb, 4);
+                    let target = address
+                        .wrapping_add(4)
+                        .wrapping_add(high)
+                        .wrapping_add(bits(second, 0, 11) << 1);
+                    inst.branch("bl", target)
+                }
+                Some(second) if v5 && bits(second, 11, 5) == 0b11101 && !bit(second, 0) => {
+                    let inst = Instruction::new(address, op | (second << 16), Mode::Thumb, 4);
+                    let target = address
+                        .wrapping_add(4)
+                        .wrapping_add(high)
+                        .wrapping_add(bits(second, 0, 11) << 1);
+                    inst.branch("blx", target & !3)
+                }
+                // A lone first half only sets up LR.
+                _ => {
+                    let offset = (high as i32).unsigned_abs();
+                    let name = if (high as i32) < 0 { "sub" } else { "add" };
+                    inst.with(name, format!("lr, pc, {}", imm(offset)))
+                }
+            }
+        }
+        0b11111 => inst.with("bl", format!("lr, {}", imm(bits(op, 0, 11) << 1))),
+        _ => {
+            // 0b11101: second half of BLX
+            if v5 && !bit(op, 0) {
+                inst.with("blx", format!("lr, {}", imm(bits(op, 0, 11) << 1)))
+            } else {
+                inst.undefined()
+            }
+        }
+    }
+}
+
+fn thumb_imm_address(rd: &str, rn: &str, offset: u32) -> String {
+    if offset == 0 {
+        format!("{rd}, [{rn}]")
+    } else {
+        format!("{rd}, [{rn}, {}]", imm(offset))
+    }
+}
+
+/// How an instruction affects the call stack. Used for stepping and call stack tracking.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Flow {
+    Call { length: u32, return_address: u32 },
+    Return { length: u32 },
+    Other { length: u32 },
+}
+
+impl Flow {
+    pub(crate) fn length(self) -> u32 {
+        match self {
+            Flow::Call { length, .. } | Flow::Return { length } | Flow::Other { length } => length,
+        }
+    }
+
+    pub(crate) fn arm(address: u32, opcode: u32) -> Self {
+        let call = Flow::Call {
+            length: 4,
+            return_address: address + 4,
+        };
+        let is_call = opcode & 0xFE00_0000 == 0xFA00_0000 // BLX <imm>
+            || (opcode >> 28 != 0xF && opcode & 0x0F00_0000 == 0x0B00_0000) // BL
+            || opcode & 0x0FFF_FFF0 == 0x012F_FF30; // BLX <reg>
+        let is_return = opcode & 0x0FFF_FFFF == 0x012F_FF1E // BX LR
+            || opcode & 0x0FFF_FFFF == 0x01A0_F00E // MOV PC, LR
+            || opcode & 0x0FFF_FFFF == 0x049D_F004 // LDR PC, [SP], #4
+            || opcode & 0x0E1F_8000 == 0x081D_8000; // LDM SP, {.., PC}
+        if is_call {
+            call
+        } else if is_return {
+            Flow::Return { length: 4 }
+        } else {
+            Flow::Other { length: 4 }
+        }
+    }
+
+    pub(crate) fn thumb(address: u32, opcode: u16) -> Self {
+        if opcode & 0xF800 == 0xF000 {
+            // First half of BL / BLX. The call happens in the second half.
+            Flow::Call {
+                length: 2,
+                return_address: address + 4,
+            }
+        } else if opcode & 0xF800 == 0xF800
+            || opcode & 0xF800 == 0xE800
+            || opcode & 0xFF87 == 0x4780
+        {
+            // Second half of BL / BLX or BLX <reg>.
+            Flow::Call {
+                length: 2,
+                return_address: address + 2,
+            }
+        } else if opcode & 0xFF00 == 0xBD00 || opcode == 0x4770 {
+            // POP {.., PC} or BX LR.
+            Flow::Return { length: 2 }
+        } else {
+            Flow::Other { length: 2 }
+        }
+    }
+}
@@ -17,6 +17,18 @@ pub enum DeSmuMEError {
     LoadSavestateFailed,
     #[error("Failed to save savestate.")]
     SaveSavestateFailed,
+    #[error("No rewind state is available.")]
+    NoRewindState,
+    #[error("The data is not a valid trace.")]
+    InvalidTrace,
+    #[error("Invalid symbol definition in line {line}: {reason}")]
+    InvalidSymbols { line: usize, reason: String },
+    #[error("Unknown symbol: {0}")]
+    UnknownSymbol(String),
+    #[error("Invalid ROM: {0}")]
+    InvalidRom(String),
+    #[error("The called function did not return.")]
+    FunctionCallTimeout,
     #[error("{0}")]
     MoviePlayError(String),
     #[error("No movie is active.")]
@@ -33,6 +45,8 @@ pub enum DeSmuMEError {
     FailedMetalInit,
     #[error("Null error while trying to convert string.")]
     NulError(#[source] NulError),
+    #[error("Failed to read file.")]
+    Io(#[source] std::io::Error),
 }
 
 impl From<NulError> for DeSmuMEError {
@@ -40,3 +54,9 @@ impl From<NulError> for DeSmuMEError {
         Self::NulError(e)
     }
 }
+
+impl From<std::io::Error> for DeSmuMEError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
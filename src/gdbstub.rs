@@ -0,0 +1,446 @@
+//! A server for the GDB remote serial protocol, to debug games with GDB (eg. `gdb-multiarch`).
+//!
+//! # Usage example
+//! ```rs
+//! use rs_desmume::gdbstub::GdbStub;
+//! use rs_desmume::mem::Processor;
+//!
+//! let stub = GdbStub::bind("127.0.0.1:2345", Processor::Arm9)?;
+//! // In GDB: `target remote localhost:2345`
+//! stub.serve(&mut emu)?;
+//! ```
+
+use crate::debugger::{Breakpoint, BreakpointId, StopReason};
+use crate::mem::{read_block, write_block, Processor, Register};
+use crate::DeSmuME;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+/// Number of instructions executed between checks for an interrupt from GDB while continuing.
+const CONTINUE_CHUNK: u32 = 100_000;
+
+/// Maximum packet size advertised to GDB, in bytes.
+const PACKET_SIZE: usize = 0x4000;
+
+/// Maximum length of a memory read or write. The data is hex encoded, so it takes up two bytes
+/// of a packet per byte.
+const MAX_MEMORY_LENGTH: usize = PACKET_SIZE / 2;
+
+/// Registers in the order of the `g` packet, as described by [`TARGET_XML`].
+const REGISTER_COUNT: usize = 17;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <architecture>arm</architecture>
+  <feature name="org.gnu.gdb.arm.core">
+    <reg name="r0" bitsize="32"/>
+    <reg name="r1" bitsize="32"/>
+    <reg name="r2" bitsize="32"/>
+    <reg name="r3" bitsize="32"/>
+    <reg name="r4" bitsize="32"/>
+    <reg name="r5" bitsize="32"/>
+    <reg name="r6" bitsize="32"/>
+    <reg name="r7" bitsize="32"/>
+    <reg name="r8" bitsize="32"/>
+    <reg name="r9" bitsize="32"/>
+    <reg name="r10" bitsize="32"/>
+    <reg name="r11" bitsize="32"/>
+    <reg name="r12" bitsize="32"/>
+    <reg name="sp" bitsize="32" type="data_ptr"/>
+    <reg name="lr" bitsize="32"/>
+    <reg name="pc" bitsize="32" type="code_ptr"/>
+    <reg name="cpsr" bitsize="32"/>
+  </feature>
+</target>
+"#;
+
+/// Serves the GDB remote serial protocol for one processor over TCP.
+///
+/// Supports reading and writing registers and memory, software and hardware breakpoints,
+/// watchpoints, continuing, single-stepping and interrupting with Ctrl+C. Breakpoints and stepping
+/// use the [`crate::Debugger`], so breakpoints of the debugger also stop a running GDB session.
+pub struct GdbStub {
+    listener: TcpListener,
+    processor: Processor,
+}
+
+impl GdbStub {
+    /// Listen for GDB on the given address.
+    pub fn bind(addr: impl ToSocketAddrs, processor: Processor) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            processor,
+        })
+    }
+
+    /// The address the stub is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// The processor that is debugged.
+    pub fn processor(&self) -> Processor {
+        self.processor
+    }
+
+    /// Wait for GDB to connect and serve it until it detaches, kills the session or disconnects.
+    /// Breakpoints set by GDB are removed afterwards.
+    pub fn serve(&self, emu: &mut DeSmuME) -> io::Result<()> {
+        let (stream, _) = self.listener.accept()?;
+        stream.set_nodelay(true)?;
+        let mut session = Session {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            emu,
+            processor: self.processor,
+            no_ack: false,
+            breakpoints: HashMap::new(),
+        };
+        let result = session.run();
+        let mut debugger = session.emu.debugger();
+        for id in session.breakpoints.into_values().flatten() {
+            debugger.remove_breakpoint(id);
+        }
+        match result {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(()),
+            result => result,
+        }
+    }
+}
+
+struct Session<'a> {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    emu: &'a mut DeSmuME,
+    processor: Processor,
+    no_ack: bool,
+    /// Breakpoints by `Z` packet type and address.
+    breakpoints: HashMap<(u8, u32), Vec<BreakpointId>>,
+}
+
+impl Session<'_> {
+    fn run(&mut self) -> io::Result<()> {
+        loop {
+            let packet = self.read_packet()?;
+            match packet.as_bytes().first() {
+                Some(b'D') => return self.send("OK"),
+                Some(b'k') => return Ok(()),
+                _ => {
+                    let reply = self.handle(&packet)?;
+                    self.send(&reply)?;
+                }
+            }
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<u8> {
+        let byte = match self.reader.fill_buf()? {
+            [] => return Err(ErrorKind::UnexpectedEof.into()),
+            buf => buf[0],
+        };
+        self.reader.consume(1);
+        Ok(byte)
+    }
+
+    fn read_packet(&mut self) -> io::Result<String> {
+        loop {
+            // Skip acknowledgements and interrupts received while stopped.
+            while self.read_byte()? != b'$' {}
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    b'#' => break,
+                    b'}' => data.push(self.read_byte()? ^ 0x20),
+                    byte => data.push(byte),
+                }
+            }
+            let checksum = [self.read_byte()?, self.read_byte()?];
+            let valid = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                == Some(checksum_of(&data));
+            if !self.no_ack {
+                self.writer.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid {
+                return Ok(String::from_utf8_lossy(&data).into_owned());
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${data}#{:02x}", checksum_of(data.as_bytes()));
+        self.writer.write_all(packet.as_bytes())?;
+        if !self.no_ack {
+            // Resend until acknowledged.
+            while self.read_byte()? == b'-' {
+                self.writer.write_all(packet.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether GDB sent an interrupt (Ctrl+C), without blocking.
+    fn interrupted(&mut self) -> io::Result<bool> {
+        if self.reader.buffer().is_empty() {
+            self.reader.get_ref().set_nonblocking(true)?;
+            let result = self.reader.fill_buf().map(|_| ());
+            self.reader.get_ref().set_nonblocking(false)?;
+            match result {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                result => result?,
+            }
+        }
+        match self.reader.buffer().first() {
+            Some(0x03) => {
+                self.reader.consume(1);
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => Err(ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    fn handle(&mut self, packet: &str) -> io::Result<String> {
+        if packet.is_empty() {
+            return Ok(String::new());
+        }
+        let (command, args) = packet.split_at(1);
+        let reply = match command {
+            "?" => "S05".to_string(),
+            "g" => {
+                let mut reply = String::new();
+                for n in 0..REGISTER_COUNT {
+                    reply.push_str(&hex_u32(self.read_register(n)));
+                }
+                reply
+            }
+            "G" => {
+                for n in 0..REGISTER_COUNT {
+                    let Some(value) = args.get(n * 8..n * 8 + 8).and_then(parse_hex_u32) else {
+                        return Ok("E01".to_string());
+                    };
+                    self.write_register(n, value);
+                }
+                "OK".to_string()
+            }
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(n) if n < REGISTER_COUNT => hex_u32(self.read_register(n)),
+                _ => "E01".to_string(),
+            },
+            "P" => {
+                let parsed = args.split_once('=').and_then(|(n, value)| {
+                    Some((usize::from_str_radix(n, 16).ok()?, parse_hex_u32(value)?))
+                });
+                match parsed {
+                    Some((n, value)) if n < REGISTER_COUNT => {
+                        self.write_register(n, value);
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            "m" => match parse_address_length(args) {
+                Some((address, length)) => {
+                    let mut buffer = vec![0; length];
+                    read_block(self.processor, address, &mut buffer);
+                    buffer.iter().map(|b| format!("{b:02x}")).collect()
+                }
+                None => "E01".to_string(),
+            },
+            "M" => {
+                let parsed = args.split_once(':').and_then(|(range, data)| {
+                    let (address, length) = parse_address_length(range)?;
+                    let bytes = parse_hex_bytes(data)?;
+                    (bytes.len() == length).then_some((address, bytes))
+                });
+                match parsed {
+                    Some((address, bytes)) => {
+                        write_block(self.processor, address, &bytes);
+                        "OK".to_string()
+                    }
+                    None => "E01".to_string(),
+                }
+            }
+            "c" => self.resume(false)?,
+            "s" => self.resume(true)?,
+            "Z" | "z" => self.handle_breakpoint(command == "Z", args),
+            "H" => "OK".to_string(),
+            "T" => "OK".to_string(),
+            "q" | "Q" => self.handle_query(packet),
+            _ => String::new(),
+        };
+        Ok(reply)
+    }
+
+    fn handle_query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            format!("PacketSize={PACKET_SIZE:x};qXfer:features:read+;QStartNoAckMode+")
+        } else if packet == "QStartNoAckMode" {
+            self.no_ack = true;
+            "OK".to_string()
+        } else if packet == "qAttached" {
+            "1".to_string()
+        } else if packet == "qC" {
+            "QC1".to_string()
+        } else if packet == "qfThreadInfo" {
+            "m1".to_string()
+        } else if packet == "qsThreadInfo" {
+            "l".to_string()
+        } else if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((offset, length)) = parse_address_length(range) else {
+                return "E01".to_string();
+            };
+            let offset = (offset as usize).min(TARGET_XML.len());
+            let end = (offset + length).min(TARGET_XML.len());
+            let prefix = if end == TARGET_XML.len() { 'l' } else { 'm' };
+            format!("{prefix}{}", &TARGET_XML[offset..end])
+        } else {
+            String::new()
+        }
+    }
+
+    fn read_register(&mut self, n: usize) -> u32 {
+        let processor = self.processor;
+        match n {
+            // GDB expects the address of the next instruction, not the pipelined R15.
+            15 => self.emu.debugger().instruction_address(processor),
+            16 => self.emu.memory().get_reg(processor, Register::CPSR),
+            n => {
+                let register = Register::try_from(n as u32).unwrap();
+                self.emu.memory().get_reg(processor, register)
+            }
+        }
+    }
+
+    fn write_register(&mut self, n: usize, value: u32) {
+        let register = match n {
+            15 => {
+                let processor = self.processor;
+                self.emu
+                    .debugger()
+                    .set_instruction_address(processor, value);
+                return;
+            }
+            16 => Register::CPSR,
+            n => Register::try_from(n as u32).unwrap(),
+        };
+        self.emu
+            .memory_mut()
+            .set_reg(self.processor, register, value);
+    }
+
+    fn resume(&mut self, step: bool) -> io::Result<String> {
+        let processor = self.processor;
+        if step {
+            self.emu.debugger().step_instruction(processor);
+            return Ok("S05".to_string());
+        }
+        loop {
+            let reason = self
+                .emu
+                .debugger()
+                .run_instructions_until_break(processor, CONTINUE_CHUNK);
+            if let StopReason::Breakpoint(hit) = reason {
+                let watch = self
+                    .breakpoints
+                    .iter()
+                    .find(|(_, ids)| ids.contains(&hit.id))
+                    .and_then(|((kind, _), _)| match kind {
+                        2 => Some("watch"),
+                        3 => Some("rwatch"),
+                        4 => Some("awatch"),
+                        _ => None,
+                    });
+                return Ok(match watch {
+                    Some(watch) => format!("T05{watch}:{:x};", hit.address),
+                    None => "S05".to_string(),
+                });
+            }
+            if self.interrupted()? {
+                return Ok("S02".to_string());
+            }
+        }
+    }
+
+    fn handle_breakpoint(&mut self, insert: bool, args: &str) -> String {
+        let mut parts = args.split(',');
+        let (Some(kind), Some(address), Some(length)) = (
+            parts.next().and_then(|k| k.parse::<u8>().ok()),
+            parts.next().and_then(|a| u32::from_str_radix(a, 16).ok()),
+            parts.next().and_then(|l| u32::from_str_radix(l, 16).ok()),
+        ) else {
+            return "E01".to_string();
+        };
+        if kind > 4 {
+            return String::new();
+        }
+        let mut debugger = self.emu.debugger();
+        if !insert {
+            for id in self
+                .breakpoints
+                .remove(&(kind, address))
+                .unwrap_or_default()
+            {
+                debugger.remove_breakpoint(id);
+            }
+            return "OK".to_string();
+        }
+        let processor = self.processor;
+        let length = length.max(1);
+        let breakpoints = match kind {
+            0 | 1 => vec![Breakpoint::exec(processor, address)],
+            2 => vec![Breakpoint::write(processor, address, length)],
+            3 => vec![Breakpoint::read(processor, address, length)],
+            _ => vec![
+                Breakpoint::read(processor, address, length),
+                Breakpoint::write(processor, address, length),
+            ],
+        };
+        let ids = self.breakpoints.entry((kind, address)).or_default();
+        for breakpoint in breakpoints {
+            ids.push(debugger.add_breakpoint(breakpoint));
+        }
+        "OK".to_string()
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+/// Registers are transferred in target byte order.
+fn hex_u32(value: u32) -> String {
+    value
+        .to_le_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn parse_hex_u32(s: &str) -> Option<u32> {
+    let bytes: [u8; 4] = parse_hex_bytes(s)?.try_into().ok()?;
+    Some(u32::from_le_bytes(bytes))
+}
+
+fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Parses `address,length`, both in hex. Lengths above [`MAX_MEMORY_LENGTH`] are rejected.
+fn parse_address_length(s: &str) -> Option<(u32, usize)> {
+    let (address, length) = s.split_once(',')?;
+    let length = usize::from_str_radix(length, 16).ok()?;
+    if length > MAX_MEMORY_LENGTH {
+        return None;
+    }
+    Some((u32::from_str_radix(address, 16).ok()?, length))
+}
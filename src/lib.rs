@@ -7,19 +7,45 @@ use std::sync::atomic::{AtomicBool, Ordering};
 #[macro_use]
 mod macros;
 
+mod call;
+mod crash;
+mod debug_output;
+pub mod debugger;
+pub mod disasm;
 mod err;
+pub mod gdbstub;
 pub mod input;
 pub mod mem;
 mod movie;
+pub mod nitro;
+mod profiler;
+mod rewind;
 mod savestate;
 mod sdl_window;
-
+pub mod search;
+pub mod symbols;
+mod trace;
+
+pub use crate::call::CALL_SENTINEL;
+pub use crate::crash::{CrashConfig, CrashKind, CrashReport, DeSmuMECrashDetector};
+pub use crate::debug_output::{DeSmuMEDebugOutput, DebugMessage};
+use crate::debugger::Breakpoints;
+pub use crate::debugger::Debugger;
 pub use crate::err::DeSmuMEError;
 pub use crate::input::DeSmuMEInput;
+use crate::mem::clear_shadow_stacks;
 pub use crate::mem::DeSmuMEMemory;
 pub use crate::movie::DeSmuMEMovie;
+pub use crate::profiler::{
+    DeSmuMEProfiler, FunctionProfile, ProcessorProfile, Profile, ProfilerConfig,
+};
+pub use crate::rewind::{DeSmuMERewind, RewindConfig};
 pub use crate::savestate::DeSmuMESavestate;
 pub use crate::sdl_window::DeSmuMESdlWindow;
+use crate::symbols::Symbols;
+pub use crate::trace::{
+    DeSmuMETrace, RegisterDelta, Trace, TraceConfig, TraceEntry, TraceIter,
+};
 
 static WAS_EVER_ALREADY_INITIALIZED: AtomicBool = AtomicBool::new(false);
 static ALREADY_INITIALIZED: AtomicBool = AtomicBool::new(false);
@@ -112,10 +138,17 @@ impl Default for InitOptions {
 
 /// The DeSmuME emulator.
 pub struct DeSmuME {
+    breakpoints: Breakpoints,
+    crash_detector: DeSmuMECrashDetector,
+    debug_output: DeSmuMEDebugOutput,
     input: DeSmuMEInput,
     memory: DeSmuMEMemory,
     movie: DeSmuMEMovie,
+    profiler: DeSmuMEProfiler,
+    rewind: DeSmuMERewind,
     savestate: DeSmuMESavestate,
+    symbols: Symbols,
+    trace: DeSmuMETrace,
     window: Option<DeSmuMESdlWindow>,
 }
 
@@ -152,13 +185,20 @@ impl DeSmuME {
             WAS_EVER_ALREADY_INITIALIZED.store(true, Ordering::Relaxed);
         }
         Ok(Self {
+            breakpoints: Breakpoints::new(),
+            crash_detector: DeSmuMECrashDetector::new(),
+            debug_output: DeSmuMEDebugOutput::new(),
             input: DeSmuMEInput {
                 joystick_was_init: false,
                 _notsendsync: PhantomData,
             },
             memory: DeSmuMEMemory(PhantomData),
             movie: DeSmuMEMovie(PhantomData),
+            profiler: DeSmuMEProfiler::new(),
+            rewind: DeSmuMERewind::new(),
             savestate: DeSmuMESavestate(PhantomData),
+            symbols: Symbols::new(),
+            trace: DeSmuMETrace::new(),
             window: None,
         })
     }
@@ -204,13 +244,20 @@ impl DeSmuME {
         WAS_EVER_ALREADY_INITIALIZED.store(true, Ordering::Relaxed);
 
         Ok(Self {
+            breakpoints: Breakpoints::new(),
+            crash_detector: DeSmuMECrashDetector::new(),
+            debug_output: DeSmuMEDebugOutput::new(),
             input: DeSmuMEInput {
                 joystick_was_init: false,
                 _notsendsync: PhantomData,
             },
             memory: DeSmuMEMemory(PhantomData),
             movie: DeSmuMEMovie(PhantomData),
+            profiler: DeSmuMEProfiler::new(),
+            rewind: DeSmuMERewind::new(),
             savestate: DeSmuMESavestate(PhantomData),
+            symbols: Symbols::new(),
+            trace: DeSmuMETrace::new(),
             window: None,
         })
     }
@@ -238,6 +285,27 @@ impl DeSmuME {
         &mut self.input
     }
 
+    pub fn crash_detector(&self) -> &DeSmuMECrashDetector {
+        &self.crash_detector
+    }
+
+    pub fn crash_detector_mut(&mut self) -> &mut DeSmuMECrashDetector {
+        &mut self.crash_detector
+    }
+
+    pub fn debug_output(&self) -> &DeSmuMEDebugOutput {
+        &self.debug_output
+    }
+
+    pub fn debug_output_mut(&mut self) -> &mut DeSmuMEDebugOutput {
+        &mut self.debug_output
+    }
+
+    /// Breakpoints and execution control.
+    pub fn debugger(&mut self) -> Debugger<'_> {
+        Debugger { emu: self }
+    }
+
     pub fn memory(&self) -> &DeSmuMEMemory {
         &self.memory
     }
@@ -254,6 +322,22 @@ impl DeSmuME {
         &mut self.movie
     }
 
+    pub fn profiler(&self) -> &DeSmuMEProfiler {
+        &self.profiler
+    }
+
+    pub fn profiler_mut(&mut self) -> &mut DeSmuMEProfiler {
+        &mut self.profiler
+    }
+
+    pub fn rewind(&self) -> &DeSmuMERewind {
+        &self.rewind
+    }
+
+    pub fn rewind_mut(&mut self) -> &mut DeSmuMERewind {
+        &mut self.rewind
+    }
+
     pub fn savestate(&self) -> &DeSmuMESavestate {
         &self.savestate
     }
@@ -262,6 +346,27 @@ impl DeSmuME {
         &mut self.savestate
     }
 
+    pub fn trace(&self) -> &DeSmuMETrace {
+        &self.trace
+    }
+
+    pub fn trace_mut(&mut self) -> &mut DeSmuMETrace {
+        &mut self.trace
+    }
+
+    /// The symbol table used to name addresses. Empty until symbols are loaded into it:
+    ///
+    /// ```rs
+    /// *emu.symbols_mut() = Symbols::load("game.sym")?;
+    /// ```
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    pub fn symbols_mut(&mut self) -> &mut Symbols {
+        &mut self.symbols
+    }
+
     /// Set the current firmware language.
     pub fn set_language(&mut self, lang: Language) {
         unsafe { desmume_set_language(lang as u8) }
@@ -277,6 +382,8 @@ impl DeSmuME {
                 return Err(DeSmuMEError::FailedOpen);
             }
         }
+        clear_shadow_stacks();
+        self.rewind.clear();
         if auto_resume {
             self.resume(false);
         }
@@ -311,6 +418,8 @@ impl DeSmuME {
         unsafe {
             desmume_reset();
         }
+        clear_shadow_stacks();
+        self.rewind.clear();
     }
 
     /// Returns `true`, if a game is loaded and the emulator is running (not paused).
@@ -318,16 +427,37 @@ impl DeSmuME {
         unsafe { desmume_running() > 0 }
     }
 
+    /// The number of frames emulated since the last reset.
+    pub fn frame_count(&self) -> u64 {
+        unsafe { desmume_get_frame_count() }
+    }
+
     /// Tell the emulator to skip the next frame.
     pub fn skip_next_frame(&mut self) {
         unsafe { desmume_skip_next_frame() }
     }
 
     /// Cycle one game cycle / frame.
+    ///
+    /// If rewinding is enabled, this also captures rewind snapshots. If crash detection is
+    /// running, crashes detected during the frame are available from
+    /// [`DeSmuMECrashDetector::take_reports`] afterwards.
     pub fn cycle(&mut self) {
+        self.emulate_frame();
+        self.on_frame_end();
+    }
+
+    /// Run the emulation until the end of the current frame, or until a stop is requested.
+    pub(crate) fn emulate_frame(&mut self) {
         unsafe { desmume_cycle(self.input.joystick_was_init as c_bool) }
     }
 
+    /// Per-frame work after a frame was emulated to its end.
+    pub(crate) fn on_frame_end(&mut self) {
+        self.rewind.on_frame();
+        self.crash_detector.on_frame();
+    }
+
     /// Returns `true`, if OpenGL is available for rendering.
     pub fn has_opengl(&self) -> bool {
         unsafe { desmume_has_opengl() > 0 }
@@ -1,40 +1,62 @@
 macro_rules! impl_read_write_access (
-    ($for_type:ident, $integer_type:ident, $as_unsigned:ident, $read_fn:ident, $write_fn:ident) => (
-        impl_read_access!($for_type, $integer_type, $as_unsigned, $read_fn);
-        impl_write_access!($for_type, $integer_type, $as_unsigned, $write_fn);
+    ($for_type:ident, $integer_type:ident, $as_unsigned:ident, $read_fn:ident, $write_fn:ident, $read_fn_arm7:ident, $write_fn_arm7:ident) => (
+        impl_read_access!($for_type, $integer_type, $as_unsigned, $read_fn, $read_fn_arm7);
+        impl_write_access!($for_type, $integer_type, $as_unsigned, $write_fn, $write_fn_arm7);
     );
 );
 
 macro_rules! impl_read_access (
-    ($for_type:ident, $integer_type:ident, $as_unsigned:ident, $read_fn:ident) => (
+    ($for_type:ident, $integer_type:ident, $as_unsigned:ident, $read_fn:ident, $read_fn_arm7:ident) => (
         impl<M> MemoryReadAccess<$integer_type> for $for_type<M, $integer_type> where M: std::convert::AsRef<crate::mem::DeSmuMEMemory> {
+            fn scope(&self) -> AccessScope {
+                self.1
+            }
+
             fn read_range(&self, start: u32, end: u32) -> Vec<$integer_type> {
                 let size_of = std::mem::size_of::<$integer_type>();
                 assert_eq!(0, (end as usize - start as usize + 1) % size_of);
-                (start..(end + 1)).step_by(size_of).map(|a| unsafe { $read_fn(a as c_int) as $integer_type }).collect()
+                if is_watched(HookKind::Read, self.1.processor, start, end) {
+                    // Read value by value, so the read callbacks fire.
+                    return (start..=end).step_by(size_of).map(|a| self.read(a)).collect();
+                }
+                let mut buffer = vec![0u8; end as usize - start as usize + 1];
+                read_block(self.1.processor, start, &mut buffer);
+                buffer.chunks_exact(size_of).map(|c| $integer_type::from_le_bytes(c.try_into().unwrap())).collect()
             }
 
             fn read(&self, addr: u32) -> $integer_type {
-                unsafe { $read_fn(addr as i32) as $integer_type }
+                match self.1.processor {
+                    Processor::Arm9 => unsafe { $read_fn(addr as i32) as $integer_type },
+                    Processor::Arm7 => unsafe { $read_fn_arm7(addr as i32) as $integer_type },
+                }
             }
         }
     );
 );
 
 macro_rules! impl_write_access (
-    ($for_type:ident, $integer_type:ident, $as_unsigned:ident, $write_fn:ident) => (
+    ($for_type:ident, $integer_type:ident, $as_unsigned:ident, $write_fn:ident, $write_fn_arm7:ident) => (
         impl<M> MemoryWriteAccess<$integer_type> for $for_type<M, $integer_type> where M: std::convert::AsRef<crate::mem::DeSmuMEMemory> {
             fn write_range(&mut self, start: u32, end: u32, source: &[$integer_type]) {
                 let size_of = std::mem::size_of::<$integer_type>();
                 assert_eq!(0, (end as usize - start as usize + 1) % size_of);
                 assert_eq!((end as usize - start as usize + 1) / size_of, source.len());
-                for (addr, value) in std::iter::zip((start..(end + 1)).step_by(size_of), source) {
-                    unsafe { $write_fn(addr as c_int, *value as $as_unsigned) }
+                if is_watched(HookKind::Write, self.1.processor, start, end) {
+                    // Write value by value, so the write callbacks fire.
+                    for (addr, value) in std::iter::zip((start..=end).step_by(size_of), source) {
+                        self.write(addr, *value);
+                    }
+                    return;
                 }
+                let buffer: Vec<u8> = source.iter().flat_map(|v| v.to_le_bytes()).collect();
+                write_block(self.1.processor, start, &buffer);
             }
 
             fn write(&mut self, addr: u32, value: $integer_type) {
-                unsafe { $write_fn(addr as c_int, value as $as_unsigned) }
+                match self.1.processor {
+                    Processor::Arm9 => unsafe { $write_fn(addr as c_int, value as $as_unsigned) },
+                    Processor::Arm7 => unsafe { $write_fn_arm7(addr as c_int, value as $as_unsigned) },
+                }
             }
         }
     );
@@ -0,0 +1,284 @@
+use crate::disasm::Flow;
+use crate::mem::{
+    add_observer, read_block, DeSmuMEMemory, InstructionEvent, MemoryRegion, ObserverHandle,
+    Processor, Register,
+};
+use crate::symbols::{Symbolize, Symbols};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+/// Frames beyond this depth are dropped from the bottom of the shadow stack.
+const MAX_SHADOW_DEPTH: usize = 256;
+/// Number of stack words inspected by the stack scan.
+const MAX_SCAN_WORDS: u32 = 1024;
+/// Number of frames reported by the stack scan.
+const MAX_SCAN_FRAMES: usize = 64;
+
+/// How a [`Backtrace`] was obtained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnwindMethod {
+    /// From the calls and returns observed during execution. Exact, as long as the code uses
+    /// regular calls and returns.
+    ShadowStack,
+    /// By scanning the stack for values that look like return addresses. Can contain stale
+    /// entries and miss frames.
+    StackScan,
+}
+
+/// A function call on the call stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackFrame {
+    /// The address execution continues at when the function returns. Bit 0 is set for Thumb code.
+    pub return_address: u32,
+    /// Address of the call instruction, if known.
+    pub call_site: Option<u32>,
+    /// Entry point of the called function, if known.
+    pub function: Option<u32>,
+    /// The stack pointer at the time of the call, if known.
+    pub sp: Option<u32>,
+}
+
+/// The call stack of a processor. See [`DeSmuMEMemory::backtrace`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backtrace {
+    pub processor: Processor,
+    /// Address of the next instruction the processor will execute.
+    pub pc: u32,
+    /// The frames, innermost first.
+    pub frames: Vec<StackFrame>,
+    pub method: UnwindMethod,
+}
+
+/// Prints the return address and, if known, the call site, eg.
+/// `UpdateScore+0x20 (called from GameMain+0x44)`.
+impl Symbolize for StackFrame {
+    fn symbolize(&self, symbols: &Symbols) -> String {
+        let return_address = symbols.symbolize(self.return_address & !1);
+        match self.call_site {
+            Some(call_site) => format!(
+                "{return_address} (called from {})",
+                symbols.symbolize(call_site)
+            ),
+            None => return_address,
+        }
+    }
+}
+
+/// Prints one line per frame, starting with the current instruction:
+///
+/// ```text
+/// #0 UpdateScore+0x8
+/// #1 GameMain+0x48 (called from GameMain+0x44)
+/// ```
+impl Symbolize for Backtrace {
+    fn symbolize(&self, symbols: &Symbols) -> String {
+        let mut lines = vec![format!("#0 {}", symbols.symbolize(self.pc))];
+        for (i, frame) in self.frames.iter().enumerate() {
+            lines.push(format!("#{} {}", i + 1, frame.symbolize(symbols)));
+        }
+        lines.join("\n")
+    }
+}
+
+#[derive(Default)]
+struct ShadowStack {
+    frames: Vec<StackFrame>,
+    /// The last instruction, whose effect on the call stack is known once the next one runs.
+    previous: Option<InstructionEvent>,
+}
+
+impl ShadowStack {
+    fn on_instruction(&mut self, event: &InstructionEvent) {
+        let Some(previous) = self.previous.replace(*event) else {
+            return;
+        };
+        let flow = if previous.thumb {
+            Flow::thumb(previous.address, previous.opcode as u16)
+        } else {
+            Flow::arm(previous.address, previous.opcode)
+        };
+        if event.address == previous.address.wrapping_add(flow.length()) {
+            return;
+        }
+        if let Flow::Call { return_address, .. } = flow {
+            if self.frames.len() == MAX_SHADOW_DEPTH {
+                self.frames.remove(0);
+            }
+            // The call of a Thumb BL / BLX pair happens in its second half, but the instruction
+            // starts at the first one.
+            let pair = previous.thumb && matches!(previous.opcode as u16 & 0xF800, 0xF800 | 0xE800);
+            let call_site = if pair {
+                return_address.wrapping_sub(4)
+            } else {
+                previous.address
+            };
+            let mem = DeSmuMEMemory(PhantomData);
+            self.frames.push(StackFrame {
+                return_address: return_address | previous.thumb as u32,
+                call_site: Some(call_site),
+                function: Some(event.address),
+                sp: Some(mem.get_reg(event.processor, Register::SP)),
+            });
+            return;
+        }
+        // Any jump to a known return address returns from that frame and all frames above it,
+        // which also covers tail calls and unusual return sequences.
+        if let Some(pos) = self
+            .frames
+            .iter()
+            .rposition(|f| f.return_address & !1 == event.address)
+        {
+            self.frames.truncate(pos);
+        }
+    }
+}
+
+struct TrackedStack {
+    processor: Processor,
+    stack: Arc<Mutex<ShadowStack>>,
+    _observer: ObserverHandle,
+}
+
+static SHADOW_STACKS: Mutex<Vec<TrackedStack>> = Mutex::new(Vec::new());
+
+pub(crate) fn set_tracking(processor: Processor, enabled: bool) {
+    let mut stacks = SHADOW_STACKS.lock().unwrap();
+    let tracked = stacks.iter().any(|t| t.processor == processor);
+    if enabled && !tracked {
+        let stack = Arc::new(Mutex::new(ShadowStack::default()));
+        let observer_stack = stack.clone();
+        let handle = add_observer(processor, move |event| {
+            observer_stack.lock().unwrap().on_instruction(event)
+        });
+        stacks.push(TrackedStack {
+            processor,
+            stack,
+            _observer: handle,
+        });
+    } else if !enabled {
+        stacks.retain(|t| t.processor != processor);
+    }
+}
+
+/// Forget the tracked call stacks, eg. after a reset or loading a savestate, when the emulated
+/// call stack no longer matches them. Tracking continues from the next instruction.
+pub(crate) fn clear_shadow_stacks() {
+    for tracked in SHADOW_STACKS.lock().unwrap().iter() {
+        *tracked.stack.lock().unwrap() = ShadowStack::default();
+    }
+}
+
+pub(crate) fn is_tracking(processor: Processor) -> bool {
+    SHADOW_STACKS
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|t| t.processor == processor)
+}
+
+pub(crate) fn backtrace(mem: &DeSmuMEMemory, processor: Processor) -> Backtrace {
+    let pc = mem.cpu_state(processor).instruction_address;
+    let shadow = SHADOW_STACKS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|t| t.processor == processor)
+        .map(|t| t.stack.lock().unwrap().frames.clone());
+    match shadow {
+        Some(mut frames) => {
+            frames.reverse();
+            Backtrace {
+                processor,
+                pc,
+                frames,
+                method: UnwindMethod::ShadowStack,
+            }
+        }
+        None => Backtrace {
+            processor,
+            pc,
+            frames: scan_stack(mem, processor),
+            method: UnwindMethod::StackScan,
+        },
+    }
+}
+
+/// Looks for return addresses in LR and on the stack: values that point right behind a
+/// BL / BLX instruction in memory that can contain code.
+fn scan_stack(mem: &DeSmuMEMemory, processor: Processor) -> Vec<StackFrame> {
+    let mut frames = Vec::new();
+    let lr = mem.get_reg(processor, Register::LR);
+    if let Some(call_site) = call_site_for(processor, lr) {
+        frames.push(StackFrame {
+            return_address: lr,
+            call_site: Some(call_site),
+            function: None,
+            sp: None,
+        });
+    }
+
+    let sp = mem.get_reg(processor, Register::SP) & !3;
+    // Don't scan past the end of the memory region the stack is in.
+    let end = MemoryRegion::containing_on(processor, sp)
+        .and_then(|r| r.mirror_bounds(sp))
+        .map_or(sp, |bounds| *bounds.end());
+    let words = ((end.saturating_sub(sp) / 4) + 1).min(MAX_SCAN_WORDS);
+    let mut stack = vec![0; words as usize * 4];
+    read_block(processor, sp, &mut stack);
+    for (i, word) in stack.chunks_exact(4).enumerate() {
+        if frames.len() >= MAX_SCAN_FRAMES {
+            break;
+        }
+        let value = u32::from_le_bytes(word.try_into().unwrap());
+        if frames.last().is_some_and(|f| f.return_address == value) {
+            continue;
+        }
+        if let Some(call_site) = call_site_for(processor, value) {
+            frames.push(StackFrame {
+                return_address: value,
+                call_site: Some(call_site),
+                function: None,
+                sp: Some(sp + i as u32 * 4),
+            });
+        }
+    }
+    frames
+}
+
+/// If `value` is a plausible return address, returns the address of the call instruction.
+fn call_site_for(processor: Processor, value: u32) -> Option<u32> {
+    let code = MemoryRegion::containing_on(processor, value)?;
+    if !matches!(
+        code,
+        MemoryRegion::Itcm
+            | MemoryRegion::MainRam
+            | MemoryRegion::SharedWram
+            | MemoryRegion::Arm7Wram
+            | MemoryRegion::Arm9Bios
+            | MemoryRegion::Arm7Bios
+    ) {
+        return None;
+    }
+    if value & 1 != 0 {
+        let address = value & !1;
+        let mut bytes = [0; 4];
+        read_block(processor, address.wrapping_sub(4), &mut bytes);
+        let first = u16::from_le_bytes([bytes[0], bytes[1]]);
+        let second = u16::from_le_bytes([bytes[2], bytes[3]]);
+        if first & 0xF800 == 0xF000 && matches!(second & 0xF800, 0xF800 | 0xE800) {
+            return Some(address.wrapping_sub(4));
+        }
+        if second & 0xFF87 == 0x4780 {
+            return Some(address.wrapping_sub(2));
+        }
+        None
+    } else if value & 3 == 0 {
+        let mut bytes = [0; 4];
+        read_block(processor, value.wrapping_sub(4), &mut bytes);
+        let opcode = u32::from_le_bytes(bytes);
+        let address = value.wrapping_sub(4);
+        matches!(Flow::arm(address, opcode), Flow::Call { .. }).then_some(address)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,260 @@
+use crate::mem::{Processor, Registers};
+
+/// The operating mode of a processor, from the lower bits of the CPSR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CpuMode {
+    User,
+    Fiq,
+    Irq,
+    Supervisor,
+    Abort,
+    Undefined,
+    System,
+}
+
+impl CpuMode {
+    /// Decodes the mode bits of a CPSR. Returns `None` for invalid modes.
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        match bits & 0x1F {
+            0x10 => Some(Self::User),
+            0x11 => Some(Self::Fiq),
+            0x12 => Some(Self::Irq),
+            0x13 => Some(Self::Supervisor),
+            0x17 => Some(Self::Abort),
+            0x1B => Some(Self::Undefined),
+            0x1F => Some(Self::System),
+            _ => None,
+        }
+    }
+
+    /// The mode bits of the CPSR for this mode.
+    pub fn bits(self) -> u32 {
+        match self {
+            Self::User => 0x10,
+            Self::Fiq => 0x11,
+            Self::Irq => 0x12,
+            Self::Supervisor => 0x13,
+            Self::Abort => 0x17,
+            Self::Undefined => 0x1B,
+            Self::System => 0x1F,
+        }
+    }
+}
+
+/// A decoded CPSR or SPSR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cpsr {
+    /// The raw register value.
+    pub raw: u32,
+    pub negative: bool,
+    pub zero: bool,
+    pub carry: bool,
+    pub overflow: bool,
+    /// Sticky overflow flag of the saturating instructions. Always `false` on the ARM7.
+    pub saturation: bool,
+    pub irq_disabled: bool,
+    pub fiq_disabled: bool,
+    /// Whether the processor is in Thumb mode.
+    pub thumb: bool,
+    /// The operating mode, `None` if the mode bits are invalid.
+    pub mode: Option<CpuMode>,
+}
+
+impl From<u32> for Cpsr {
+    fn from(raw: u32) -> Self {
+        let bit = |n: u32| raw & (1 << n) != 0;
+        Self {
+            raw,
+            negative: bit(31),
+            zero: bit(30),
+            carry: bit(29),
+            overflow: bit(28),
+            saturation: bit(27),
+            irq_disabled: bit(7),
+            fiq_disabled: bit(6),
+            thumb: bit(5),
+            mode: CpuMode::from_bits(raw),
+        }
+    }
+}
+
+/// R13, R14 and SPSR of one of the exception modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RegisterBank {
+    pub r13: u32,
+    pub r14: u32,
+    pub spsr: u32,
+}
+
+/// The registers banked for FIQ mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FiqBank {
+    /// R8 - R14.
+    pub r8_r14: [u32; 7],
+    pub spsr: u32,
+}
+
+/// Snapshot of the full register file of a processor, including the banked registers of all
+/// modes. See [`crate::DeSmuMEMemory::cpu_state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuState {
+    pub processor: Processor,
+    /// The registers as seen in the current mode.
+    pub registers: Registers,
+    pub cpsr: Cpsr,
+    /// R8 - R14 of user and system mode.
+    pub user: [u32; 7],
+    pub fiq: FiqBank,
+    pub irq: RegisterBank,
+    pub supervisor: RegisterBank,
+    pub abort: RegisterBank,
+    pub undefined: RegisterBank,
+    /// Address of the next instruction the processor will execute.
+    pub instruction_address: u32,
+}
+
+impl CpuState {
+    pub(crate) fn new(processor: Processor, raw: &desmume_sys::CpuState) -> Self {
+        let bank = |r: [u32; 2], spsr: u32| RegisterBank {
+            r13: r[0],
+            r14: r[1],
+            spsr,
+        };
+        Self {
+            processor,
+            registers: Registers {
+                r: raw.r,
+                cpsr: raw.cpsr,
+                spsr: raw.spsr,
+            },
+            cpsr: Cpsr::from(raw.cpsr),
+            user: raw.r8_r14_usr,
+            fiq: FiqBank {
+                r8_r14: raw.r8_r14_fiq,
+                spsr: raw.spsr_fiq,
+            },
+            irq: bank(raw.r13_r14_irq, raw.spsr_irq),
+            supervisor: bank(raw.r13_r14_svc, raw.spsr_svc),
+            abort: bank(raw.r13_r14_abt, raw.spsr_abt),
+            undefined: bank(raw.r13_r14_und, raw.spsr_und),
+            instruction_address: raw.instruction_address,
+        }
+    }
+
+    /// Register `n` (0-15) as seen in the given mode.
+    ///
+    /// # Panics
+    /// If `n` is larger than 15.
+    pub fn register_in_mode(&self, mode: CpuMode, n: usize) -> u32 {
+        let bank = match mode {
+            CpuMode::Irq => Some(&self.irq),
+            CpuMode::Supervisor => Some(&self.supervisor),
+            CpuMode::Abort => Some(&self.abort),
+            CpuMode::Undefined => Some(&self.undefined),
+            CpuMode::User | CpuMode::System | CpuMode::Fiq => None,
+        };
+        match (n, mode, bank) {
+            (0..=7 | 15, _, _) => self.registers.r[n],
+            (8..=14, CpuMode::Fiq, _) => self.fiq.r8_r14[n - 8],
+            (13, _, Some(bank)) => bank.r13,
+            (14, _, Some(bank)) => bank.r14,
+            (8..=14, _, _) => self.user[n - 8],
+            _ => panic!("invalid register number {n}"),
+        }
+    }
+
+    /// The SPSR of the given mode. `None` for user and system mode, which have no SPSR.
+    pub fn spsr_in_mode(&self, mode: CpuMode) -> Option<Cpsr> {
+        let raw = match mode {
+            CpuMode::User | CpuMode::System => return None,
+            CpuMode::Fiq => self.fiq.spsr,
+            CpuMode::Irq => self.irq.spsr,
+            CpuMode::Supervisor => self.supervisor.spsr,
+            CpuMode::Abort => self.abort.spsr,
+            CpuMode::Undefined => self.undefined.spsr,
+        };
+        Some(Cpsr::from(raw))
+    }
+}
+
+/// Location of a tightly coupled memory, as configured in CP15.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcmRegion {
+    pub base: u32,
+    /// The size of the (mirrored) region in bytes.
+    pub size: u64,
+}
+
+impl TcmRegion {
+    fn from_raw(raw: u32) -> Self {
+        Self {
+            base: raw & 0xFFFF_F000,
+            size: 512u64 << ((raw >> 1) & 0x1F),
+        }
+    }
+}
+
+/// One of the eight protection regions of the ARM9 MPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MpuRegion {
+    pub enabled: bool,
+    pub base: u32,
+    /// The size of the region in bytes.
+    pub size: u64,
+    pub dcache: bool,
+    pub icache: bool,
+    pub write_buffer: bool,
+    /// The 4-bit extended access permissions for data accesses.
+    pub data_permissions: u8,
+    /// The 4-bit extended access permissions for instruction fetches.
+    pub instruction_permissions: u8,
+}
+
+/// Snapshot of the CP15 state of the ARM9. See [`crate::DeSmuMEMemory::cp15`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cp15State {
+    /// The raw control register (c1,c0,0).
+    pub control: u32,
+    pub mpu_enabled: bool,
+    pub dcache_enabled: bool,
+    pub icache_enabled: bool,
+    /// Whether the exception vectors are at 0xFFFF0000 instead of 0.
+    pub high_vectors: bool,
+    pub dtcm_enabled: bool,
+    pub itcm_enabled: bool,
+    pub dtcm: TcmRegion,
+    pub itcm: TcmRegion,
+    pub regions: [MpuRegion; 8],
+}
+
+impl From<&desmume_sys::Cp15State> for Cp15State {
+    fn from(raw: &desmume_sys::Cp15State) -> Self {
+        let bit = |value: u32, n: u32| value & (1 << n) != 0;
+        let regions = std::array::from_fn(|i| {
+            let region = raw.protection_regions[i];
+            let n = i as u32;
+            MpuRegion {
+                enabled: bit(region, 0),
+                base: region & 0xFFFF_F000,
+                size: 2u64 << ((region >> 1) & 0x1F),
+                dcache: bit(raw.dcache_config, n),
+                icache: bit(raw.icache_config, n),
+                write_buffer: bit(raw.write_buffer, n),
+                data_permissions: ((raw.data_permissions >> (n * 4)) & 0xF) as u8,
+                instruction_permissions: ((raw.instruction_permissions >> (n * 4)) & 0xF) as u8,
+            }
+        });
+        Self {
+            control: raw.control,
+            mpu_enabled: bit(raw.control, 0),
+            dcache_enabled: bit(raw.control, 2),
+            icache_enabled: bit(raw.control, 12),
+            high_vectors: bit(raw.control, 13),
+            dtcm_enabled: bit(raw.control, 16),
+            itcm_enabled: bit(raw.control, 18),
+            dtcm: TcmRegion::from_raw(raw.dtcm_region),
+            itcm: TcmRegion::from_raw(raw.itcm_region),
+            regions,
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use crate::mem::hook::next_hook_id;
+use crate::mem::{HookAction, HookHandle, Processor};
+use desmume_sys::*;
+use std::sync::{Arc, Mutex};
+
+/// The source of an interrupt, by its bit in the IE and IF registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IrqSource {
+    VBlank,
+    HBlank,
+    VCounter,
+    Timer(u8),
+    /// The serial port / RTC. ARM7 only.
+    Serial,
+    Dma(u8),
+    Keypad,
+    GbaSlot,
+    IpcSync,
+    IpcSendFifoEmpty,
+    IpcReceiveFifoNotEmpty,
+    CardTransferComplete,
+    CardIreqMc,
+    /// ARM9 only.
+    GeometryFifo,
+    /// The lid was opened. ARM7 only.
+    ScreensUnfolding,
+    /// ARM7 only.
+    SpiBus,
+    /// ARM7 only.
+    Wifi,
+    /// Any other bit.
+    Other(u8),
+}
+
+impl IrqSource {
+    /// The source for a bit of the IE / IF registers.
+    pub fn from_bit(bit: u8) -> Self {
+        match bit {
+            0 => Self::VBlank,
+            1 => Self::HBlank,
+            2 => Self::VCounter,
+            3..=6 => Self::Timer(bit - 3),
+            7 => Self::Serial,
+            8..=11 => Self::Dma(bit - 8),
+            12 => Self::Keypad,
+            13 => Self::GbaSlot,
+            16 => Self::IpcSync,
+            17 => Self::IpcSendFifoEmpty,
+            18 => Self::IpcReceiveFifoNotEmpty,
+            19 => Self::CardTransferComplete,
+            20 => Self::CardIreqMc,
+            21 => Self::GeometryFifo,
+            22 => Self::ScreensUnfolding,
+            23 => Self::SpiBus,
+            24 => Self::Wifi,
+            _ => Self::Other(bit),
+        }
+    }
+
+    /// The bit of the source in the IE / IF registers.
+    pub fn bit(self) -> u8 {
+        match self {
+            Self::VBlank => 0,
+            Self::HBlank => 1,
+            Self::VCounter => 2,
+            Self::Timer(n) => 3 + n,
+            Self::Serial => 7,
+            Self::Dma(n) => 8 + n,
+            Self::Keypad => 12,
+            Self::GbaSlot => 13,
+            Self::IpcSync => 16,
+            Self::IpcSendFifoEmpty => 17,
+            Self::IpcReceiveFifoNotEmpty => 18,
+            Self::CardTransferComplete => 19,
+            Self::CardIreqMc => 20,
+            Self::GeometryFifo => 21,
+            Self::ScreensUnfolding => 22,
+            Self::SpiBus => 23,
+            Self::Wifi => 24,
+            Self::Other(bit) => bit,
+        }
+    }
+}
+
+/// What happened to an interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrqEventKind {
+    /// The IRQ flag was set in IF.
+    Raised,
+    /// The game acknowledged the IRQ by writing to IF.
+    Acknowledged,
+}
+
+/// An interrupt was raised or acknowledged. See [`crate::DeSmuMEMemory::hook_irq`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct IrqEvent {
+    pub kind: IrqEventKind,
+    pub processor: Processor,
+    pub source: IrqSource,
+    /// Emulated time since the last reset, in ARM9 cycles.
+    pub cycles: u64,
+}
+
+/// When a DMA transfer starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DmaTiming {
+    Immediate,
+    VBlank,
+    HBlank,
+    /// Synchronized to the start of the display. ARM9 only.
+    StartOfDisplay,
+    /// Main memory display. ARM9 only.
+    MainMemoryDisplay,
+    DsCard,
+    GbaSlot,
+    /// ARM9 only.
+    GeometryFifo,
+    /// ARM7 only.
+    Wifi,
+}
+
+/// A DMA transfer was started. See [`crate::DeSmuMEMemory::hook_dma`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct DmaEvent {
+    pub processor: Processor,
+    /// The channel, 0-3.
+    pub channel: u8,
+    pub source: u32,
+    pub destination: u32,
+    /// The number of units transferred.
+    pub length: u32,
+    /// The size of a unit in bytes, 2 or 4.
+    pub unit_size: u32,
+    pub timing: DmaTiming,
+    /// Whether the transfer repeats on every occurrence of its timing.
+    pub repeat: bool,
+    /// The raw DMAxCNT register.
+    pub control: u32,
+    /// Emulated time since the last reset, in ARM9 cycles.
+    pub cycles: u64,
+}
+
+impl DmaEvent {
+    fn new(processor: Processor, info: &HardwareEventInfo) -> Self {
+        let channel = info.index as u8;
+        let control = info.value;
+        let (length, timing) = match processor {
+            Processor::Arm9 => {
+                let timing = match (control >> 27) & 7 {
+                    0 => DmaTiming::Immediate,
+                    1 => DmaTiming::VBlank,
+                    2 => DmaTiming::HBlank,
+                    3 => DmaTiming::StartOfDisplay,
+                    4 => DmaTiming::MainMemoryDisplay,
+                    5 => DmaTiming::DsCard,
+                    6 => DmaTiming::GbaSlot,
+                    _ => DmaTiming::GeometryFifo,
+                };
+                (control & 0x1F_FFFF, timing)
+            }
+            Processor::Arm7 => {
+                let timing = match ((control >> 28) & 3, channel) {
+                    (0, _) => DmaTiming::Immediate,
+                    (1, _) => DmaTiming::VBlank,
+                    (2, _) => DmaTiming::DsCard,
+                    (_, 0 | 2) => DmaTiming::Wifi,
+                    _ => DmaTiming::GbaSlot,
+                };
+                let mask = if channel == 3 { 0xFFFF } else { 0x3FFF };
+                (control & mask, timing)
+            }
+        };
+        // A length of 0 means the maximum.
+        let length = match (length, processor, channel) {
+            (0, Processor::Arm9, _) => 0x20_0000,
+            (0, Processor::Arm7, 3) => 0x1_0000,
+            (0, Processor::Arm7, _) => 0x4000,
+            (length, _, _) => length,
+        };
+        Self {
+            processor,
+            channel,
+            source: info.source,
+            destination: info.destination,
+            length,
+            unit_size: if control & (1 << 26) != 0 { 4 } else { 2 },
+            timing,
+            repeat: control & (1 << 25) != 0,
+            control,
+            cycles: info.cycles,
+        }
+    }
+}
+
+/// A timer overflowed. See [`crate::DeSmuMEMemory::hook_timer_overflow`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TimerEvent {
+    pub processor: Processor,
+    /// The timer, 0-3.
+    pub timer: u8,
+    /// Emulated time since the last reset, in ARM9 cycles.
+    pub cycles: u64,
+}
+
+/// The direction of an IPC FIFO transfer, as seen from the hooked processor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpcFifoDirection {
+    /// The processor wrote the value to its send FIFO.
+    Send,
+    /// The processor read the value from its receive FIFO.
+    Receive,
+}
+
+/// A value was sent or received through the IPC FIFO. See
+/// [`crate::DeSmuMEMemory::hook_ipc_fifo`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct IpcFifoEvent {
+    pub processor: Processor,
+    pub direction: IpcFifoDirection,
+    pub value: u32,
+    /// Emulated time since the last reset, in ARM9 cycles.
+    pub cycles: u64,
+}
+
+pub(crate) enum HardwareEvent {
+    Irq(IrqEvent),
+    Dma(DmaEvent),
+    TimerOverflow(TimerEvent),
+    IpcFifo(IpcFifoEvent),
+    /// An undefined instruction or abort, before the processor jumps to `vector`.
+    Exception {
+        processor: Processor,
+        vector: u32,
+        /// Address of the instruction that caused the exception.
+        address: u32,
+    },
+}
+
+impl HardwareEvent {
+    fn new(info: &HardwareEventInfo) -> Option<Self> {
+        let processor = Processor::from_id(info.processor);
+        let irq = |kind| {
+            Self::Irq(IrqEvent {
+                kind,
+                processor,
+                source: IrqSource::from_bit(info.index as u8),
+                cycles: info.cycles,
+            })
+        };
+        let ipc = |direction| {
+            Self::IpcFifo(IpcFifoEvent {
+                processor,
+                direction,
+                value: info.value,
+                cycles: info.cycles,
+            })
+        };
+        Some(match info.kind {
+            DESMUME_EVENT_IRQ_RAISED => irq(IrqEventKind::Raised),
+            DESMUME_EVENT_IRQ_ACKNOWLEDGED => irq(IrqEventKind::Acknowledged),
+            DESMUME_EVENT_DMA_STARTED => Self::Dma(DmaEvent::new(processor, info)),
+            DESMUME_EVENT_TIMER_OVERFLOW => Self::TimerOverflow(TimerEvent {
+                processor,
+                timer: info.index as u8,
+                cycles: info.cycles,
+            }),
+            DESMUME_EVENT_IPC_FIFO_SEND => ipc(IpcFifoDirection::Send),
+            DESMUME_EVENT_IPC_FIFO_RECEIVE => ipc(IpcFifoDirection::Receive),
+            DESMUME_EVENT_CPU_EXCEPTION => Self::Exception {
+                processor,
+                vector: info.index as u32,
+                address: info.value,
+            },
+            _ => return None,
+        })
+    }
+
+    fn processor(&self) -> Processor {
+        match self {
+            Self::Irq(e) => e.processor,
+            Self::Dma(e) => e.processor,
+            Self::TimerOverflow(e) => e.processor,
+            Self::IpcFifo(e) => e.processor,
+            Self::Exception { processor, .. } => *processor,
+        }
+    }
+}
+
+type EventCallback = Arc<Mutex<dyn FnMut(&HardwareEvent) -> HookAction + Send>>;
+
+struct EventHook {
+    id: u64,
+    processor: Processor,
+    callback: EventCallback,
+}
+
+/// All event hooks. DeSmuME has a single event callback, which dispatches to them.
+static EVENT_HOOKS: Mutex<Vec<EventHook>> = Mutex::new(Vec::new());
+
+pub(crate) fn add_event_hook<F>(processor: Processor, callback: F) -> HookHandle
+where
+    F: FnMut(&HardwareEvent) -> HookAction + Send + 'static,
+{
+    let id = next_hook_id();
+    let mut hooks = EVENT_HOOKS.lock().unwrap();
+    hooks.push(EventHook {
+        id,
+        processor,
+        callback: Arc::new(Mutex::new(callback)),
+    });
+    sync_registration(&hooks);
+    HookHandle::new(id, remove_event_hook)
+}
+
+fn remove_event_hook(id: u64) {
+    let mut hooks = EVENT_HOOKS.lock().unwrap();
+    if let Some(pos) = hooks.iter().position(|h| h.id == id) {
+        hooks.remove(pos);
+        sync_registration(&hooks);
+    }
+}
+
+fn sync_registration(hooks: &[EventHook]) {
+    let callback: HardwareEventCbFnc = (!hooks.is_empty()).then_some(trampoline as _);
+    unsafe { desmume_set_hardware_event_callback(callback, std::ptr::null_mut()) }
+}
+
+extern "C" fn trampoline(info: *const HardwareEventInfo, _userdata: *mut c_void) {
+    let Some(event) = HardwareEvent::new(unsafe { &*info }) else {
+        return;
+    };
+    // Clone the matching callbacks, so hooks can be added and removed from within callbacks.
+    let matching: Vec<(u64, EventCallback)> = EVENT_HOOKS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|h| h.processor == event.processor())
+        .map(|h| (h.id, h.callback.clone()))
+        .collect();
+    for (id, callback) in matching {
+        let Ok(mut callback) = callback.try_lock() else {
+            continue;
+        };
+        if callback(&event) == HookAction::Unregister {
+            drop(callback);
+            remove_event_hook(id);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use crate::mem::read::set_watch;
+use crate::mem::Processor;
+use crate::symbols::{Symbolize, Symbols};
+use desmume_sys::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The kind of memory access a hook watches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookKind {
+    Read,
+    Write,
+    Exec,
+}
+
+/// Information about the memory access that triggered a hook.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct HookContext {
+    /// The kind of access.
+    pub kind: HookKind,
+    /// The processor that did the access.
+    pub processor: Processor,
+    /// The address that was accessed.
+    pub address: u32,
+    /// The width of the access in bytes.
+    pub size: u32,
+    /// For writes, the value before the write. For reads the value that was read and for
+    /// execution the opcode of the instruction.
+    pub old_value: u32,
+    /// For writes, the value that was written. For reads the value that was read and for
+    /// execution the opcode of the instruction.
+    pub new_value: u32,
+    /// Address of the instruction that did the access.
+    pub pc: u32,
+}
+
+/// Prints the access with addresses as symbols, eg. `write 0x00000003 to gScore+0x4 at
+/// UpdateScore+0x1c`.
+impl Symbolize for HookContext {
+    fn symbolize(&self, symbols: &Symbols) -> String {
+        let pc = symbols.symbolize(self.pc);
+        let address = symbols.symbolize(self.address);
+        match self.kind {
+            HookKind::Read => format!("read 0x{:08x} from {address} at {pc}", self.new_value),
+            HookKind::Write => format!("write 0x{:08x} to {address} at {pc}", self.new_value),
+            HookKind::Exec => format!("exec {address}"),
+        }
+    }
+}
+
+/// What should happen with a hook after it ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookAction {
+    /// Keep the hook registered.
+    Continue,
+    /// Unregister the hook. Its [`HookHandle`] becomes inert.
+    Unregister,
+}
+
+type HookCallback = Arc<Mutex<dyn FnMut(&HookContext) -> HookAction + Send>>;
+
+struct Hook {
+    id: u64,
+    kind: HookKind,
+    processor: Processor,
+    address: u32,
+    size: u32,
+    callback: HookCallback,
+}
+
+/// All closure hooks. DeSmuME only supports one callback per address, so the crate registers a
+/// trampoline for each watched address and dispatches to the matching closures from there.
+static HOOKS: Mutex<Vec<Hook>> = Mutex::new(Vec::new());
+static NEXT_HOOK_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Keeps a hook registered. The hook is unregistered when the handle is dropped.
+#[must_use = "the hook is unregistered when the handle is dropped"]
+pub struct HookHandle {
+    id: u64,
+    /// Removes the hook from the registry it was added to.
+    remove: fn(u64),
+}
+
+impl HookHandle {
+    pub(crate) fn new(id: u64, remove: fn(u64)) -> Self {
+        Self { id, remove }
+    }
+}
+
+impl Drop for HookHandle {
+    fn drop(&mut self) {
+        (self.remove)(self.id)
+    }
+}
+
+/// A new id for a [`HookHandle`], unique across all kinds of hooks.
+pub(crate) fn next_hook_id() -> u64 {
+    NEXT_HOOK_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+pub(crate) fn add_hook<F>(
+    kind: HookKind,
+    processor: Processor,
+    address: u32,
+    size: u32,
+    callback: F,
+) -> HookHandle
+where
+    F: FnMut(&HookContext) -> HookAction + Send + 'static,
+{
+    let id = next_hook_id();
+    let mut hooks = HOOKS.lock().unwrap();
+    hooks.push(Hook {
+        id,
+        kind,
+        processor,
+        address,
+        size,
+        callback: Arc::new(Mutex::new(callback)),
+    });
+    sync_registration(&hooks, kind, processor, address);
+    HookHandle::new(id, remove_hook)
+}
+
+fn remove_hook(id: u64) {
+    let mut hooks = HOOKS.lock().unwrap();
+    if let Some(pos) = hooks.iter().position(|h| h.id == id) {
+        let hook = hooks.remove(pos);
+        sync_registration(&hooks, hook.kind, hook.processor, hook.address);
+    }
+}
+
+/// (Re-)register the trampoline for an address with DeSmuME, or unregister it if no hooks
+/// are left for it. If multiple hooks share a start address, the largest size is watched.
+fn sync_registration(hooks: &[Hook], kind: HookKind, processor: Processor, address: u32) {
+    let size = hooks
+        .iter()
+        .filter(|h| h.kind == kind && h.processor == processor && h.address == address)
+        .map(|h| h.size)
+        .max();
+    if kind != HookKind::Exec {
+        set_watch(kind, processor, address, size);
+    }
+    let register = match kind {
+        HookKind::Read => desmume_memory_register_read_ex,
+        HookKind::Write => desmume_memory_register_write_ex,
+        HookKind::Exec => desmume_memory_register_exec_ex,
+    };
+    let callback: MemoryCbFncEx = size.map(|_| match kind {
+        HookKind::Read => trampoline_read as _,
+        HookKind::Write => trampoline_write as _,
+        HookKind::Exec => trampoline_exec as _,
+    });
+    // The start address doubles as the user data, so dispatching knows which
+    // registration was triggered.
+    let userdata = address as usize as *mut c_void;
+    unsafe {
+        register(
+            processor.id(),
+            address,
+            size.unwrap_or(0),
+            callback,
+            userdata,
+        )
+    }
+}
+
+fn dispatch(kind: HookKind, info: *const MemoryAccessInfo, userdata: *mut c_void) -> c_bool {
+    let info = unsafe { &*info };
+    let registered_address = userdata as usize as u32;
+    let ctx = HookContext {
+        kind,
+        processor: Processor::from_id(info.processor),
+        address: info.address,
+        size: info.size as u32,
+        old_value: info.old_value,
+        new_value: info.new_value,
+        pc: info.pc,
+    };
+    let access_end = ctx.address as u64 + ctx.size.max(1) as u64;
+    // Clone the matching callbacks, so hooks can be added and removed from within callbacks.
+    let matching: Vec<(u64, HookCallback)> = HOOKS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|h| {
+            h.kind == kind
+                && h.processor == ctx.processor
+                && h.address == registered_address
+                && (h.address as u64) < access_end
+                && ctx.address < h.address.saturating_add(h.size.max(1))
+        })
+        .map(|h| (h.id, h.callback.clone()))
+        .collect();
+    for (id, callback) in matching {
+        // A hook that triggers itself (eg. by reading watched memory) is not run recursively.
+        let Ok(mut callback) = callback.try_lock() else {
+            continue;
+        };
+        if callback(&ctx) == HookAction::Unregister {
+            drop(callback);
+            remove_hook(id);
+        }
+    }
+    1
+}
+
+extern "C" fn trampoline_read(info: *const MemoryAccessInfo, userdata: *mut c_void) -> c_bool {
+    dispatch(HookKind::Read, info, userdata)
+}
+
+extern "C" fn trampoline_write(info: *const MemoryAccessInfo, userdata: *mut c_void) -> c_bool {
+    dispatch(HookKind::Write, info, userdata)
+}
+
+extern "C" fn trampoline_exec(info: *const MemoryAccessInfo, userdata: *mut c_void) -> c_bool {
+    dispatch(HookKind::Exec, info, userdata)
+}
@@ -1,27 +1,66 @@
+mod backtrace;
+mod cpu;
+mod event;
+mod hook;
 mod index;
+mod observer;
+mod patch;
 mod read;
+mod region;
+mod swi;
+mod view;
 
+use crate::disasm::{disassemble, Instruction, Mode};
+pub(crate) use crate::mem::backtrace::clear_shadow_stacks;
+pub use crate::mem::backtrace::{Backtrace, StackFrame, UnwindMethod};
+pub use crate::mem::cpu::{
+    Cp15State, Cpsr, CpuMode, CpuState, FiqBank, MpuRegion, RegisterBank, TcmRegion,
+};
+pub(crate) use crate::mem::event::{add_event_hook, HardwareEvent};
+pub use crate::mem::event::{
+    DmaEvent, DmaTiming, IpcFifoDirection, IpcFifoEvent, IrqEvent, IrqEventKind, IrqSource,
+    TimerEvent,
+};
+pub use crate::mem::hook::{HookAction, HookContext, HookHandle, HookKind};
 pub use crate::mem::index::{IndexMove, IndexSet};
+pub(crate) use crate::mem::observer::{add_observer, InstructionEvent, ObserverHandle};
+pub use crate::mem::patch::PatchContext;
+use crate::mem::read::AccessScope;
+pub(crate) use crate::mem::read::{read_block, write_block};
 pub use crate::mem::read::{MemIndexWrapper, TypedMemoryAccessor};
+pub use crate::mem::region::MemoryRegion;
+use crate::mem::swi::add_swi_hook;
+pub use crate::mem::swi::{swi_name, SwiContext};
+pub use crate::mem::view::MemoryView;
 pub use desmume_sys::MemoryCbFnc;
 use desmume_sys::*;
 use std::ffi::CString;
 use std::marker::PhantomData;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Processor {
     Arm9,
     Arm7,
 }
 
 impl Processor {
-    fn get_name(&self) -> &str {
+    pub(crate) fn id(self) -> c_int {
         match self {
-            Processor::Arm9 => "arm9",
-            Processor::Arm7 => "arm7",
+            Processor::Arm9 => DESMUME_PROCESSOR_ARM9,
+            Processor::Arm7 => DESMUME_PROCESSOR_ARM7,
+        }
+    }
+
+    pub(crate) fn from_id(id: c_int) -> Self {
+        if id == DESMUME_PROCESSOR_ARM7 {
+            Processor::Arm7
+        } else {
+            Processor::Arm9
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[non_exhaustive]
 pub enum Register {
     R0,
@@ -48,32 +87,52 @@ pub enum Register {
 }
 
 impl Register {
-    fn get_name(&self) -> &str {
+    /// Index of the register in [`Registers`]: 0-15 for R0-R15, 16 for CPSR and 17 for SPSR.
+    pub(crate) fn index(&self) -> usize {
         match self {
-            Self::R0 => "r0",
-            Self::R1 => "r1",
-            Self::R2 => "r2",
-            Self::R3 => "r3",
-            Self::R4 => "r4",
-            Self::R5 => "r5",
-            Self::R6 => "r6",
-            Self::R7 => "r7",
-            Self::R8 => "r8",
-            Self::R9 => "r9",
-            Self::R10 => "r10",
-            Self::R11 => "r11",
-            Self::R12 => "r12",
-            Self::R13 => "r13",
-            Self::R14 => "r14",
-            Self::R15 => "r15",
-            Self::CPSR => "cpsr",
-            Self::SPSR => "spsr",
-
-            Self::SP => "r13",
-            Self::LR => "r14",
-            Self::PC => "r15",
+            Self::R0 => 0,
+            Self::R1 => 1,
+            Self::R2 => 2,
+            Self::R3 => 3,
+            Self::R4 => 4,
+            Self::R5 => 5,
+            Self::R6 => 6,
+            Self::R7 => 7,
+            Self::R8 => 8,
+            Self::R9 => 9,
+            Self::R10 => 10,
+            Self::R11 => 11,
+            Self::R12 => 12,
+            Self::R13 | Self::SP => 13,
+            Self::R14 | Self::LR => 14,
+            Self::R15 | Self::PC => 15,
+            Self::CPSR => DESMUME_REGISTER_CPSR as usize,
+            Self::SPSR => DESMUME_REGISTER_SPSR as usize,
+        }
+    }
+}
+
+/// Snapshot of the registers of a processor in its current mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Registers {
+    /// R0 - R15.
+    pub r: [u32; 16],
+    pub cpsr: u32,
+    pub spsr: u32,
+}
+
+impl Registers {
+    pub fn get(&self, reg: Register) -> u32 {
+        match reg.index() {
+            16 => self.cpsr,
+            17 => self.spsr,
+            i => self.r[i],
         }
     }
+
+    pub fn pc(&self) -> u32 {
+        self.r[15]
+    }
 }
 
 /// Try from register number. If number is not a valid register, errors.
@@ -125,7 +184,10 @@ impl DeSmuMEMemory {
     /// }
     /// ```
     pub fn u8(&self) -> MemIndexWrapper<TypedMemoryAccessor<&DeSmuMEMemory, u8>, u8> {
-        MemIndexWrapper(TypedMemoryAccessor(self, PhantomData), PhantomData)
+        MemIndexWrapper(
+            TypedMemoryAccessor(self, AccessScope::default(), PhantomData),
+            PhantomData,
+        )
     }
 
     /// Allows writing to the memory using a &mut \[u8]-like type.
@@ -148,7 +210,10 @@ impl DeSmuMEMemory {
     /// }
     /// ```
     pub fn u8_mut(&mut self) -> MemIndexWrapper<TypedMemoryAccessor<&mut DeSmuMEMemory, u8>, u8> {
-        MemIndexWrapper(TypedMemoryAccessor(self, PhantomData), PhantomData)
+        MemIndexWrapper(
+            TypedMemoryAccessor(self, AccessScope::default(), PhantomData),
+            PhantomData,
+        )
     }
 
     /// Allows reading the memory using a &\[u16]-like type. Please note that reading memory always copies.
@@ -157,7 +222,10 @@ impl DeSmuMEMemory {
     ///
     /// See [`DeSmuMEMemory::u8`] for info on how to use this type.
     pub fn u16(&self) -> MemIndexWrapper<TypedMemoryAccessor<&DeSmuMEMemory, u16>, u16> {
-        MemIndexWrapper(TypedMemoryAccessor(self, PhantomData), PhantomData)
+        MemIndexWrapper(
+            TypedMemoryAccessor(self, AccessScope::default(), PhantomData),
+            PhantomData,
+        )
     }
 
     /// Allows writing to the memory using a &mut \[u16]-like type.
@@ -168,7 +236,10 @@ impl DeSmuMEMemory {
     pub fn u16_mut(
         &mut self,
     ) -> MemIndexWrapper<TypedMemoryAccessor<&mut DeSmuMEMemory, u16>, u16> {
-        MemIndexWrapper(TypedMemoryAccessor(self, PhantomData), PhantomData)
+        MemIndexWrapper(
+            TypedMemoryAccessor(self, AccessScope::default(), PhantomData),
+            PhantomData,
+        )
     }
 
     /// Allows reading the memory using a &\[u32]-like type. Please note that reading memory always copies.
@@ -177,7 +248,10 @@ impl DeSmuMEMemory {
     ///
     /// See [`DeSmuMEMemory::u8`] for info on how to use this type.
     pub fn u32(&self) -> MemIndexWrapper<TypedMemoryAccessor<&DeSmuMEMemory, u32>, u32> {
-        MemIndexWrapper(TypedMemoryAccessor(self, PhantomData), PhantomData)
+        MemIndexWrapper(
+            TypedMemoryAccessor(self, AccessScope::default(), PhantomData),
+            PhantomData,
+        )
     }
 
     /// Allows writing to the memory using a &mut \[u32]-like type.
@@ -188,21 +262,30 @@ impl DeSmuMEMemory {
     pub fn u32_mut(
         &mut self,
     ) -> MemIndexWrapper<TypedMemoryAccessor<&mut DeSmuMEMemory, u32>, u32> {
-        MemIndexWrapper(TypedMemoryAccessor(self, PhantomData), PhantomData)
+        MemIndexWrapper(
+            TypedMemoryAccessor(self, AccessScope::default(), PhantomData),
+            PhantomData,
+        )
     }
 
     /// Allows reading the memory using a &\[i8]-like type. Please note that reading memory always copies.
     ///
     /// See [`DeSmuMEMemory::u8`] for info on how to use this type.
     pub fn i8(&self) -> MemIndexWrapper<TypedMemoryAccessor<&DeSmuMEMemory, i8>, i8> {
-        MemIndexWrapper(TypedMemoryAccessor(self, PhantomData), PhantomData)
+        MemIndexWrapper(
+            TypedMemoryAccessor(self, AccessScope::default(), PhantomData),
+            PhantomData,
+        )
     }
 
     /// Allows writing to the memory using a &mut \[i8]-like type.
     ///
     /// See [`DeSmuMEMemory::u8_mut`] for info on how to use this type.
     pub fn i8_mut(&mut self) -> MemIndexWrapper<TypedMemoryAccessor<&mut DeSmuMEMemory, i8>, i8> {
-        MemIndexWrapper(TypedMemoryAccessor(self, PhantomData), PhantomData)
+        MemIndexWrapper(
+            TypedMemoryAccessor(self, AccessScope::default(), PhantomData),
+            PhantomData,
+        )
     }
 
     /// Allows reading the memory using a &\[i16]-like type. Please note that reading memory always copies.
@@ -211,7 +294,10 @@ impl DeSmuMEMemory {
     ///
     /// See [`DeSmuMEMemory::u8`] for info on how to use this type.
     pub fn i16(&self) -> MemIndexWrapper<TypedMemoryAccessor<&DeSmuMEMemory, i16>, i16> {
-        MemIndexWrapper(TypedMemoryAccessor(self, PhantomData), PhantomData)
+        MemIndexWrapper(
+            TypedMemoryAccessor(self, AccessScope::default(), PhantomData),
+            PhantomData,
+        )
     }
 
     /// Allows writing to the memory using a &mut \[i16]-like type.
@@ -222,7 +308,10 @@ impl DeSmuMEMemory {
     pub fn i16_mut(
         &mut self,
     ) -> MemIndexWrapper<TypedMemoryAccessor<&mut DeSmuMEMemory, i16>, i16> {
-        MemIndexWrapper(TypedMemoryAccessor(self, PhantomData), PhantomData)
+        MemIndexWrapper(
+            TypedMemoryAccessor(self, AccessScope::default(), PhantomData),
+            PhantomData,
+        )
     }
 
     /// Allows reading the memory using a &\[i32]-like type. Please note that reading memory always copies.
@@ -230,7 +319,10 @@ impl DeSmuMEMemory {
     ///
     /// See [`DeSmuMEMemory::u8`] for info on how to use this type.
     pub fn i32(&self) -> MemIndexWrapper<TypedMemoryAccessor<&DeSmuMEMemory, i32>, i32> {
-        MemIndexWrapper(TypedMemoryAccessor(self, PhantomData), PhantomData)
+        MemIndexWrapper(
+            TypedMemoryAccessor(self, AccessScope::default(), PhantomData),
+            PhantomData,
+        )
     }
 
     /// Allows writing to the memory using a &mut \[i32]-like type.
@@ -241,41 +333,178 @@ impl DeSmuMEMemory {
     pub fn i32_mut(
         &mut self,
     ) -> MemIndexWrapper<TypedMemoryAccessor<&mut DeSmuMEMemory, i32>, i32> {
-        MemIndexWrapper(TypedMemoryAccessor(self, PhantomData), PhantomData)
+        MemIndexWrapper(
+            TypedMemoryAccessor(self, AccessScope::default(), PhantomData),
+            PhantomData,
+        )
+    }
+
+    /// Returns a view of the memory that is scoped to the given region. Full and open ranges
+    /// used on the accessors of the view resolve to the bounds of the region.
+    ///
+    /// # Usage example
+    /// ```rs
+    /// use rs_desmume::DeSmuMEMemory;
+    /// use rs_desmume::mem::{IndexMove, MemoryRegion};
+    ///
+    /// fn example(mem: DeSmuMEMemory) {
+    ///     let main_ram: Vec<u32> = mem.region(MemoryRegion::MainRam).u32().index_move(..);
+    /// }
+    /// ```
+    ///
+    /// Regions that are only visible to the ARM7 are accessed through the ARM7 bus,
+    /// all others through the ARM9 bus.
+    pub fn region(&self, region: MemoryRegion) -> MemoryView<&DeSmuMEMemory> {
+        MemoryView::new(self, AccessScope::for_region(region))
+    }
+
+    /// Returns a mutable view of the memory that is scoped to the given region.
+    ///
+    /// See [`DeSmuMEMemory::region`].
+    pub fn region_mut(&mut self, region: MemoryRegion) -> MemoryView<&mut DeSmuMEMemory> {
+        MemoryView::new(self, AccessScope::for_region(region))
+    }
+
+    /// Returns a view of the memory as seen by the given processor. All other accessors on
+    /// [`DeSmuMEMemory`] use the ARM9 view of the bus.
+    ///
+    /// # Usage example
+    /// ```rs
+    /// use rs_desmume::DeSmuMEMemory;
+    /// use rs_desmume::mem::{IndexMove, MemoryRegion, Processor};
+    ///
+    /// fn example(mem: DeSmuMEMemory) {
+    ///     let a: u32 = mem.cpu(Processor::Arm7).u32().index_move(0x3800000);
+    ///     let wram: Vec<u8> = mem
+    ///         .cpu(Processor::Arm7)
+    ///         .in_region(MemoryRegion::Arm7Wram)
+    ///         .u8()
+    ///         .index_move(..);
+    /// }
+    /// ```
+    pub fn cpu(&self, processor: Processor) -> MemoryView<&DeSmuMEMemory> {
+        MemoryView::new(self, AccessScope::for_processor(processor))
+    }
+
+    /// Returns a mutable view of the memory as seen by the given processor.
+    ///
+    /// See [`DeSmuMEMemory::cpu`].
+    pub fn cpu_mut(&mut self, processor: Processor) -> MemoryView<&mut DeSmuMEMemory> {
+        MemoryView::new(self, AccessScope::for_processor(processor))
     }
 
     /// Reads a CString (\0 terminated) starting at the given memory location.
     pub fn read_cstring(&self, start: u32) -> CString {
-        let mut buffer: Vec<u8> = Vec::with_capacity(64);
-        let mut addr = start as i32;
-        let mut cur_byte = unsafe { desmume_memory_read_byte(addr) };
-        while cur_byte != 0 {
-            buffer.push(cur_byte);
-            addr += 1;
-            cur_byte = unsafe { desmume_memory_read_byte(addr) };
-        }
-        // SAFETY: cur_byte was never added to buffer when it was 0.
-        unsafe { CString::from_vec_unchecked(buffer) }
+        self.cpu(Processor::Arm9).read_cstring(start)
     }
 
     pub fn get_reg(&self, processor: Processor, reg: Register) -> u32 {
-        let mut bytes = format!("{}.{}", processor.get_name(), reg.get_name()).into_bytes();
-        bytes.push(0);
-        let mut cchars = bytes
-            .into_iter()
-            .map(|b| b as c_char)
-            .collect::<Vec<c_char>>();
-        unsafe { desmume_memory_read_register(cchars.as_mut_ptr()) }
+        unsafe { desmume_cpu_get_register(processor.id(), reg.index() as c_int) }
     }
 
     pub fn set_reg(&mut self, processor: Processor, reg: Register, value: u32) {
-        let mut bytes = format!("{}.{}", processor.get_name(), reg.get_name()).into_bytes();
-        bytes.push(0);
-        let mut cchars = bytes
-            .into_iter()
-            .map(|b| b as c_char)
-            .collect::<Vec<c_char>>();
-        unsafe { desmume_memory_write_register(cchars.as_mut_ptr(), value) }
+        unsafe { desmume_cpu_set_register(processor.id(), reg.index() as c_int, value) }
+    }
+
+    /// Read all registers of the given processor in its current mode at once.
+    pub fn registers(&self, processor: Processor) -> Registers {
+        self.cpu_state(processor).registers
+    }
+
+    /// Snapshot of the full register file of the given processor, including the banked
+    /// registers of all modes.
+    pub fn cpu_state(&self, processor: Processor) -> CpuState {
+        let mut raw = desmume_sys::CpuState::default();
+        unsafe { desmume_cpu_get_state(processor.id(), &mut raw) };
+        CpuState::new(processor, &raw)
+    }
+
+    /// The decoded CPSR of the given processor.
+    pub fn cpsr(&self, processor: Processor) -> Cpsr {
+        Cpsr::from(self.get_reg(processor, Register::CPSR))
+    }
+
+    /// Snapshot of the CP15 state of the ARM9: TCM locations, MPU regions and cache control.
+    pub fn cp15(&self) -> Cp15State {
+        let mut raw = desmume_sys::Cp15State::default();
+        unsafe { desmume_cp15_get_state(&mut raw) };
+        Cp15State::from(&raw)
+    }
+
+    /// Track the calls and returns of the processor during execution, so
+    /// [`DeSmuMEMemory::backtrace`] can use an exact shadow stack. This uses a callback for every
+    /// executed instruction and slows down emulation.
+    ///
+    /// The shadow stack only knows about calls made after tracking was enabled.
+    pub fn set_call_stack_tracking(&mut self, processor: Processor, enabled: bool) {
+        backtrace::set_tracking(processor, enabled)
+    }
+
+    /// Whether calls and returns of the processor are tracked.
+    pub fn is_call_stack_tracking(&self, processor: Processor) -> bool {
+        backtrace::is_tracking(processor)
+    }
+
+    /// Best-effort call stack of the processor.
+    ///
+    /// If call stack tracking is enabled (see [`DeSmuMEMemory::set_call_stack_tracking`]), this
+    /// uses the shadow stack. Otherwise the return addresses are guessed by scanning LR and the
+    /// stack for values that point right behind a call instruction.
+    ///
+    /// # Usage example
+    /// ```rs
+    /// use rs_desmume::mem::Processor;
+    ///
+    /// for frame in emu.memory().backtrace(Processor::Arm9).frames {
+    ///     println!("called from {:08x?}", frame.call_site);
+    /// }
+    /// ```
+    pub fn backtrace(&self, processor: Processor) -> Backtrace {
+        backtrace::backtrace(self, processor)
+    }
+
+    /// Disassemble `count` instructions starting at `address`, as seen by the given processor.
+    /// ARM or Thumb mode is selected using the T bit of the processor's CPSR.
+    ///
+    /// The code is read as a block and does not trigger memory callbacks.
+    ///
+    /// # Usage example
+    /// ```rs
+    /// use rs_desmume::mem::{Processor, Register};
+    ///
+    /// let pc = emu.memory().get_reg(Processor::Arm9, Register::PC);
+    /// for instruction in emu.memory().disassemble(Processor::Arm9, pc, 8) {
+    ///     println!("{:08x}: {instruction}", instruction.address);
+    /// }
+    /// ```
+    pub fn disassemble(
+        &self,
+        processor: Processor,
+        address: u32,
+        count: usize,
+    ) -> Vec<Instruction> {
+        let mode = if self.cpsr(processor).thumb {
+            Mode::Thumb
+        } else {
+            Mode::Arm
+        };
+        self.disassemble_as(processor, address, count, mode)
+    }
+
+    /// Like [`DeSmuMEMemory::disassemble`], but with an explicit instruction set.
+    pub fn disassemble_as(
+        &self,
+        processor: Processor,
+        address: u32,
+        count: usize,
+        mode: Mode,
+    ) -> Vec<Instruction> {
+        // Enough for `count` instructions, even if they are all Thumb BL pairs.
+        let mut bytes = vec![0; count * 4];
+        read_block(processor, address, &mut bytes);
+        let mut instructions = disassemble(&bytes, address, mode, processor.into());
+        instructions.truncate(count);
+        instructions
     }
 
     pub fn get_next_instruction(&self) -> u32 {
@@ -286,37 +515,249 @@ impl DeSmuMEMemory {
         unsafe { desmume_memory_set_next_instruction(value) }
     }
 
-    /// Add a memory callback for when the memory at the specified address was changed.
+    /// Add a memory callback for when the memory at the specified address was changed by the ARM9.
+    /// Use [`DeSmuMEMemory::cpu_mut`] to watch the ARM7 instead.
     ///
     /// Setting a callback will override the previously registered one for this address.
     /// Set callback to None, to remove the callback for this address.
     ///
-    /// `size` is the maximum size that will be watched. If you set this to 4 for example,
-    ///  a range of (address, address + 3) will be monitored.
-    pub fn register_write(&mut self, address: u32, size: u16, callback: MemoryCbFnc) {
-        unsafe { desmume_memory_register_write(address as c_int, size as c_int, callback) }
+    /// `size` is the size of the watched range. If you set this to 4 for example,
+    ///  a range of (address, address + 3) will be monitored. Ranges can be of any length,
+    ///  so whole structs or buffers can be watched with one callback.
+    ///
+    /// Range writes (eg. `mem.u8_mut().index_set(a..b, ..)`) that overlap a watched range are
+    /// done value by value and trigger this callback like single value writes. Other range
+    /// writes are done as block copies.
+    pub fn register_write(&mut self, address: u32, size: u32, callback: MemoryCbFnc) {
+        self.cpu_mut(Processor::Arm9)
+            .register_write(address, size, callback)
     }
 
-    /// Add a memory callback for when the memory at the specified address was read.
+    /// Add a memory callback for when the memory at the specified address was read by the ARM9.
+    /// Use [`DeSmuMEMemory::cpu_mut`] to watch the ARM7 instead.
     ///
     /// Setting a callback will override the previously registered one for this address.
     /// Set callback to None, to remove the callback for this address.
     ///
-    /// `size` is the maximum size that will be watched. If you set this to 4 for example,
-    ///  a range of (address, address + 3) will be monitored.
-    pub fn register_read(&mut self, address: u32, size: u16, callback: MemoryCbFnc) {
-        unsafe { desmume_memory_register_read(address as c_int, size as c_int, callback) }
+    /// `size` is the size of the watched range. If you set this to 4 for example,
+    ///  a range of (address, address + 3) will be monitored. Ranges can be of any length,
+    ///  so whole structs or buffers can be watched with one callback.
+    ///
+    /// Range reads (eg. `mem.u8().index_move(a..b)`) that overlap a watched range are done
+    /// value by value and trigger this callback like single value reads. Other range reads are
+    /// done as block copies.
+    pub fn register_read(&mut self, address: u32, size: u32, callback: MemoryCbFnc) {
+        self.cpu_mut(Processor::Arm9)
+            .register_read(address, size, callback)
     }
 
-    /// Add a memory callback for when the memory at the specified address was read.
+    /// Add a memory callback for when the instruction at the specified address is executed by the
+    /// ARM9. Use [`DeSmuMEMemory::cpu_mut`] to watch the ARM7 instead.
     ///
     /// Setting a callback will override the previously registered one for this address.
     /// Set callback to None, to remove the callback for this address.
     ///
-    /// `size` is the maximum size that will be watched. If you set this to 4 for example,
-    ///  a range of (address, address + 3) will be monitored.
+    /// This watches the single instruction starting at `address`, in both ARM and Thumb mode.
+    /// Use [`DeSmuMEMemory::register_exec_range`] to watch a range of code.
     pub fn register_exec(&mut self, address: u32, callback: MemoryCbFnc) {
-        unsafe { desmume_memory_register_exec(address as c_int, 2, callback) }
+        self.cpu_mut(Processor::Arm9)
+            .register_exec(address, callback)
+    }
+
+    /// Add a memory callback for when any instruction between `address` and `address + size - 1`
+    /// is executed by the ARM9, in both ARM and Thumb mode. This can be used to watch whole
+    /// functions or overlays. Use [`DeSmuMEMemory::cpu_mut`] to watch the ARM7 instead.
+    ///
+    /// Setting a callback will override the previously registered one for this address.
+    /// Set callback to None, to remove the callback for this address.
+    pub fn register_exec_range(&mut self, address: u32, size: u32, callback: MemoryCbFnc) {
+        self.cpu_mut(Processor::Arm9)
+            .register_exec_range(address, size, callback)
+    }
+
+    /// Call `callback` whenever the ARM9 writes to the memory between `address` and
+    /// `address + size - 1`. Use [`DeSmuMEMemory::cpu_mut`] to watch the ARM7 instead.
+    ///
+    /// Any number of hooks can watch the same address. The hook stays registered until the
+    /// returned handle is dropped or the callback returns [`HookAction::Unregister`].
+    ///
+    /// Hooks share the callback slots of DeSmuME with [`DeSmuMEMemory::register_write`], so
+    /// don't mix both on the same address.
+    ///
+    /// # Usage example
+    /// ```rs
+    /// use rs_desmume::DeSmuMEMemory;
+    /// use rs_desmume::mem::HookAction;
+    ///
+    /// fn example(mem: &mut DeSmuMEMemory) {
+    ///     let handle = mem.hook_write(0x2000000, 4, |ctx| {
+    ///         println!("write to {:#x}", ctx.address);
+    ///         HookAction::Continue
+    ///     });
+    ///     // ...
+    ///     drop(handle);
+    /// }
+    /// ```
+    pub fn hook_write<F>(&mut self, address: u32, size: u32, callback: F) -> HookHandle
+    where
+        F: FnMut(&HookContext) -> HookAction + Send + 'static,
+    {
+        self.cpu_mut(Processor::Arm9)
+            .hook_write(address, size, callback)
+    }
+
+    /// Call `callback` whenever the ARM9 reads the memory between `address` and
+    /// `address + size - 1`. See [`DeSmuMEMemory::hook_write`].
+    pub fn hook_read<F>(&mut self, address: u32, size: u32, callback: F) -> HookHandle
+    where
+        F: FnMut(&HookContext) -> HookAction + Send + 'static,
+    {
+        self.cpu_mut(Processor::Arm9)
+            .hook_read(address, size, callback)
+    }
+
+    /// Call `callback` whenever the ARM9 executes the instruction at `address`, in ARM or
+    /// Thumb mode. See [`DeSmuMEMemory::hook_write`].
+    pub fn hook_exec<F>(&mut self, address: u32, callback: F) -> HookHandle
+    where
+        F: FnMut(&HookContext) -> HookAction + Send + 'static,
+    {
+        self.cpu_mut(Processor::Arm9).hook_exec(address, callback)
+    }
+
+    /// Call `callback` for every instruction the ARM9 executes between `address` and
+    /// `address + size - 1`, in ARM or Thumb mode. See [`DeSmuMEMemory::hook_write`].
+    pub fn hook_exec_range<F>(&mut self, address: u32, size: u32, callback: F) -> HookHandle
+    where
+        F: FnMut(&HookContext) -> HookAction + Send + 'static,
+    {
+        self.cpu_mut(Processor::Arm9)
+            .hook_exec_range(address, size, callback)
+    }
+
+    /// Replace the function at `address` with `handler`. Bit 0 of the address is ignored.
+    ///
+    /// When the processor reaches the address, the handler runs instead of the function: it can
+    /// read the arguments and memory through the [`PatchContext`] and set the return registers.
+    /// Execution then continues at the return address in LR, so the original code never runs,
+    /// unless the handler calls [`PatchContext::fall_through`].
+    ///
+    /// The patch is an exec hook: it stays active until the returned handle is dropped or the
+    /// handler returns [`HookAction::Unregister`]. See [`DeSmuMEMemory::hook_write`].
+    ///
+    /// # Usage example
+    /// ```rs
+    /// use rs_desmume::mem::{HookAction, Processor};
+    ///
+    /// // int Rand(int max): always roll the maximum.
+    /// let handle = emu.memory_mut().patch_function(Processor::Arm9, 0x02001234, |ctx| {
+    ///     ctx.set_return(ctx.args[0] - 1);
+    ///     HookAction::Continue
+    /// });
+    /// ```
+    pub fn patch_function<F>(
+        &mut self,
+        processor: Processor,
+        address: u32,
+        handler: F,
+    ) -> HookHandle
+    where
+        F: FnMut(&mut PatchContext) -> HookAction + Send + 'static,
+    {
+        patch::patch_function(self, processor, address, handler)
+    }
+
+    /// Call `callback` whenever the processor calls the BIOS function `number` with a SWI
+    /// instruction, before the function runs.
+    ///
+    /// The hook can observe the call through [`SwiContext::args`], or replace the BIOS function
+    /// with [`SwiContext::replace`]. If any hook replaces the call, the BIOS function is skipped.
+    /// See [`DeSmuMEMemory::hook_write`] for how long hooks stay registered.
+    ///
+    /// # Usage example
+    /// ```rs
+    /// use rs_desmume::mem::{HookAction, Processor};
+    ///
+    /// // Log LZ77 decompression.
+    /// let handle = emu.memory_mut().hook_swi(Processor::Arm9, 0x11, |ctx| {
+    ///     println!("LZ77 {:#x} -> {:#x} at {:#x}", ctx.args[0], ctx.args[1], ctx.address);
+    ///     HookAction::Continue
+    /// });
+    /// // Replace Div.
+    /// let handle = emu.memory_mut().hook_swi(Processor::Arm9, 0x09, |ctx| {
+    ///     let (n, d) = (ctx.args[0] as i32, ctx.args[1] as i32);
+    ///     let q = n.wrapping_div(d);
+    ///     ctx.replace([q as u32, n.wrapping_rem(d) as u32, ctx.args[2], q.unsigned_abs()]);
+    ///     HookAction::Continue
+    /// });
+    /// ```
+    pub fn hook_swi<F>(&mut self, processor: Processor, number: u8, callback: F) -> HookHandle
+    where
+        F: FnMut(&mut SwiContext) -> HookAction + Send + 'static,
+    {
+        add_swi_hook(processor, number, callback)
+    }
+
+    /// Call `callback` whenever an interrupt of the processor is raised or acknowledged.
+    ///
+    /// Like memory hooks, event hooks stay registered until the returned handle is dropped or
+    /// the callback returns [`HookAction::Unregister`].
+    ///
+    /// # Usage example
+    /// ```rs
+    /// use rs_desmume::mem::{HookAction, IrqEventKind, IrqSource, Processor};
+    ///
+    /// let handle = emu.memory_mut().hook_irq(Processor::Arm9, |event| {
+    ///     if event.kind == IrqEventKind::Raised && event.source == IrqSource::IpcSync {
+    ///         println!("IPC sync at {}", event.cycles);
+    ///     }
+    ///     HookAction::Continue
+    /// });
+    /// ```
+    pub fn hook_irq<F>(&mut self, processor: Processor, mut callback: F) -> HookHandle
+    where
+        F: FnMut(&IrqEvent) -> HookAction + Send + 'static,
+    {
+        add_event_hook(processor, move |event| match event {
+            HardwareEvent::Irq(event) => callback(event),
+            _ => HookAction::Continue,
+        })
+    }
+
+    /// Call `callback` whenever a DMA transfer of the processor starts. Repeating transfers
+    /// report every repetition. See [`DeSmuMEMemory::hook_irq`].
+    pub fn hook_dma<F>(&mut self, processor: Processor, mut callback: F) -> HookHandle
+    where
+        F: FnMut(&DmaEvent) -> HookAction + Send + 'static,
+    {
+        add_event_hook(processor, move |event| match event {
+            HardwareEvent::Dma(event) => callback(event),
+            _ => HookAction::Continue,
+        })
+    }
+
+    /// Call `callback` whenever a timer of the processor overflows. See
+    /// [`DeSmuMEMemory::hook_irq`].
+    pub fn hook_timer_overflow<F>(&mut self, processor: Processor, mut callback: F) -> HookHandle
+    where
+        F: FnMut(&TimerEvent) -> HookAction + Send + 'static,
+    {
+        add_event_hook(processor, move |event| match event {
+            HardwareEvent::TimerOverflow(event) => callback(event),
+            _ => HookAction::Continue,
+        })
+    }
+
+    /// Call `callback` whenever the processor sends a value through the IPC FIFO or receives one
+    /// from it. See [`DeSmuMEMemory::hook_irq`].
+    pub fn hook_ipc_fifo<F>(&mut self, processor: Processor, mut callback: F) -> HookHandle
+    where
+        F: FnMut(&IpcFifoEvent) -> HookAction + Send + 'static,
+    {
+        add_event_hook(processor, move |event| match event {
+            HardwareEvent::IpcFifo(event) => callback(event),
+            _ => HookAction::Continue,
+        })
     }
 }
 
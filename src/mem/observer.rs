@@ -0,0 +1,88 @@
+//! Internal per-instruction observers, used by the tracer, the profiler and the call stack
+//! tracking. DeSmuME only supports one instruction callback per processor, so all observers are
+//! dispatched from a single trampoline.
+
+use crate::mem::Processor;
+use desmume_sys::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// An instruction that is about to be executed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct InstructionEvent {
+    pub(crate) processor: Processor,
+    pub(crate) address: u32,
+    pub(crate) opcode: u32,
+    pub(crate) thumb: bool,
+    /// Emulated time since the last reset, in ARM9 cycles.
+    pub(crate) cycles: u64,
+}
+
+type ObserverCallback = Box<dyn FnMut(&InstructionEvent) + Send>;
+
+struct Observer {
+    id: u64,
+    processor: Processor,
+    callback: ObserverCallback,
+}
+
+static OBSERVERS: Mutex<Vec<Observer>> = Mutex::new(Vec::new());
+static NEXT_OBSERVER_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Keeps an observer registered. The observer is removed when the handle is dropped.
+pub(crate) struct ObserverHandle {
+    id: u64,
+}
+
+impl Drop for ObserverHandle {
+    fn drop(&mut self) {
+        let mut observers = OBSERVERS.lock().unwrap();
+        if let Some(pos) = observers.iter().position(|o| o.id == self.id) {
+            let observer = observers.remove(pos);
+            sync_registration(&observers, observer.processor);
+        }
+    }
+}
+
+/// Call `callback` before every instruction the processor executes.
+///
+/// Observers must not add or remove observers themselves.
+pub(crate) fn add_observer<F>(processor: Processor, callback: F) -> ObserverHandle
+where
+    F: FnMut(&InstructionEvent) + Send + 'static,
+{
+    let id = NEXT_OBSERVER_ID.fetch_add(1, Ordering::Relaxed);
+    let mut observers = OBSERVERS.lock().unwrap();
+    observers.push(Observer {
+        id,
+        processor,
+        callback: Box::new(callback),
+    });
+    sync_registration(&observers, processor);
+    ObserverHandle { id }
+}
+
+fn sync_registration(observers: &[Observer], processor: Processor) {
+    let callback: InstructionCbFnc = observers
+        .iter()
+        .any(|o| o.processor == processor)
+        .then_some(trampoline as _);
+    unsafe { desmume_cpu_set_instruction_callback(processor.id(), callback, std::ptr::null_mut()) }
+}
+
+extern "C" fn trampoline(info: *const InstructionInfo, _userdata: *mut c_void) {
+    let info = unsafe { &*info };
+    let event = InstructionEvent {
+        processor: Processor::from_id(info.processor),
+        address: info.address,
+        opcode: info.opcode,
+        thumb: info.thumb != 0,
+        cycles: info.cycles,
+    };
+    let mut observers = OBSERVERS.lock().unwrap();
+    for observer in observers.iter_mut() {
+        if observer.processor == event.processor {
+            (observer.callback)(&event);
+        }
+    }
+}
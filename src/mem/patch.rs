@@ -0,0 +1,109 @@
+use crate::mem::{read_block, DeSmuMEMemory, HookAction, HookHandle, Processor, Register};
+use crate::symbols::{Symbolize, Symbols};
+use desmume_sys::*;
+use std::marker::PhantomData;
+
+/// A call of a patched function. See [`DeSmuMEMemory::patch_function`].
+#[non_exhaustive]
+pub struct PatchContext {
+    pub processor: Processor,
+    /// Address of the patched function.
+    pub address: u32,
+    /// R0 - R3 at the time of the call.
+    pub args: [u32; 4],
+    /// The address the function returns to (LR). Bit 0 is set for Thumb callers.
+    pub return_address: u32,
+    memory: DeSmuMEMemory,
+    fall_through: bool,
+}
+
+impl PatchContext {
+    /// The memory and registers of the emulator.
+    pub fn memory(&self) -> &DeSmuMEMemory {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut DeSmuMEMemory {
+        &mut self.memory
+    }
+
+    /// Argument `n` of the call per the ARM calling convention: the first four are in R0 - R3,
+    /// the rest on the stack.
+    pub fn arg(&self, n: usize) -> u32 {
+        if n < 4 {
+            return self.args[n];
+        }
+        let sp = self.memory.get_reg(self.processor, Register::SP);
+        let mut value = [0; 4];
+        read_block(
+            self.processor,
+            sp.wrapping_add((n as u32 - 4) * 4),
+            &mut value,
+        );
+        u32::from_le_bytes(value)
+    }
+
+    /// Set the return value (R0).
+    pub fn set_return(&mut self, value: u32) {
+        self.set_register(Register::R0, value);
+    }
+
+    /// Set a register of the processor, eg. R1 for 64-bit return values.
+    pub fn set_register(&mut self, register: Register, value: u32) {
+        self.memory.set_reg(self.processor, register, value);
+    }
+
+    /// Run the original function after the handler, instead of returning to the caller. Use
+    /// this to only instrument some calls.
+    pub fn fall_through(&mut self) {
+        self.fall_through = true;
+    }
+}
+
+/// Prints the call with addresses as symbols, eg.
+/// `UpdateScore(0x00000003, 0x00000000, 0x00000000, 0x00000000) returning to GameMain+0x48`.
+impl Symbolize for PatchContext {
+    fn symbolize(&self, symbols: &Symbols) -> String {
+        let args: Vec<_> = self.args.iter().map(|a| format!("0x{a:08x}")).collect();
+        format!(
+            "{}({}) returning to {}",
+            symbols.symbolize(self.address),
+            args.join(", "),
+            symbols.symbolize(self.return_address & !1)
+        )
+    }
+}
+
+pub(crate) fn patch_function<F>(
+    mem: &mut DeSmuMEMemory,
+    processor: Processor,
+    address: u32,
+    mut handler: F,
+) -> HookHandle
+where
+    F: FnMut(&mut PatchContext) -> HookAction + Send + 'static,
+{
+    let address = address & !1;
+    mem.cpu_mut(processor).hook_exec(address, move |_| {
+        let memory = DeSmuMEMemory(PhantomData);
+        let registers = memory.registers(processor);
+        let mut ctx = PatchContext {
+            processor,
+            address,
+            args: [
+                registers.r[0],
+                registers.r[1],
+                registers.r[2],
+                registers.r[3],
+            ],
+            return_address: registers.r[14],
+            memory,
+            fall_through: false,
+        };
+        let action = handler(&mut ctx);
+        if !ctx.fall_through {
+            unsafe { desmume_cpu_set_instruction_address(processor.id(), ctx.return_address) };
+        }
+        action
+    })
+}
@@ -1,9 +1,10 @@
-use crate::mem::{IndexMove, IndexSet};
+use crate::mem::{HookKind, IndexMove, IndexSet, MemoryRegion, Processor};
 use desmume_sys::*;
 use std::marker::PhantomData;
 use std::ops::{
     Deref, DerefMut, Range, RangeFrom, RangeFull, RangeInclusive, RangeTo, RangeToInclusive,
 };
+use std::sync::Mutex;
 
 /// Numeric data types that can be read from / written into NDS memory.
 pub trait MemType: Sized + Copy {}
@@ -14,14 +15,139 @@ impl MemType for i8 {}
 impl MemType for i16 {}
 impl MemType for i32 {}
 
-const START_OF_MEMORY: u32 = 0;
-const END_OF_MEMORY: u32 = 0xFFFFFFFF; // todo: is this true?
+/// The bus and part of memory a [`TypedMemoryAccessor`] is restricted to.
+/// The region is used to resolve open ranges.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AccessScope {
+    pub(crate) processor: Processor,
+    pub(crate) region: Option<MemoryRegion>,
+}
+
+impl Default for AccessScope {
+    fn default() -> Self {
+        Self::for_processor(Processor::Arm9)
+    }
+}
+
+impl AccessScope {
+    pub(crate) fn for_processor(processor: Processor) -> Self {
+        Self {
+            processor,
+            region: None,
+        }
+    }
+
+    /// Scope for the given region, accessed through the bus of the processor that can see it.
+    pub(crate) fn for_region(region: MemoryRegion) -> Self {
+        Self {
+            processor: region.processor().unwrap_or(Processor::Arm9),
+            region: Some(region),
+        }
+    }
+
+    /// First and last address of the region this scope is restricted to.
+    ///
+    /// Panics for unscoped accessors.
+    fn full(&self) -> (u32, u32) {
+        let Some(region) = self.region else {
+            panic!("the full range `..` needs an accessor scoped to a memory region");
+        };
+        let range = region.range();
+        (*range.start(), *range.end())
+    }
+
+    /// First and last address of the region (or the mirror of it) that contains `addr`.
+    ///
+    /// Panics if the address is outside of the scoped region or not part of any known region.
+    fn around(&self, addr: u32) -> (u32, u32) {
+        let bounds = match self.region {
+            Some(region) => region.mirror_bounds(addr).unwrap_or_else(|| {
+                panic!("open range at {addr:#010x} is outside of the scoped region {region}")
+            }),
+            None => MemoryRegion::containing_on(self.processor, addr)
+                .and_then(|region| region.mirror_bounds(addr))
+                .unwrap_or_else(|| panic!("open range at {addr:#010x} is not in a memory region")),
+        };
+        (*bounds.start(), *bounds.end())
+    }
+}
+
+/// Largest block copied with a single FFI call, the length is passed as a `c_int`.
+const MAX_BLOCK_LEN: usize = c_int::MAX as usize;
+
+/// Copy a block of memory, as seen by `processor`, into `buffer`.
+pub(crate) fn read_block(processor: Processor, start: u32, buffer: &mut [u8]) {
+    for (i, chunk) in buffer.chunks_mut(MAX_BLOCK_LEN).enumerate() {
+        let address = start.wrapping_add((i * MAX_BLOCK_LEN) as u32) as c_int;
+        let len = chunk.len() as c_int;
+        match processor {
+            Processor::Arm9 => unsafe {
+                desmume_memory_read_block(address, chunk.as_mut_ptr(), len)
+            },
+            Processor::Arm7 => unsafe {
+                desmume_memory_read_block_arm7(address, chunk.as_mut_ptr(), len)
+            },
+        }
+    }
+}
+
+/// Copy `buffer` into the memory, as seen by `processor`.
+pub(crate) fn write_block(processor: Processor, start: u32, buffer: &[u8]) {
+    for (i, chunk) in buffer.chunks(MAX_BLOCK_LEN).enumerate() {
+        let address = start.wrapping_add((i * MAX_BLOCK_LEN) as u32) as c_int;
+        let len = chunk.len() as c_int;
+        match processor {
+            Processor::Arm9 => unsafe { desmume_memory_write_block(address, chunk.as_ptr(), len) },
+            Processor::Arm7 => unsafe {
+                desmume_memory_write_block_arm7(address, chunk.as_ptr(), len)
+            },
+        }
+    }
+}
+
+/// A read or write callback registered with DeSmuME.
+struct Watch {
+    kind: HookKind,
+    processor: Processor,
+    address: u32,
+    size: u32,
+}
+
+/// The registered read and write callbacks. Block copies don't trigger callbacks, so range
+/// accesses that overlap one of them are done value by value instead.
+static WATCHES: Mutex<Vec<Watch>> = Mutex::new(Vec::new());
+
+/// Record that a callback was registered (`Some(size)`) or removed (`None`) for `address`.
+pub(crate) fn set_watch(kind: HookKind, processor: Processor, address: u32, size: Option<u32>) {
+    let mut watches = WATCHES.lock().unwrap();
+    watches.retain(|w| !(w.kind == kind && w.processor == processor && w.address == address));
+    if let Some(size) = size.filter(|&size| size > 0) {
+        watches.push(Watch {
+            kind,
+            processor,
+            address,
+            size,
+        });
+    }
+}
+
+/// Whether a callback of the given kind watches any address between `start` and `end`.
+pub(crate) fn is_watched(kind: HookKind, processor: Processor, start: u32, end: u32) -> bool {
+    WATCHES.lock().unwrap().iter().any(|w| {
+        w.kind == kind
+            && w.processor == processor
+            && w.address <= end
+            && start <= w.address.saturating_add(w.size - 1)
+    })
+}
 
 /// Trait for accessing memory. You probably don't want to use this, use the `IndexMove` trait
 /// instead, if available. See [`TypedMemoryAccessor`].
 ///
 /// This trait is an implementation detail and not meant to be implemented from other crates.
 trait MemoryReadAccess<T: MemType> {
+    /// The part of memory this accessor is restricted to.
+    fn scope(&self) -> AccessScope;
     /// Read a part of memory. `end - size + 1` must be a multiple of the size of `T`.
     fn read_range(&self, start: u32, end: u32) -> Vec<T>;
     /// Read a single value from memory.
@@ -44,49 +170,73 @@ trait MemoryWriteAccess<T: MemType> {
 /// [`IndexMove`] and [`IndexSet`] (via [`MemIndexWrapper`] for compiler-compatibility reasons). It is indexed by
 /// using `u32`'s or ranges over `u32`s that address a specific space in the NDS memory. The value
 /// returned is the data at those memory locations in the format specified by `T` (eg. `u8`, `i16`, `u32`, etc.).
-pub struct TypedMemoryAccessor<M, T: MemType>(pub(crate) M, pub(crate) PhantomData<T>);
+///
+/// Open ranges (`a..`, `..b`, `..=b`) end at the bounds of the [`MemoryRegion`] (or the mirror of it)
+/// the given address belongs to. The full range (`..`) covers the whole region of accessors scoped
+/// to a region (see [`crate::DeSmuMEMemory::region`]). Ranges that can't be resolved this way,
+/// eg. the full range on an unscoped accessor or an address outside of all regions, panic.
+///
+/// Ranges are copied as a single block, unless they overlap an address watched by a read or write
+/// callback. Those ranges are accessed value by value, so the callbacks fire.
+pub struct TypedMemoryAccessor<M, T: MemType>(
+    pub(crate) M,
+    pub(crate) AccessScope,
+    pub(crate) PhantomData<T>,
+);
 
 impl_read_write_access!(
     TypedMemoryAccessor,
     u8,
     u8,
     desmume_memory_read_byte,
-    desmume_memory_write_byte
+    desmume_memory_write_byte,
+    desmume_memory_read_byte_arm7,
+    desmume_memory_write_byte_arm7
 );
 impl_read_write_access!(
     TypedMemoryAccessor,
     u16,
     u16,
     desmume_memory_read_short,
-    desmume_memory_write_short
+    desmume_memory_write_short,
+    desmume_memory_read_short_arm7,
+    desmume_memory_write_short_arm7
 );
 impl_read_write_access!(
     TypedMemoryAccessor,
     u32,
     c_ulong,
     desmume_memory_read_long,
-    desmume_memory_write_long
+    desmume_memory_write_long,
+    desmume_memory_read_long_arm7,
+    desmume_memory_write_long_arm7
 );
 impl_read_write_access!(
     TypedMemoryAccessor,
     i8,
     u8,
     desmume_memory_read_byte_signed,
-    desmume_memory_write_byte
+    desmume_memory_write_byte,
+    desmume_memory_read_byte_signed_arm7,
+    desmume_memory_write_byte_arm7
 );
 impl_read_write_access!(
     TypedMemoryAccessor,
     i16,
     u16,
     desmume_memory_read_short_signed,
-    desmume_memory_write_short
+    desmume_memory_write_short,
+    desmume_memory_read_short_signed_arm7,
+    desmume_memory_write_short_arm7
 );
 impl_read_write_access!(
     TypedMemoryAccessor,
     i32,
     c_ulong,
     desmume_memory_read_long_signed,
-    desmume_memory_write_long
+    desmume_memory_write_long,
+    desmume_memory_read_long_signed_arm7,
+    desmume_memory_write_long_arm7
 );
 
 /// A tiny wrapper to work around Rust's orphan rules limitations for the Index/IndexMut implementations of the readers and writers.
@@ -122,6 +272,9 @@ where
     type Output = Vec<T>;
 
     fn index_move(&self, index: Range<u32>) -> Self::Output {
+        if index.is_empty() {
+            return Vec::new();
+        }
         self.read_range(index.start, index.end - 1)
     }
 }
@@ -133,7 +286,8 @@ where
     type Output = Vec<T>;
 
     fn index_move(&self, index: RangeFrom<u32>) -> Self::Output {
-        self.read_range(index.start, END_OF_MEMORY)
+        let (_, end) = self.scope().around(index.start);
+        self.read_range(index.start, end)
     }
 }
 
@@ -144,7 +298,8 @@ where
     type Output = Vec<T>;
 
     fn index_move(&self, _index: RangeFull) -> Self::Output {
-        self.read_range(START_OF_MEMORY, END_OF_MEMORY)
+        let (start, end) = self.scope().full();
+        self.read_range(start, end)
     }
 }
 
@@ -155,6 +310,9 @@ where
     type Output = Vec<T>;
 
     fn index_move(&self, index: RangeInclusive<u32>) -> Self::Output {
+        if index.is_empty() {
+            return Vec::new();
+        }
         self.read_range(*index.start(), *index.end())
     }
 }
@@ -166,7 +324,11 @@ where
     type Output = Vec<T>;
 
     fn index_move(&self, index: RangeTo<u32>) -> Self::Output {
-        self.read_range(START_OF_MEMORY, index.end - 1)
+        let Some(end) = index.end.checked_sub(1) else {
+            return Vec::new();
+        };
+        let (start, _) = self.scope().around(end);
+        self.read_range(start, end)
     }
 }
 
@@ -177,7 +339,8 @@ where
     type Output = Vec<T>;
 
     fn index_move(&self, index: RangeToInclusive<u32>) -> Self::Output {
-        self.read_range(START_OF_MEMORY, index.end)
+        let (start, _) = self.scope().around(index.end);
+        self.read_range(start, index.end)
     }
 }
 
@@ -195,7 +358,9 @@ where
     U: MemoryReadAccess<T> + MemoryWriteAccess<T>,
 {
     fn index_set(&mut self, index: Range<u32>, value: &Self::Output) {
-        self.write_range(index.start, index.end - 1, value)
+        if !index.is_empty() {
+            self.write_range(index.start, index.end - 1, value)
+        }
     }
 }
 
@@ -204,7 +369,8 @@ where
     U: MemoryReadAccess<T> + MemoryWriteAccess<T>,
 {
     fn index_set(&mut self, index: RangeFrom<u32>, value: &Self::Output) {
-        self.write_range(index.start, END_OF_MEMORY, value)
+        let (_, end) = self.scope().around(index.start);
+        self.write_range(index.start, end, value)
     }
 }
 
@@ -213,7 +379,8 @@ where
     U: MemoryReadAccess<T> + MemoryWriteAccess<T>,
 {
     fn index_set(&mut self, _index: RangeFull, value: &Self::Output) {
-        self.write_range(START_OF_MEMORY, END_OF_MEMORY, value)
+        let (start, end) = self.scope().full();
+        self.write_range(start, end, value)
     }
 }
 
@@ -222,7 +389,9 @@ where
     U: MemoryReadAccess<T> + MemoryWriteAccess<T>,
 {
     fn index_set(&mut self, index: RangeInclusive<u32>, value: &Self::Output) {
-        self.write_range(*index.start(), *index.end(), value)
+        if !index.is_empty() {
+            self.write_range(*index.start(), *index.end(), value)
+        }
     }
 }
 
@@ -231,7 +400,11 @@ where
     U: MemoryReadAccess<T> + MemoryWriteAccess<T>,
 {
     fn index_set(&mut self, index: RangeTo<u32>, value: &Self::Output) {
-        self.write_range(START_OF_MEMORY, index.end - 1, value)
+        let Some(end) = index.end.checked_sub(1) else {
+            return;
+        };
+        let (start, _) = self.scope().around(end);
+        self.write_range(start, end, value)
     }
 }
 
@@ -240,6 +413,7 @@ where
     U: MemoryReadAccess<T> + MemoryWriteAccess<T>,
 {
     fn index_set(&mut self, index: RangeToInclusive<u32>, value: &Self::Output) {
-        self.write_range(START_OF_MEMORY, index.end, value)
+        let (start, _) = self.scope().around(index.end);
+        self.write_range(start, index.end, value)
     }
 }
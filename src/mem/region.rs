@@ -0,0 +1,268 @@
+use crate::mem::Processor;
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+
+/// A named region of the NDS address space.
+///
+/// Base addresses, sizes and mirroring follow GBATEK. The TCMs can be moved around by the game
+/// through CP15, the locations used here are the ones set up by the Nitro SDK.
+///
+/// Some regions are only visible to one of the processors, see [`MemoryRegion::processor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MemoryRegion {
+    /// ARM9 instruction TCM (32 KB), mirrored through the first 32 MB of the address space.
+    Itcm,
+    /// ARM9 data TCM (16 KB). Overlays main RAM.
+    Dtcm,
+    /// Main RAM (4 MB), mirrored up to 0x02FFFFFF.
+    MainRam,
+    /// Shared WRAM (32 KB), mirrored up to 0x037FFFFF.
+    SharedWram,
+    /// ARM7 WRAM (64 KB), mirrored up to 0x03FFFFFF. Only visible to the ARM7.
+    Arm7Wram,
+    /// I/O registers of both engines, the 3D engine, DMA, timers, IPC, the card interface and
+    /// the ARM7 Wi-Fi registers. Covers the whole I/O window, including unused addresses.
+    Io,
+    /// Standard palettes of both engines (2 KB), mirrored up to 0x05FFFFFF.
+    Palette,
+    /// Engine A background VRAM (up to 512 KB).
+    VramBgA,
+    /// Engine B background VRAM (up to 128 KB).
+    VramBgB,
+    /// Engine A OBJ VRAM (up to 256 KB).
+    VramObjA,
+    /// Engine B OBJ VRAM (up to 128 KB).
+    VramObjB,
+    /// VRAM banks A-I in LCDC mode (656 KB), mirrored up to 0x06FFFFFF.
+    VramLcdc,
+    /// OAM of both engines (2 KB), mirrored up to 0x07FFFFFF.
+    Oam,
+    /// GBA slot ROM (32 MB).
+    GbaRom,
+    /// GBA slot RAM (64 KB), mirrored up to 0x0AFFFFFF.
+    GbaRam,
+    /// ARM9 BIOS (32 KB), mirrored up to the end of the address space.
+    Arm9Bios,
+    /// ARM7 BIOS (16 KB).
+    Arm7Bios,
+    /// VRAM banks C and D when mapped to the ARM7 (up to 256 KB), mirrored up to 0x06FFFFFF.
+    Arm7Vram,
+    /// VRAM bank A (128 KB), at its LCDC address.
+    VramA,
+    /// VRAM bank B (128 KB), at its LCDC address.
+    VramB,
+    /// VRAM bank C (128 KB), at its LCDC address.
+    VramC,
+    /// VRAM bank D (128 KB), at its LCDC address.
+    VramD,
+    /// VRAM bank E (64 KB), at its LCDC address.
+    VramE,
+    /// VRAM bank F (16 KB), at its LCDC address.
+    VramF,
+    /// VRAM bank G (16 KB), at its LCDC address.
+    VramG,
+    /// VRAM bank H (32 KB), at its LCDC address.
+    VramH,
+    /// VRAM bank I (16 KB), at its LCDC address.
+    VramI,
+}
+
+impl MemoryRegion {
+    /// All regions. Regions that overlay others (DTCM) are listed before the regions they overlay.
+    /// The VRAM banks are part of [`MemoryRegion::VramLcdc`] and listed last.
+    pub const ALL: [MemoryRegion; 27] = [
+        MemoryRegion::Itcm,
+        MemoryRegion::Dtcm,
+        MemoryRegion::MainRam,
+        MemoryRegion::SharedWram,
+        MemoryRegion::Arm7Wram,
+        MemoryRegion::Io,
+        MemoryRegion::Palette,
+        MemoryRegion::VramBgA,
+        MemoryRegion::VramBgB,
+        MemoryRegion::VramObjA,
+        MemoryRegion::VramObjB,
+        MemoryRegion::VramLcdc,
+        MemoryRegion::Oam,
+        MemoryRegion::GbaRom,
+        MemoryRegion::GbaRam,
+        MemoryRegion::Arm9Bios,
+        MemoryRegion::Arm7Bios,
+        MemoryRegion::Arm7Vram,
+        MemoryRegion::VramA,
+        MemoryRegion::VramB,
+        MemoryRegion::VramC,
+        MemoryRegion::VramD,
+        MemoryRegion::VramE,
+        MemoryRegion::VramF,
+        MemoryRegion::VramG,
+        MemoryRegion::VramH,
+        MemoryRegion::VramI,
+    ];
+
+    /// `(base, size, last address of the mirror window)`
+    fn layout(self) -> (u32, u32, u32) {
+        match self {
+            Self::Itcm => (0x01FF8000, 0x8000, 0x01FFFFFF),
+            Self::Dtcm => (0x027E0000, 0x4000, 0x027E3FFF),
+            Self::MainRam => (0x02000000, 0x400000, 0x02FFFFFF),
+            Self::SharedWram => (0x03000000, 0x8000, 0x037FFFFF),
+            Self::Arm7Wram => (0x03800000, 0x10000, 0x03FFFFFF),
+            Self::Io => (0x04000000, 0x1000000, 0x04FFFFFF),
+            Self::Palette => (0x05000000, 0x800, 0x05FFFFFF),
+            Self::VramBgA => (0x06000000, 0x80000, 0x061FFFFF),
+            Self::VramBgB => (0x06200000, 0x20000, 0x063FFFFF),
+            Self::VramObjA => (0x06400000, 0x40000, 0x065FFFFF),
+            Self::VramObjB => (0x06600000, 0x20000, 0x067FFFFF),
+            Self::VramLcdc => (0x06800000, 0xA4000, 0x06FFFFFF),
+            Self::Oam => (0x07000000, 0x800, 0x07FFFFFF),
+            Self::GbaRom => (0x08000000, 0x2000000, 0x09FFFFFF),
+            Self::GbaRam => (0x0A000000, 0x10000, 0x0AFFFFFF),
+            Self::Arm9Bios => (0xFFFF0000, 0x8000, 0xFFFFFFFF),
+            Self::Arm7Bios => (0x00000000, 0x4000, 0x00003FFF),
+            Self::Arm7Vram => (0x06000000, 0x40000, 0x06FFFFFF),
+            Self::VramA => (0x06800000, 0x20000, 0x0681FFFF),
+            Self::VramB => (0x06820000, 0x20000, 0x0683FFFF),
+            Self::VramC => (0x06840000, 0x20000, 0x0685FFFF),
+            Self::VramD => (0x06860000, 0x20000, 0x0687FFFF),
+            Self::VramE => (0x06880000, 0x10000, 0x0688FFFF),
+            Self::VramF => (0x06890000, 0x4000, 0x06893FFF),
+            Self::VramG => (0x06894000, 0x4000, 0x06897FFF),
+            Self::VramH => (0x06898000, 0x8000, 0x0689FFFF),
+            Self::VramI => (0x068A0000, 0x4000, 0x068A3FFF),
+        }
+    }
+
+    /// The processor this region is visible to, or `None` if both processors can access it.
+    pub fn processor(self) -> Option<Processor> {
+        match self {
+            Self::Itcm
+            | Self::Dtcm
+            | Self::Palette
+            | Self::VramBgA
+            | Self::VramBgB
+            | Self::VramObjA
+            | Self::VramObjB
+            | Self::VramLcdc
+            | Self::Oam
+            | Self::Arm9Bios
+            | Self::VramA
+            | Self::VramB
+            | Self::VramC
+            | Self::VramD
+            | Self::VramE
+            | Self::VramF
+            | Self::VramG
+            | Self::VramH
+            | Self::VramI => Some(Processor::Arm9),
+            Self::Arm7Wram | Self::Arm7Bios | Self::Arm7Vram => Some(Processor::Arm7),
+            Self::MainRam | Self::SharedWram | Self::Io | Self::GbaRom | Self::GbaRam => None,
+        }
+    }
+
+    /// Returns whether the region can be accessed by the given processor.
+    pub fn is_visible_to(self, processor: Processor) -> bool {
+        self.processor().is_none_or(|p| p == processor)
+    }
+
+    /// The first address of the region.
+    pub fn base(self) -> u32 {
+        self.layout().0
+    }
+
+    /// The size of the region in bytes (not counting mirrors).
+    pub fn size(self) -> u32 {
+        self.layout().1
+    }
+
+    /// The addresses of the region itself, without mirrors.
+    pub fn range(self) -> RangeInclusive<u32> {
+        self.base()..=(self.base() + (self.size() - 1))
+    }
+
+    /// The full address window the region occupies, including all mirrors.
+    pub fn mirror_window(self) -> RangeInclusive<u32> {
+        let (base, size, last) = self.layout();
+        // The window always starts at a multiple of the size before the base.
+        let start = match self {
+            Self::Itcm => 0,
+            _ => base,
+        };
+        debug_assert_eq!((base - start) % size, 0);
+        start..=last
+    }
+
+    /// Returns whether `addr` lies in the region or one of its mirrors.
+    pub fn contains(self, addr: u32) -> bool {
+        self.mirror_window().contains(&addr)
+    }
+
+    /// Maps an address in one of the mirrors of this region to the address in the region itself.
+    /// Returns `None` if the address is not part of this region.
+    pub fn canonicalize(self, addr: u32) -> Option<u32> {
+        let window = self.mirror_window();
+        if !window.contains(&addr) {
+            return None;
+        }
+        Some(self.base() + (addr - window.start()) % self.size())
+    }
+
+    /// The bounds of the mirror of this region that contains `addr`.
+    /// Returns `None` if the address is not part of this region.
+    pub fn mirror_bounds(self, addr: u32) -> Option<RangeInclusive<u32>> {
+        let window = self.mirror_window();
+        if !window.contains(&addr) {
+            return None;
+        }
+        let start = addr - (addr - window.start()) % self.size();
+        let end = start.saturating_add(self.size() - 1).min(*window.end());
+        Some(start..=end)
+    }
+
+    /// Returns the region that `addr` belongs to on the ARM9 bus, if any.
+    pub fn containing(addr: u32) -> Option<MemoryRegion> {
+        Self::containing_on(Processor::Arm9, addr)
+    }
+
+    /// Returns the region that `addr` belongs to on the bus of the given processor, if any.
+    pub fn containing_on(processor: Processor, addr: u32) -> Option<MemoryRegion> {
+        Self::ALL
+            .into_iter()
+            .find(|r| r.is_visible_to(processor) && r.contains(addr))
+    }
+}
+
+impl Display for MemoryRegion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Itcm => "ITCM",
+            Self::Dtcm => "DTCM",
+            Self::MainRam => "Main RAM",
+            Self::SharedWram => "Shared WRAM",
+            Self::Arm7Wram => "ARM7 WRAM",
+            Self::Io => "I/O",
+            Self::Palette => "Palette",
+            Self::VramBgA => "VRAM (Engine A BG)",
+            Self::VramBgB => "VRAM (Engine B BG)",
+            Self::VramObjA => "VRAM (Engine A OBJ)",
+            Self::VramObjB => "VRAM (Engine B OBJ)",
+            Self::VramLcdc => "VRAM (LCDC)",
+            Self::Oam => "OAM",
+            Self::GbaRom => "GBA ROM",
+            Self::GbaRam => "GBA RAM",
+            Self::Arm9Bios => "ARM9 BIOS",
+            Self::Arm7Bios => "ARM7 BIOS",
+            Self::Arm7Vram => "VRAM (ARM7)",
+            Self::VramA => "VRAM bank A",
+            Self::VramB => "VRAM bank B",
+            Self::VramC => "VRAM bank C",
+            Self::VramD => "VRAM bank D",
+            Self::VramE => "VRAM bank E",
+            Self::VramF => "VRAM bank F",
+            Self::VramG => "VRAM bank G",
+            Self::VramH => "VRAM bank H",
+            Self::VramI => "VRAM bank I",
+        })
+    }
+}
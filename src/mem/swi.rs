@@ -0,0 +1,177 @@
+use crate::mem::hook::next_hook_id;
+use crate::mem::{DeSmuMEMemory, HookAction, HookHandle, Processor, Register};
+use crate::symbols::{Symbolize, Symbols};
+use desmume_sys::*;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+/// A software interrupt, ie. a call of a BIOS function. See [`DeSmuMEMemory::hook_swi`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SwiContext {
+    pub processor: Processor,
+    /// The number of the BIOS function.
+    pub number: u8,
+    /// Address of the SWI instruction.
+    pub address: u32,
+    pub thumb: bool,
+    /// R0 - R3 at the time of the call.
+    pub args: [u32; 4],
+    replacement: Option<[u32; 4]>,
+}
+
+impl SwiContext {
+    /// The name of the BIOS function, eg. `Div` or `LZ77UnCompReadNormalWrite8bit`.
+    pub fn name(&self) -> Option<&'static str> {
+        swi_name(self.processor, self.number)
+    }
+
+    /// Skip the BIOS function and return to the caller with R0 - R3 set to `results`. Copy
+    /// values from [`SwiContext::args`] to leave registers unchanged.
+    pub fn replace(&mut self, results: [u32; 4]) {
+        self.replacement = Some(results);
+    }
+
+    /// Whether a hook replaced the BIOS function.
+    pub fn is_replaced(&self) -> bool {
+        self.replacement.is_some()
+    }
+}
+
+/// The name of the BIOS function with the given SWI number.
+pub fn swi_name(processor: Processor, number: u8) -> Option<&'static str> {
+    Some(match (number, processor) {
+        (0x00, _) => "SoftReset",
+        (0x03, _) => "WaitByLoop",
+        (0x04, _) => "IntrWait",
+        (0x05, _) => "VBlankIntrWait",
+        (0x06, _) => "Halt",
+        (0x07, Processor::Arm7) => "Sleep",
+        (0x08, Processor::Arm7) => "SoundBias",
+        (0x09, _) => "Div",
+        (0x0B, _) => "CpuSet",
+        (0x0C, _) => "CpuFastSet",
+        (0x0D, _) => "Sqrt",
+        (0x0E, _) => "GetCRC16",
+        (0x0F, _) => "IsDebugger",
+        (0x10, _) => "BitUnPack",
+        (0x11, _) => "LZ77UnCompReadNormalWrite8bit",
+        (0x12, _) => "LZ77UnCompReadByCallbackWrite16bit",
+        (0x13, _) => "HuffUnCompReadByCallback",
+        (0x14, _) => "RLUnCompReadNormalWrite8bit",
+        (0x15, _) => "RLUnCompReadByCallbackWrite16bit",
+        (0x16, Processor::Arm9) => "Diff8bitUnFilterWrite8bit",
+        (0x18, Processor::Arm9) => "Diff16bitUnFilter",
+        (0x1A, Processor::Arm7) => "GetSineTable",
+        (0x1B, Processor::Arm7) => "GetPitchTable",
+        (0x1C, Processor::Arm7) => "GetVolumeTable",
+        (0x1D, Processor::Arm7) => "GetBootProcs",
+        (0x1F, Processor::Arm7) => "CustomHalt",
+        _ => return None,
+    })
+}
+
+/// Prints the call with the address as a symbol, eg. `swi 0x09 Div at UpdateScore+0x1c`.
+impl Symbolize for SwiContext {
+    fn symbolize(&self, symbols: &Symbols) -> String {
+        let address = symbols.symbolize(self.address);
+        match self.name() {
+            Some(name) => format!("swi 0x{:02x} {name} at {address}", self.number),
+            None => format!("swi 0x{:02x} at {address}", self.number),
+        }
+    }
+}
+
+type SwiCallback = Arc<Mutex<dyn FnMut(&mut SwiContext) -> HookAction + Send>>;
+
+struct SwiHook {
+    id: u64,
+    processor: Processor,
+    number: u8,
+    callback: SwiCallback,
+}
+
+/// All SWI hooks. DeSmuME has one SWI callback per processor, which dispatches to them.
+static SWI_HOOKS: Mutex<Vec<SwiHook>> = Mutex::new(Vec::new());
+
+pub(crate) fn add_swi_hook<F>(processor: Processor, number: u8, callback: F) -> HookHandle
+where
+    F: FnMut(&mut SwiContext) -> HookAction + Send + 'static,
+{
+    let id = next_hook_id();
+    let mut hooks = SWI_HOOKS.lock().unwrap();
+    hooks.push(SwiHook {
+        id,
+        processor,
+        number,
+        callback: Arc::new(Mutex::new(callback)),
+    });
+    sync_registration(&hooks, processor);
+    HookHandle::new(id, remove_swi_hook)
+}
+
+fn remove_swi_hook(id: u64) {
+    let mut hooks = SWI_HOOKS.lock().unwrap();
+    if let Some(pos) = hooks.iter().position(|h| h.id == id) {
+        let hook = hooks.remove(pos);
+        sync_registration(&hooks, hook.processor);
+    }
+}
+
+fn sync_registration(hooks: &[SwiHook], processor: Processor) {
+    let callback: SwiCbFnc = hooks
+        .iter()
+        .any(|h| h.processor == processor)
+        .then_some(trampoline as _);
+    unsafe { desmume_cpu_set_swi_callback(processor.id(), callback, std::ptr::null_mut()) }
+}
+
+extern "C" fn trampoline(info: *const SwiInfo, _userdata: *mut c_void) -> c_bool {
+    let info = unsafe { &*info };
+    let processor = Processor::from_id(info.processor);
+    let number = info.number as u8;
+    // Clone the matching callbacks, so hooks can be added and removed from within callbacks.
+    let matching: Vec<(u64, SwiCallback)> = SWI_HOOKS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|h| h.processor == processor && h.number == number)
+        .map(|h| (h.id, h.callback.clone()))
+        .collect();
+    if matching.is_empty() {
+        return 0;
+    }
+
+    let mut mem = DeSmuMEMemory(PhantomData);
+    let mut ctx = SwiContext {
+        processor,
+        number,
+        address: info.address,
+        thumb: info.thumb != 0,
+        args: [
+            mem.get_reg(processor, Register::R0),
+            mem.get_reg(processor, Register::R1),
+            mem.get_reg(processor, Register::R2),
+            mem.get_reg(processor, Register::R3),
+        ],
+        replacement: None,
+    };
+    for (id, callback) in matching {
+        let Ok(mut callback) = callback.try_lock() else {
+            continue;
+        };
+        if callback(&mut ctx) == HookAction::Unregister {
+            drop(callback);
+            remove_swi_hook(id);
+        }
+    }
+
+    let Some(results) = ctx.replacement else {
+        return 0;
+    };
+    let registers = [Register::R0, Register::R1, Register::R2, Register::R3];
+    for (register, value) in registers.into_iter().zip(results) {
+        mem.set_reg(processor, register, value);
+    }
+    1
+}
@@ -0,0 +1,251 @@
+use crate::mem::hook::add_hook;
+use crate::mem::read::{set_watch, AccessScope, MemType};
+use crate::mem::{
+    DeSmuMEMemory, HookAction, HookContext, HookHandle, HookKind, IndexMove, MemIndexWrapper,
+    MemoryRegion, Processor, TypedMemoryAccessor,
+};
+use desmume_sys::*;
+use std::ffi::CString;
+use std::marker::PhantomData;
+
+/// A view of the memory as seen by one processor, optionally restricted to a region.
+/// See [`DeSmuMEMemory::cpu`] and [`DeSmuMEMemory::region`].
+///
+/// The accessors work the same way as the ones of [`DeSmuMEMemory`] and are indexed using normal
+/// memory addresses.
+pub struct MemoryView<M> {
+    mem: M,
+    scope: AccessScope,
+}
+
+impl<M> MemoryView<M> {
+    pub(crate) fn new(mem: M, scope: AccessScope) -> Self {
+        Self { mem, scope }
+    }
+
+    /// Restrict this view to the given region. Full and open ranges used on the accessors of the
+    /// view resolve to the bounds of the region.
+    pub fn in_region(self, region: MemoryRegion) -> Self {
+        Self {
+            mem: self.mem,
+            scope: AccessScope {
+                processor: self.scope.processor,
+                region: Some(region),
+            },
+        }
+    }
+
+    /// The processor whose view of the bus is used.
+    pub fn processor(&self) -> Processor {
+        self.scope.processor
+    }
+
+    /// The region this view is scoped to, if any.
+    pub fn region(&self) -> Option<MemoryRegion> {
+        self.scope.region
+    }
+}
+
+impl<M: AsRef<DeSmuMEMemory>> MemoryView<M> {
+    fn accessor<T: MemType>(&self) -> MemIndexWrapper<TypedMemoryAccessor<&DeSmuMEMemory, T>, T> {
+        MemIndexWrapper(
+            TypedMemoryAccessor(self.mem.as_ref(), self.scope, PhantomData),
+            PhantomData,
+        )
+    }
+
+    /// See [`DeSmuMEMemory::u8`].
+    pub fn u8(&self) -> MemIndexWrapper<TypedMemoryAccessor<&DeSmuMEMemory, u8>, u8> {
+        self.accessor()
+    }
+
+    /// See [`DeSmuMEMemory::u16`].
+    pub fn u16(&self) -> MemIndexWrapper<TypedMemoryAccessor<&DeSmuMEMemory, u16>, u16> {
+        self.accessor()
+    }
+
+    /// See [`DeSmuMEMemory::u32`].
+    pub fn u32(&self) -> MemIndexWrapper<TypedMemoryAccessor<&DeSmuMEMemory, u32>, u32> {
+        self.accessor()
+    }
+
+    /// See [`DeSmuMEMemory::i8`].
+    pub fn i8(&self) -> MemIndexWrapper<TypedMemoryAccessor<&DeSmuMEMemory, i8>, i8> {
+        self.accessor()
+    }
+
+    /// See [`DeSmuMEMemory::i16`].
+    pub fn i16(&self) -> MemIndexWrapper<TypedMemoryAccessor<&DeSmuMEMemory, i16>, i16> {
+        self.accessor()
+    }
+
+    /// See [`DeSmuMEMemory::i32`].
+    pub fn i32(&self) -> MemIndexWrapper<TypedMemoryAccessor<&DeSmuMEMemory, i32>, i32> {
+        self.accessor()
+    }
+
+    /// Reads a CString (\0 terminated) starting at the given memory location.
+    pub fn read_cstring(&self, start: u32) -> CString {
+        let reader = self.u8();
+        let mut buffer: Vec<u8> = Vec::with_capacity(64);
+        let mut addr = start;
+        let mut cur_byte = reader.index_move(addr);
+        while cur_byte != 0 {
+            buffer.push(cur_byte);
+            addr += 1;
+            cur_byte = reader.index_move(addr);
+        }
+        // SAFETY: cur_byte was never added to buffer when it was 0.
+        unsafe { CString::from_vec_unchecked(buffer) }
+    }
+}
+
+impl<M: AsMut<DeSmuMEMemory>> MemoryView<M> {
+    fn accessor_mut<T: MemType>(
+        &mut self,
+    ) -> MemIndexWrapper<TypedMemoryAccessor<&mut DeSmuMEMemory, T>, T> {
+        MemIndexWrapper(
+            TypedMemoryAccessor(self.mem.as_mut(), self.scope, PhantomData),
+            PhantomData,
+        )
+    }
+
+    /// See [`DeSmuMEMemory::u8_mut`].
+    pub fn u8_mut(&mut self) -> MemIndexWrapper<TypedMemoryAccessor<&mut DeSmuMEMemory, u8>, u8> {
+        self.accessor_mut()
+    }
+
+    /// See [`DeSmuMEMemory::u16_mut`].
+    pub fn u16_mut(
+        &mut self,
+    ) -> MemIndexWrapper<TypedMemoryAccessor<&mut DeSmuMEMemory, u16>, u16> {
+        self.accessor_mut()
+    }
+
+    /// See [`DeSmuMEMemory::u32_mut`].
+    pub fn u32_mut(
+        &mut self,
+    ) -> MemIndexWrapper<TypedMemoryAccessor<&mut DeSmuMEMemory, u32>, u32> {
+        self.accessor_mut()
+    }
+
+    /// See [`DeSmuMEMemory::i8_mut`].
+    pub fn i8_mut(&mut self) -> MemIndexWrapper<TypedMemoryAccessor<&mut DeSmuMEMemory, i8>, i8> {
+        self.accessor_mut()
+    }
+
+    /// See [`DeSmuMEMemory::i16_mut`].
+    pub fn i16_mut(
+        &mut self,
+    ) -> MemIndexWrapper<TypedMemoryAccessor<&mut DeSmuMEMemory, i16>, i16> {
+        self.accessor_mut()
+    }
+
+    /// See [`DeSmuMEMemory::i32_mut`].
+    pub fn i32_mut(
+        &mut self,
+    ) -> MemIndexWrapper<TypedMemoryAccessor<&mut DeSmuMEMemory, i32>, i32> {
+        self.accessor_mut()
+    }
+
+    /// Add a memory callback for when the memory at the specified address was changed by the
+    /// processor of this view. See [`DeSmuMEMemory::register_write`].
+    pub fn register_write(&mut self, address: u32, size: u32, callback: MemoryCbFnc) {
+        let watched = callback.map(|_| size);
+        set_watch(HookKind::Write, self.scope.processor, address, watched);
+        let (address, size) = (address as c_int, size as c_int);
+        match self.scope.processor {
+            Processor::Arm9 => unsafe { desmume_memory_register_write(address, size, callback) },
+            Processor::Arm7 => unsafe {
+                desmume_memory_register_write_arm7(address, size, callback)
+            },
+        }
+    }
+
+    /// Add a memory callback for when the memory at the specified address was read by the
+    /// processor of this view. See [`DeSmuMEMemory::register_read`].
+    pub fn register_read(&mut self, address: u32, size: u32, callback: MemoryCbFnc) {
+        let watched = callback.map(|_| size);
+        set_watch(HookKind::Read, self.scope.processor, address, watched);
+        let (address, size) = (address as c_int, size as c_int);
+        match self.scope.processor {
+            Processor::Arm9 => unsafe { desmume_memory_register_read(address, size, callback) },
+            Processor::Arm7 => unsafe {
+                desmume_memory_register_read_arm7(address, size, callback)
+            },
+        }
+    }
+
+    /// Add a memory callback for when the instruction at the specified address is executed by the
+    /// processor of this view. See [`DeSmuMEMemory::register_exec`].
+    pub fn register_exec(&mut self, address: u32, callback: MemoryCbFnc) {
+        self.register_exec_range(address, 1, callback)
+    }
+
+    /// Add a memory callback for when any instruction between `address` and
+    /// `address + size - 1` is executed by the processor of this view.
+    /// See [`DeSmuMEMemory::register_exec_range`].
+    pub fn register_exec_range(&mut self, address: u32, size: u32, callback: MemoryCbFnc) {
+        let (address, size) = (address as c_int, size as c_int);
+        match self.scope.processor {
+            Processor::Arm9 => unsafe { desmume_memory_register_exec(address, size, callback) },
+            Processor::Arm7 => unsafe {
+                desmume_memory_register_exec_arm7(address, size, callback)
+            },
+        }
+    }
+
+    /// Call `callback` whenever the processor of this view writes to the memory between
+    /// `address` and `address + size - 1`. See [`DeSmuMEMemory::hook_write`].
+    pub fn hook_write<F>(&mut self, address: u32, size: u32, callback: F) -> HookHandle
+    where
+        F: FnMut(&HookContext) -> HookAction + Send + 'static,
+    {
+        add_hook(
+            HookKind::Write,
+            self.scope.processor,
+            address,
+            size,
+            callback,
+        )
+    }
+
+    /// Call `callback` whenever the processor of this view reads the memory between
+    /// `address` and `address + size - 1`. See [`DeSmuMEMemory::hook_write`].
+    pub fn hook_read<F>(&mut self, address: u32, size: u32, callback: F) -> HookHandle
+    where
+        F: FnMut(&HookContext) -> HookAction + Send + 'static,
+    {
+        add_hook(
+            HookKind::Read,
+            self.scope.processor,
+            address,
+            size,
+            callback,
+        )
+    }
+
+    /// Call `callback` whenever the processor of this view executes the instruction at `address`.
+    /// See [`DeSmuMEMemory::hook_write`].
+    pub fn hook_exec<F>(&mut self, address: u32, callback: F) -> HookHandle
+    where
+        F: FnMut(&HookContext) -> HookAction + Send + 'static,
+    {
+        self.hook_exec_range(address, 1, callback)
+    }
+
+    /// Call `callback` whenever the processor of this view executes any instruction between
+    /// `address` and `address + size - 1`. See [`DeSmuMEMemory::hook_exec_range`].
+    pub fn hook_exec_range<F>(&mut self, address: u32, size: u32, callback: F) -> HookHandle
+    where
+        F: FnMut(&HookContext) -> HookAction + Send + 'static,
+    {
+        add_hook(
+            HookKind::Exec,
+            self.scope.processor,
+            address,
+            size,
+            callback,
+        )
+    }
+}
@@ -0,0 +1,536 @@
+//! Runtime structures of games built with the Nitro SDK: the OS threads, the memory arenas and
+//! heaps and the loaded overlays.
+//!
+//! ```rs
+//! use desmume_rs::nitro::OverlayTable;
+//!
+//! let nitro = emu.memory().nitro();
+//! for thread in nitro.threads(Processor::Arm9) {
+//!     println!("{} {:?} {} pc={:#010x}", thread.id, thread.state, thread.priority, thread.context.pc);
+//! }
+//! let overlays = OverlayTable::load("game.nds")?;
+//! let loaded = nitro.loaded_overlays(&overlays, Processor::Arm9);
+//! symbols.set_loaded_overlays(loaded.iter().map(|overlay| overlay.id));
+//! ```
+//!
+//! The structure layouts are those of the later SDK versions, where the thread context includes
+//! the supervisor mode stack pointer. Games built with other SDK versions may report garbage.
+
+use crate::mem::{read_block, Processor};
+use crate::symbols::Symbols;
+use crate::{DeSmuMEError, DeSmuMEMemory};
+use std::ops::Range;
+
+/// Pointers to the `OSThreadInfo` of each processor.
+const THREAD_INFO_ARM9: u32 = 0x027FFFA0;
+const THREAD_INFO_ARM7: u32 = 0x027FFFA4;
+/// The `OSArenaInfo`.
+const ARENA_INFO: u32 = 0x027FFDA0;
+
+/// Size of `OSContext`. The ARM9 context also saves the divider and square root unit.
+const CONTEXT_SIZE_ARM9: u32 = 0x64;
+const CONTEXT_SIZE_ARM7: u32 = 0x48;
+
+/// Lists longer than this are assumed to be corrupted and cut off.
+const MAX_LIST_LENGTH: usize = 1024;
+
+/// An overlay is considered loaded if at least this fraction of its RAM matches its file.
+const OVERLAY_MATCH_THRESHOLD: f64 = 0.75;
+
+/// The scheduling state of a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    /// Sleeping or waiting for a queue, mutex or message.
+    Waiting,
+    Ready,
+    Terminated,
+    Unknown(u32),
+}
+
+impl ThreadState {
+    fn from_raw(value: u32) -> Self {
+        match value {
+            0 => Self::Waiting,
+            1 => Self::Ready,
+            2 => Self::Terminated,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+/// The registers a thread saved when it was switched out (`OSContext`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadContext {
+    pub cpsr: u32,
+    /// R0 - R12.
+    pub r: [u32; 13],
+    pub sp: u32,
+    pub lr: u32,
+    /// The address the thread continues at.
+    pub pc: u32,
+    /// The stack pointer of supervisor mode.
+    pub sp_svc: u32,
+}
+
+/// A thread of the Nitro SDK OS (`OSThread`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thread {
+    /// Address of the `OSThread`.
+    pub address: u32,
+    pub id: u32,
+    pub state: ThreadState,
+    /// 0 is the highest priority, 31 the lowest.
+    pub priority: u32,
+    /// Whether this is the running thread. The context of the running thread is outdated, its
+    /// registers are those of the processor.
+    pub current: bool,
+    pub context: ThreadContext,
+    /// The lowest address of the stack (`stackTop` in the SDK).
+    pub stack_top: u32,
+    /// The address the stack starts at and grows down from (`stackBottom` in the SDK).
+    pub stack_bottom: u32,
+}
+
+/// The memory arenas of the Nitro SDK OS (`OSArenaId`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Arena {
+    Main,
+    /// Main RAM used by the ARM7.
+    MainSubPrivate,
+    /// The extended main RAM of the debugger.
+    MainExtended,
+    Itcm,
+    Dtcm,
+    /// Main RAM shared by both processors.
+    Shared,
+    /// Shared WRAM used by the ARM9.
+    WramMain,
+    /// Shared WRAM used by the ARM7.
+    WramSub,
+    /// ARM7 WRAM.
+    WramSubPrivate,
+}
+
+impl Arena {
+    pub const ALL: [Arena; 9] = [
+        Arena::Main,
+        Arena::MainSubPrivate,
+        Arena::MainExtended,
+        Arena::Itcm,
+        Arena::Dtcm,
+        Arena::Shared,
+        Arena::WramMain,
+        Arena::WramSub,
+        Arena::WramSubPrivate,
+    ];
+
+    /// The processor whose memory map the arena addresses refer to.
+    pub fn processor(self) -> Processor {
+        match self {
+            Self::MainSubPrivate | Self::WramSub | Self::WramSubPrivate => Processor::Arm7,
+            _ => Processor::Arm9,
+        }
+    }
+}
+
+/// The free memory of an arena, between its low and high pointer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArenaBounds {
+    pub arena: Arena,
+    pub lo: u32,
+    pub hi: u32,
+}
+
+/// A heap created with `OS_CreateHeap`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heap {
+    /// The heap handle.
+    pub handle: u32,
+    /// The memory of the heap, including the block headers.
+    pub range: Range<u32>,
+    /// Bytes in free blocks, including their headers.
+    pub free: u32,
+    /// Bytes in allocated blocks, including their headers.
+    pub allocated: u32,
+    pub free_blocks: usize,
+    pub allocated_blocks: usize,
+    /// Whether this is the current heap, used by `OS_Alloc`.
+    pub current: bool,
+}
+
+/// An overlay of the game, from the overlay table in the ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlay {
+    pub processor: Processor,
+    pub id: u32,
+    pub ram_address: u32,
+    /// Size of the overlay in RAM, not counting `.bss`.
+    pub ram_size: u32,
+    pub bss_size: u32,
+    /// Start and end of the table of static initializers.
+    pub static_init: Range<u32>,
+    /// The file in the ROM's file allocation table.
+    pub file_id: u32,
+    /// Whether the file is compressed.
+    pub compressed: bool,
+}
+
+impl Overlay {
+    /// The memory the overlay occupies when loaded, including `.bss`.
+    pub fn range(&self) -> Range<u32> {
+        self.ram_address
+            ..self
+                .ram_address
+                .saturating_add(self.ram_size)
+                .saturating_add(self.bss_size)
+    }
+
+    pub fn contains(&self, address: u32) -> bool {
+        self.range().contains(&address)
+    }
+}
+
+/// The overlays of a game, with their contents, read from the ROM file.
+#[derive(Debug, Clone, Default)]
+pub struct OverlayTable {
+    overlays: Vec<Overlay>,
+    /// The uncompressed contents, same order as `overlays`.
+    contents: Vec<Vec<u8>>,
+}
+
+impl OverlayTable {
+    /// Reads the ARM9 and ARM7 overlay tables from a ROM file.
+    pub fn load(rom_file_name: &str) -> Result<Self, DeSmuMEError> {
+        Self::parse(&std::fs::read(rom_file_name)?)
+    }
+
+    /// Reads the ARM9 and ARM7 overlay tables from the contents of a ROM file.
+    pub fn parse(rom: &[u8]) -> Result<Self, DeSmuMEError> {
+        let word = |offset: usize| -> Result<u32, DeSmuMEError> {
+            let bytes = rom
+                .get(offset..offset + 4)
+                .ok_or_else(|| invalid_rom(format!("offset {offset:#x} is out of bounds")))?;
+            Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+        };
+        let fat = word(0x48)?;
+        let mut table = Self::default();
+        for (processor, header) in [(Processor::Arm9, 0x50), (Processor::Arm7, 0x58)] {
+            let start = word(header)? as usize;
+            let size = word(header + 4)? as usize;
+            for entry in (start..start + size).step_by(32) {
+                let flags = word(entry + 28)?;
+                let overlay = Overlay {
+                    processor,
+                    id: word(entry)?,
+                    ram_address: word(entry + 4)?,
+                    ram_size: word(entry + 8)?,
+                    bss_size: word(entry + 12)?,
+                    static_init: word(entry + 16)?..word(entry + 20)?,
+                    file_id: word(entry + 24)?,
+                    compressed: flags & (1 << 24) != 0,
+                };
+                let fat_entry = fat as usize + overlay.file_id as usize * 8;
+                let file_start = word(fat_entry)?;
+                let file_end = word(fat_entry + 4)?;
+                let file = rom
+                    .get(file_start as usize..file_end as usize)
+                    .ok_or_else(|| {
+                        invalid_rom(format!("file of overlay {} is out of bounds", overlay.id))
+                    })?;
+                let contents = if overlay.compressed {
+                    decompress_backwards(file).ok_or_else(|| {
+                        invalid_rom(format!("overlay {} can not be decompressed", overlay.id))
+                    })?
+                } else {
+                    file.to_vec()
+                };
+                table.overlays.push(overlay);
+                table.contents.push(contents);
+            }
+        }
+        Ok(table)
+    }
+
+    /// All overlays, ARM9 overlays first.
+    pub fn overlays(&self) -> &[Overlay] {
+        &self.overlays
+    }
+
+    pub fn get(&self, processor: Processor, id: u32) -> Option<&Overlay> {
+        self.overlays
+            .iter()
+            .find(|o| o.processor == processor && o.id == id)
+    }
+
+    /// The uncompressed contents of an overlay, without `.bss`.
+    pub fn contents(&self, overlay: &Overlay) -> Option<&[u8]> {
+        let index = self.overlays.iter().position(|o| o == overlay)?;
+        Some(&self.contents[index])
+    }
+}
+
+fn invalid_rom(reason: String) -> DeSmuMEError {
+    DeSmuMEError::InvalidRom(reason)
+}
+
+/// Decompresses data compressed with the backwards LZ77 variant used for DS binaries and
+/// overlays.
+fn decompress_backwards(data: &[u8]) -> Option<Vec<u8>> {
+    let len = data.len();
+    let footer = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    if len < 8 {
+        return None;
+    }
+    let compressed_len = (footer(len - 8) & 0xFF_FFFF) as usize;
+    let header_len = (footer(len - 8) >> 24) as usize;
+    let extra_len = footer(len - 4) as usize;
+    if compressed_len > len || header_len > compressed_len {
+        return None;
+    }
+    let mut out = data.to_vec();
+    out.resize(len + extra_len, 0);
+    let end = len - compressed_len;
+    let mut src = len - header_len;
+    let mut dst = out.len();
+    while src > end {
+        src -= 1;
+        let mut flags = data[src];
+        for _ in 0..8 {
+            if src <= end {
+                break;
+            }
+            if flags & 0x80 != 0 {
+                src = src.checked_sub(2)?;
+                let info = u16::from_le_bytes([data[src], data[src + 1]]) as usize;
+                let length = (info >> 12) + 3;
+                let distance = (info & 0xFFF) + 3;
+                for _ in 0..length {
+                    dst = dst.checked_sub(1)?;
+                    *out.get_mut(dst)? = *out.get(dst + distance)?;
+                }
+            } else {
+                src -= 1;
+                dst = dst.checked_sub(1)?;
+                out[dst] = data[src];
+            }
+            flags <<= 1;
+        }
+    }
+    Some(out)
+}
+
+fn read_u32(processor: Processor, address: u32) -> u32 {
+    let mut value = [0; 4];
+    read_block(processor, address, &mut value);
+    u32::from_le_bytes(value)
+}
+
+fn read_words<const N: usize>(processor: Processor, address: u32) -> [u32; N] {
+    let mut bytes = vec![0; N * 4];
+    read_block(processor, address, &mut bytes);
+    let mut words = [0; N];
+    for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+        *word = u32::from_le_bytes(chunk.try_into().unwrap());
+    }
+    words
+}
+
+/// Nitro SDK runtime structures in the emulated memory. See the [module docs](self).
+pub struct Nitro<'a> {
+    _memory: &'a DeSmuMEMemory,
+}
+
+impl DeSmuMEMemory {
+    /// The runtime structures of the Nitro SDK, for games built with it.
+    pub fn nitro(&self) -> Nitro<'_> {
+        Nitro { _memory: self }
+    }
+}
+
+impl Nitro<'_> {
+    /// All threads of the processor, in the order of the SDK's thread list (by priority). Empty
+    /// if the OS was not initialized.
+    pub fn threads(&self, processor: Processor) -> Vec<Thread> {
+        let info_pointer = match processor {
+            Processor::Arm9 => THREAD_INFO_ARM9,
+            Processor::Arm7 => THREAD_INFO_ARM7,
+        };
+        let info = read_u32(processor, info_pointer);
+        if info == 0 {
+            return Vec::new();
+        }
+        // OSThreadInfo: isNeedRescheduling, irqDepth, current, list, switchCallback.
+        let current = read_u32(processor, info.wrapping_add(4));
+        let mut address = read_u32(processor, info.wrapping_add(8));
+        let context_size = match processor {
+            Processor::Arm9 => CONTEXT_SIZE_ARM9,
+            Processor::Arm7 => CONTEXT_SIZE_ARM7,
+        };
+        let mut threads = Vec::new();
+        while address != 0 && threads.len() < MAX_LIST_LENGTH {
+            let context = read_words::<18>(processor, address);
+            let fields = read_words::<14>(processor, address.wrapping_add(context_size));
+            threads.push(Thread {
+                address,
+                id: fields[2],
+                state: ThreadState::from_raw(fields[0]),
+                priority: fields[3],
+                current: address == current,
+                context: ThreadContext {
+                    cpsr: context[0],
+                    r: context[1..14].try_into().unwrap(),
+                    sp: context[14],
+                    lr: context[15],
+                    pc: context[16].wrapping_sub(4),
+                    sp_svc: context[17],
+                },
+                stack_top: fields[11],
+                stack_bottom: fields[12],
+            });
+            address = fields[1];
+        }
+        threads
+    }
+
+    /// The running thread of the processor.
+    pub fn current_thread(&self, processor: Processor) -> Option<Thread> {
+        self.threads(processor).into_iter().find(|t| t.current)
+    }
+
+    /// The bounds of all arenas. Empty if the arenas were not initialized.
+    pub fn arenas(&self) -> Vec<ArenaBounds> {
+        // OSArenaInfo: lo[9], hi[9], initialized.
+        let info = read_words::<19>(Processor::Arm9, ARENA_INFO);
+        if info[18] & 0xFFFF == 0 {
+            return Vec::new();
+        }
+        Arena::ALL
+            .into_iter()
+            .enumerate()
+            .map(|(i, arena)| ArenaBounds {
+                arena,
+                lo: info[i],
+                hi: info[9 + i],
+            })
+            .collect()
+    }
+
+    /// The heaps of an arena. `heap_info` is the address of `OSiHeapInfo`, the SDK's table of
+    /// heap infos per arena. Empty if no heaps were set up in the arena.
+    pub fn heaps(&self, heap_info: u32, arena: Arena) -> Vec<Heap> {
+        let processor = arena.processor();
+        let index = Arena::ALL.iter().position(|a| *a == arena).unwrap() as u32;
+        let info = read_u32(processor, heap_info.wrapping_add(index * 4));
+        if info == 0 {
+            return Vec::new();
+        }
+        // OSHeapInfo: currentHeap, numHeaps, arenaStart, arenaEnd, heapArray.
+        let [current, count, _, _, descriptors] = read_words::<5>(processor, info);
+        let mut heaps = Vec::new();
+        for handle in 0..count.min(MAX_LIST_LENGTH as u32) {
+            // HeapDesc: size, free, allocated.
+            let [size, free_list, allocated_list] =
+                read_words::<3>(processor, descriptors.wrapping_add(handle * 12));
+            if (size as i32) < 0 {
+                continue;
+            }
+            let (free, free_blocks, free_start) = walk_cells(processor, free_list);
+            let (allocated, allocated_blocks, allocated_start) =
+                walk_cells(processor, allocated_list);
+            // The blocks cover the whole heap, so the lowest one is at its start.
+            let start = free_start.min(allocated_start);
+            heaps.push(Heap {
+                handle,
+                range: start..start.wrapping_add(size),
+                free,
+                allocated,
+                free_blocks,
+                allocated_blocks,
+                current: handle == current,
+            });
+        }
+        heaps
+    }
+
+    /// The heaps of an arena, using the address of `OSiHeapInfo` from the symbols.
+    pub fn heaps_from_symbols(
+        &self,
+        symbols: &Symbols,
+        arena: Arena,
+    ) -> Result<Vec<Heap>, DeSmuMEError> {
+        let heap_info = symbols
+            .lookup("OSiHeapInfo")
+            .ok_or_else(|| DeSmuMEError::UnknownSymbol("OSiHeapInfo".to_string()))?;
+        Ok(self.heaps(heap_info, arena))
+    }
+
+    /// The overlays of the processor that are currently loaded, determined by comparing the
+    /// memory with the contents of the overlays. Of overlapping overlays only the best match
+    /// is returned.
+    pub fn loaded_overlays<'t>(
+        &self,
+        table: &'t OverlayTable,
+        processor: Processor,
+    ) -> Vec<&'t Overlay> {
+        let scores: Vec<(&Overlay, f64)> = table
+            .overlays
+            .iter()
+            .zip(&table.contents)
+            .filter(|(overlay, _)| overlay.processor == processor)
+            .map(|(overlay, contents)| (overlay, match_ratio(processor, overlay, contents)))
+            .collect();
+        scores
+            .iter()
+            .filter(|(overlay, score)| {
+                *score >= OVERLAY_MATCH_THRESHOLD
+                    && !scores.iter().any(|(other, other_score)| {
+                        other.id != overlay.id
+                            && other_score > score
+                            && other.range().start < overlay.range().end
+                            && overlay.range().start < other.range().end
+                    })
+            })
+            .map(|(overlay, _)| *overlay)
+            .collect()
+    }
+
+    /// The loaded overlay that contains `address`, if any.
+    pub fn overlay_at<'t>(
+        &self,
+        table: &'t OverlayTable,
+        processor: Processor,
+        address: u32,
+    ) -> Option<&'t Overlay> {
+        self.loaded_overlays(table, processor)
+            .into_iter()
+            .find(|overlay| overlay.contains(address))
+    }
+}
+
+/// Sums up the sizes of a list of heap blocks (`Cell`: prev, next, size). Returns the total
+/// size, the number of blocks and the lowest block address.
+fn walk_cells(processor: Processor, mut cell: u32) -> (u32, usize, u32) {
+    let mut total = 0u32;
+    let mut count = 0;
+    let mut lowest = u32::MAX;
+    while cell != 0 && count < MAX_LIST_LENGTH {
+        let [_, next, size] = read_words::<3>(processor, cell);
+        total = total.wrapping_add(size);
+        count += 1;
+        lowest = lowest.min(cell);
+        cell = next;
+    }
+    (total, count, lowest)
+}
+
+/// The fraction of bytes of the overlay in memory that match its contents.
+fn match_ratio(processor: Processor, overlay: &Overlay, contents: &[u8]) -> f64 {
+    let len = contents.len().min(overlay.ram_size as usize);
+    if len == 0 {
+        return 0.0;
+    }
+    let mut memory = vec![0; len];
+    read_block(processor, overlay.ram_address, &mut memory);
+    let matching = memory.iter().zip(contents).filter(|(a, b)| a == b).count();
+    matching as f64 / len as f64
+}
@@ -0,0 +1,390 @@
+use crate::disasm::Flow;
+use crate::mem::{add_observer, InstructionEvent, ObserverHandle, Processor};
+use crate::symbols::Symbols;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+/// Calls nested deeper than this are attributed to the outer frames.
+const MAX_STACK_DEPTH: usize = 256;
+
+/// Configuration of the profiler. See [`DeSmuMEProfiler::start`].
+#[derive(Debug, Clone)]
+pub struct ProfilerConfig {
+    /// Profile the instructions executed by the ARM9.
+    pub arm9: bool,
+    /// Profile the instructions executed by the ARM7.
+    pub arm7: bool,
+}
+
+impl Default for ProfilerConfig {
+    fn default() -> Self {
+        Self {
+            arm9: true,
+            arm7: true,
+        }
+    }
+}
+
+/// Execution statistics of one processor.
+///
+/// Cycles are measured as the emulated time between an instruction and the next one executed by
+/// the same processor, in ARM9 cycles. They are approximate, since DeSmuME runs the processors
+/// interleaved in batches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessorProfile {
+    pub processor: Processor,
+    /// How often each instruction address was executed.
+    pub instructions: HashMap<u32, u64>,
+    /// How often each basic block was entered, by the address of its first instruction. A block
+    /// starts at every target of a taken branch, call, return or exception.
+    pub blocks: HashMap<u32, u64>,
+    /// Cycles spent at each instruction address.
+    pub cycles: HashMap<u32, u64>,
+    /// How often each function was called, by entry point.
+    pub calls: HashMap<u32, u64>,
+    /// Cycles spent in each call stack. The stacks are the entry points of the called functions,
+    /// outermost first. Code running outside of any call observed during profiling has an empty
+    /// stack.
+    pub stacks: HashMap<Vec<u32>, u64>,
+}
+
+impl ProcessorProfile {
+    fn new(processor: Processor) -> Self {
+        Self {
+            processor,
+            instructions: HashMap::new(),
+            blocks: HashMap::new(),
+            cycles: HashMap::new(),
+            calls: HashMap::new(),
+            stacks: HashMap::new(),
+        }
+    }
+
+    /// The number of executed instructions.
+    pub fn total_instructions(&self) -> u64 {
+        self.instructions.values().sum()
+    }
+
+    /// The number of cycles spent executing instructions.
+    pub fn total_cycles(&self) -> u64 {
+        self.cycles.values().sum()
+    }
+
+    /// The `count` most executed instruction addresses, most executed first.
+    pub fn hot_spots(&self, count: usize) -> Vec<(u32, u64)> {
+        let mut hot_spots: Vec<_> = self.instructions.iter().map(|(&a, &n)| (a, n)).collect();
+        hot_spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hot_spots.truncate(count);
+        hot_spots
+    }
+
+    /// Statistics for every function in `symbols` that was executed, ordered by cycles, highest
+    /// first. Code outside of any symbol isn't counted.
+    pub fn functions(&self, symbols: &Symbols) -> Vec<FunctionProfile> {
+        fn function_at<'a>(
+            functions: &'a mut HashMap<u32, FunctionProfile>,
+            symbols: &Symbols,
+            address: u32,
+        ) -> Option<&'a mut FunctionProfile> {
+            let symbol = symbols.symbol_at(address)?;
+            let entry = symbol.address & !1;
+            Some(functions.entry(entry).or_insert_with(|| FunctionProfile {
+                name: symbol.name.clone(),
+                address: entry,
+                calls: 0,
+                instructions: 0,
+                cycles: 0,
+            }))
+        }
+
+        let mut functions = HashMap::new();
+        for (&address, &count) in &self.instructions {
+            if let Some(function) = function_at(&mut functions, symbols, address) {
+                function.instructions += count;
+                function.cycles += self.cycles.get(&address).copied().unwrap_or(0);
+            }
+        }
+        for (&entry, &count) in &self.calls {
+            if let Some(function) =
+                function_at(&mut functions, symbols, entry).filter(|f| f.address == entry)
+            {
+                function.calls += count;
+            }
+        }
+        let mut functions: Vec<_> = functions.into_values().collect();
+        functions.sort_by(|a, b| b.cycles.cmp(&a.cycles).then(a.address.cmp(&b.address)));
+        functions
+    }
+}
+
+/// Execution statistics of a function. See [`ProcessorProfile::functions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionProfile {
+    pub name: String,
+    /// The entry point.
+    pub address: u32,
+    /// How often the function was called.
+    pub calls: u64,
+    /// The number of instructions executed in the function itself.
+    pub instructions: u64,
+    /// The cycles spent in the function itself, not counting called functions.
+    pub cycles: u64,
+}
+
+/// The result of a profiler run. See [`DeSmuMEProfiler`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// One entry per profiled processor.
+    pub processors: Vec<ProcessorProfile>,
+}
+
+impl Profile {
+    /// The statistics of a processor, if it was profiled.
+    pub fn get(&self, processor: Processor) -> Option<&ProcessorProfile> {
+        self.processors.iter().find(|p| p.processor == processor)
+    }
+
+    /// Export the execution counts in a format modeled after lcov tracefiles, with one record
+    /// per processor. Instruction addresses take the place of line numbers and are written in hex.
+    /// Instructions of profiled functions that never ran are listed with a count of 0, if the
+    /// length of the function's symbol is known. Symbols with bit 0 set are taken to be Thumb
+    /// code:
+    ///
+    /// ```text
+    /// TN:arm9
+    /// FN:0x02000800,GameMain
+    /// FNDA:1,GameMain
+    /// FNF:1
+    /// FNH:1
+    /// DA:0x02000800,1
+    /// ...
+    /// LF:120
+    /// LH:97
+    /// end_of_record
+    /// ```
+    pub fn write_lcov(&self, mut out: impl Write, symbols: &Symbols) -> io::Result<()> {
+        for profile in &self.processors {
+            writeln!(out, "TN:{}", processor_name(profile.processor))?;
+            let mut functions = profile.functions(symbols);
+            functions.sort_by_key(|f| f.address);
+            for function in &functions {
+                writeln!(out, "FN:0x{:08x},{}", function.address, function.name)?;
+            }
+            for function in &functions {
+                writeln!(out, "FNDA:{},{}", function.calls, function.name)?;
+            }
+            writeln!(out, "FNF:{}", functions.len())?;
+            let hit = functions.iter().filter(|f| f.calls > 0).count();
+            writeln!(out, "FNH:{hit}")?;
+            let mut lines: BTreeMap<u32, u64> =
+                profile.instructions.iter().map(|(&a, &n)| (a, n)).collect();
+            for function in &functions {
+                let Some(symbol) = symbols.symbol_at(function.address) else {
+                    continue;
+                };
+                let Some(length) = symbol.length else {
+                    continue;
+                };
+                let step = if symbol.address & 1 != 0 { 2 } else { 4 };
+                let end = function.address.saturating_add(length);
+                for address in (function.address..end).step_by(step) {
+                    lines.entry(address).or_insert(0);
+                }
+            }
+            for (address, count) in &lines {
+                writeln!(out, "DA:0x{address:08x},{count}")?;
+            }
+            writeln!(out, "LF:{}", lines.len())?;
+            let hit = lines.values().filter(|&&n| n > 0).count();
+            writeln!(out, "LH:{hit}")?;
+            writeln!(out, "end_of_record")?;
+        }
+        Ok(())
+    }
+
+    /// Export the cycles per call stack in the collapsed stack format used by flame graph tools,
+    /// eg. `arm9;GameMain;UpdateScore 1234`. Functions are named with `symbols` where possible.
+    pub fn write_collapsed(&self, mut out: impl Write, symbols: &Symbols) -> io::Result<()> {
+        for profile in &self.processors {
+            let mut lines = BTreeMap::new();
+            for (stack, &cycles) in &profile.stacks {
+                let mut line = processor_name(profile.processor).to_string();
+                for &function in stack {
+                    line.push(';');
+                    line.push_str(&symbols.symbolize(function));
+                }
+                *lines.entry(line).or_insert(0) += cycles;
+            }
+            for (line, cycles) in lines {
+                if cycles > 0 {
+                    writeln!(out, "{line} {cycles}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn processor_name(processor: Processor) -> &'static str {
+    match processor {
+        Processor::Arm9 => "arm9",
+        Processor::Arm7 => "arm7",
+    }
+}
+
+struct Collector {
+    profile: ProcessorProfile,
+    previous: Option<InstructionEvent>,
+    /// Entry points and return addresses of the active calls.
+    stack: Vec<(u32, u32)>,
+    /// Call stacks are interned, so that counting cycles doesn't need a lookup by stack.
+    stack_ids: HashMap<Vec<u32>, usize>,
+    stack_cycles: Vec<u64>,
+    current_stack: usize,
+}
+
+impl Collector {
+    fn new(processor: Processor) -> Self {
+        Self {
+            profile: ProcessorProfile::new(processor),
+            previous: None,
+            stack: Vec::new(),
+            stack_ids: HashMap::from([(Vec::new(), 0)]),
+            stack_cycles: vec![0],
+            current_stack: 0,
+        }
+    }
+
+    fn on_instruction(&mut self, event: &InstructionEvent) {
+        *self.profile.instructions.entry(event.address).or_default() += 1;
+        let Some(previous) = self.previous.replace(*event) else {
+            *self.profile.blocks.entry(event.address).or_default() += 1;
+            return;
+        };
+        let cycles = event.cycles.saturating_sub(previous.cycles);
+        *self.profile.cycles.entry(previous.address).or_default() += cycles;
+        self.stack_cycles[self.current_stack] += cycles;
+
+        let flow = if previous.thumb {
+            Flow::thumb(previous.address, previous.opcode as u16)
+        } else {
+            Flow::arm(previous.address, previous.opcode)
+        };
+        if event.address == previous.address.wrapping_add(flow.length()) {
+            return;
+        }
+        *self.profile.blocks.entry(event.address).or_default() += 1;
+        if let Flow::Call { return_address, .. } = flow {
+            *self.profile.calls.entry(event.address).or_default() += 1;
+            if self.stack.len() < MAX_STACK_DEPTH {
+                self.stack.push((event.address, return_address));
+                self.update_stack_id();
+            }
+        } else if let Some(pos) = self
+            .stack
+            .iter()
+            .rposition(|&(_, return_address)| return_address == event.address)
+        {
+            self.stack.truncate(pos);
+            self.update_stack_id();
+        }
+    }
+
+    fn update_stack_id(&mut self) {
+        let stack: Vec<u32> = self.stack.iter().map(|&(function, _)| function).collect();
+        let next_id = self.stack_ids.len();
+        self.current_stack = *self.stack_ids.entry(stack).or_insert(next_id);
+        if self.current_stack == next_id {
+            self.stack_cycles.push(0);
+        }
+    }
+
+    fn profile(&self) -> ProcessorProfile {
+        let mut profile = self.profile.clone();
+        profile.stacks = self
+            .stack_ids
+            .iter()
+            .map(|(stack, &id)| (stack.clone(), self.stack_cycles[id]))
+            .filter(|&(_, cycles)| cycles > 0)
+            .collect();
+        profile
+    }
+}
+
+/// Counts how often code is executed and where the time is spent.
+///
+/// Profiling uses a callback for every executed instruction and slows down emulation
+/// considerably.
+///
+/// # Usage example
+/// ```rs
+/// use rs_desmume::ProfilerConfig;
+///
+/// emu.profiler_mut().start(ProfilerConfig::default());
+/// for _ in 0..60 {
+///     emu.cycle();
+/// }
+/// let profile = emu.profiler_mut().stop().unwrap();
+/// for function in profile.get(Processor::Arm9).unwrap().functions(emu.symbols()) {
+///     println!("{} {} {}", function.name, function.calls, function.cycles);
+/// }
+/// profile.write_collapsed(std::fs::File::create("scene.folded")?, emu.symbols())?;
+/// ```
+pub struct DeSmuMEProfiler {
+    running: Vec<(Arc<Mutex<Collector>>, ObserverHandle)>,
+}
+
+impl DeSmuMEProfiler {
+    pub(crate) fn new() -> Self {
+        Self {
+            running: Vec::new(),
+        }
+    }
+
+    /// Start profiling. A profile that is currently being collected is discarded.
+    pub fn start(&mut self, config: ProfilerConfig) {
+        self.running.clear();
+        let processors = [
+            (Processor::Arm9, config.arm9),
+            (Processor::Arm7, config.arm7),
+        ];
+        for (processor, enabled) in processors {
+            if !enabled {
+                continue;
+            }
+            let collector = Arc::new(Mutex::new(Collector::new(processor)));
+            let observer_collector = collector.clone();
+            let handle = add_observer(processor, move |event| {
+                observer_collector.lock().unwrap().on_instruction(event);
+            });
+            self.running.push((collector, handle));
+        }
+    }
+
+    /// Stop profiling and return the results. Returns `None` if the profiler isn't running.
+    pub fn stop(&mut self) -> Option<Profile> {
+        let profile = self.snapshot();
+        self.running.clear();
+        profile
+    }
+
+    /// Whether the profiler is running.
+    pub fn is_running(&self) -> bool {
+        !self.running.is_empty()
+    }
+
+    /// The results collected so far, without stopping. Returns `None` if the profiler isn't
+    /// running.
+    pub fn snapshot(&self) -> Option<Profile> {
+        if self.running.is_empty() {
+            return None;
+        }
+        let processors = self
+            .running
+            .iter()
+            .map(|(collector, _)| collector.lock().unwrap().profile())
+            .collect();
+        Some(Profile { processors })
+    }
+}
@@ -0,0 +1,283 @@
+use crate::savestate;
+use crate::DeSmuMEError;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set when a savestate was loaded, the history then belongs to a different timeline.
+static DISCARD_HISTORY: AtomicBool = AtomicBool::new(false);
+
+/// Drop the rewind history the next time it is used.
+pub(crate) fn discard_history() {
+    DISCARD_HISTORY.store(true, Ordering::Relaxed);
+}
+
+/// Configuration of the rewind buffer, see [`DeSmuMERewind::enable`].
+#[derive(Debug, Clone)]
+pub struct RewindConfig {
+    /// A snapshot is taken every `interval` frames. Must be at least 1.
+    pub interval: u32,
+    /// Maximum number of snapshots kept. The oldest snapshots are dropped first.
+    pub capacity: usize,
+    /// Maximum number of bytes all snapshots may use combined.
+    /// The newest snapshot is always kept, even if it alone exceeds this limit.
+    pub memory_limit: usize,
+}
+
+impl Default for RewindConfig {
+    fn default() -> Self {
+        Self {
+            interval: 10,
+            capacity: 600,
+            memory_limit: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// An older snapshot, stored as the difference to the next newer one.
+struct DeltaSnapshot {
+    frame: u64,
+    delta: Vec<u8>,
+}
+
+/// Rewind buffer that periodically captures in-memory savestates while the emulator cycles.
+///
+/// The newest snapshot is stored in full, all older ones are delta-compressed against their
+/// successor. Snapshots are taken automatically by [`crate::DeSmuME::cycle`] once enabled.
+///
+/// The history is cleared when a ROM is opened, the emulator is reset or a savestate is loaded.
+pub struct DeSmuMERewind {
+    config: Option<RewindConfig>,
+    frame: u64,
+    newest: Option<(u64, Vec<u8>)>,
+    older: VecDeque<DeltaSnapshot>,
+    memory_usage: usize,
+    _notsendsync: PhantomData<*mut u8>,
+}
+
+impl DeSmuMERewind {
+    pub(crate) fn new() -> Self {
+        Self {
+            config: None,
+            frame: 0,
+            newest: None,
+            older: VecDeque::new(),
+            memory_usage: 0,
+            _notsendsync: PhantomData,
+        }
+    }
+
+    /// Enable capturing snapshots with the given configuration. This clears the current history.
+    pub fn enable(&mut self, config: RewindConfig) {
+        assert!(config.interval > 0, "rewind interval must be at least 1");
+        self.config = Some(config);
+        self.clear();
+    }
+
+    /// Stop capturing snapshots and drop the history.
+    pub fn disable(&mut self) {
+        self.config = None;
+        self.clear();
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    /// Drop all captured snapshots and reset the frame counter.
+    pub fn clear(&mut self) {
+        DISCARD_HISTORY.store(false, Ordering::Relaxed);
+        self.frame = 0;
+        self.newest = None;
+        self.older.clear();
+        self.memory_usage = 0;
+    }
+
+    /// Number of frames cycled since rewinding was enabled, adjusted by rewinds.
+    pub fn frame(&self) -> u64 {
+        if self.is_discarded() {
+            return 0;
+        }
+        self.frame
+    }
+
+    /// Number of snapshots currently held.
+    pub fn len(&self) -> usize {
+        if self.is_discarded() {
+            return 0;
+        }
+        self.older.len() + self.newest.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.is_discarded() || self.newest.is_none()
+    }
+
+    /// Number of bytes used by all snapshots combined.
+    pub fn memory_usage(&self) -> usize {
+        if self.is_discarded() {
+            return 0;
+        }
+        self.memory_usage
+    }
+
+    /// Go back (at least) `frames` frames by loading the newest snapshot that was taken at or
+    /// before that point. If the history does not reach back far enough, the oldest snapshot
+    /// is loaded instead. All snapshots newer than the loaded one are dropped.
+    ///
+    /// Returns the number of frames that were actually rewound.
+    pub fn rewind(&mut self, frames: u64) -> Result<u64, DeSmuMEError> {
+        self.drop_discarded();
+        let Some((newest_frame, newest)) = &self.newest else {
+            return Err(DeSmuMEError::NoRewindState);
+        };
+        let target = self.frame.saturating_sub(frames);
+        let mut frame = *newest_frame;
+        let mut state = None;
+        let mut kept = self.older.len();
+        // Reconstruct the target state first, the history is only changed once it was loaded.
+        while frame > target && kept > 0 {
+            kept -= 1;
+            let snapshot = &self.older[kept];
+            let newer = state.as_deref().unwrap_or(newest.as_slice());
+            state = Some(apply_delta(newer, &snapshot.delta));
+            frame = snapshot.frame;
+        }
+        let state = state.unwrap_or_else(|| newest.clone());
+        savestate::load_from_slice(&state)?;
+        self.older.truncate(kept);
+        self.memory_usage = state.len() + self.older.iter().map(|s| s.delta.len()).sum::<usize>();
+        self.newest = Some((frame, state));
+        let rewound = self.frame - frame;
+        self.frame = frame;
+        Ok(rewound)
+    }
+
+    /// Called by `DeSmuME::cycle` after each frame.
+    pub(crate) fn on_frame(&mut self) {
+        self.drop_discarded();
+        let Some(config) = &self.config else {
+            return;
+        };
+        self.frame += 1;
+        if !self.frame.is_multiple_of(config.interval as u64) {
+            return;
+        }
+        // Frames where the state could not be saved are skipped.
+        if let Ok(state) = savestate::save_to_vec() {
+            self.push(state);
+        }
+    }
+
+    fn is_discarded(&self) -> bool {
+        DISCARD_HISTORY.load(Ordering::Relaxed)
+    }
+
+    fn drop_discarded(&mut self) {
+        if self.is_discarded() {
+            self.clear();
+        }
+    }
+
+    fn push(&mut self, state: Vec<u8>) {
+        let config = self.config.as_ref().unwrap();
+        if let Some((frame, previous)) = self.newest.take() {
+            let delta = encode_delta(&previous, &state);
+            self.memory_usage -= previous.len();
+            self.memory_usage += delta.len();
+            self.older.push_back(DeltaSnapshot { frame, delta });
+        }
+        self.memory_usage += state.len();
+        self.newest = Some((self.frame, state));
+
+        while !self.older.is_empty()
+            && (self.older.len() + 1 > config.capacity || self.memory_usage > config.memory_limit)
+        {
+            let dropped = self.older.pop_front().unwrap();
+            self.memory_usage -= dropped.delta.len();
+        }
+    }
+}
+
+// Deltas are encoded as the length of the old state, followed by a list of
+// (zero run length, literal length, literal bytes) entries over `old XOR new`.
+// Runs of fewer than this many zeros are kept in the literal.
+const MIN_ZERO_RUN: usize = 8;
+
+/// Encode `old` relative to `new`, so that `apply_delta(new, delta) == old`.
+fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let xor_at = |i: usize| old[i] ^ new.get(i).copied().unwrap_or(0);
+    let mut out = Vec::new();
+    write_varint(&mut out, old.len());
+    let mut i = 0;
+    while i < old.len() {
+        let zero_start = i;
+        while i < old.len() && xor_at(i) == 0 {
+            i += 1;
+        }
+        if i == old.len() {
+            break;
+        }
+        let literal_start = i;
+        let mut zeros = 0;
+        while i < old.len() && zeros < MIN_ZERO_RUN {
+            if xor_at(i) == 0 {
+                zeros += 1;
+            } else {
+                zeros = 0;
+            }
+            i += 1;
+        }
+        if zeros == MIN_ZERO_RUN {
+            i -= zeros;
+        }
+        write_varint(&mut out, literal_start - zero_start);
+        write_varint(&mut out, i - literal_start);
+        out.extend((literal_start..i).map(xor_at));
+    }
+    out
+}
+
+fn apply_delta(new: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut cursor = 0;
+    let len = read_varint(delta, &mut cursor);
+    let mut old = vec![0; len];
+    let common = len.min(new.len());
+    old[..common].copy_from_slice(&new[..common]);
+    let mut pos = 0;
+    while cursor < delta.len() {
+        pos += read_varint(delta, &mut cursor);
+        let literal = read_varint(delta, &mut cursor);
+        for (dst, x) in old[pos..pos + literal]
+            .iter_mut()
+            .zip(&delta[cursor..cursor + literal])
+        {
+            *dst ^= x;
+        }
+        pos += literal;
+        cursor += literal;
+    }
+    old
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], cursor: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*cursor];
+        *cursor += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
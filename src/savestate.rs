@@ -1,3 +1,5 @@
+use crate::mem::clear_shadow_stacks;
+use crate::rewind::discard_history;
 use crate::DeSmuMEError;
 use desmume_sys::*;
 use std::ffi::{CStr, CString};
@@ -27,7 +29,9 @@ impl DeSmuMESavestate {
     /// # Safety
     /// The caller needs to make sure the savestate exists.
     pub unsafe fn load(&mut self, slot_id: u8) {
-        desmume_savestate_slot_load(slot_id as c_int)
+        desmume_savestate_slot_load(slot_id as c_int);
+        clear_shadow_stacks();
+        discard_history();
     }
 
     /// Save the current game state to the savestate in the specified slot.
@@ -41,6 +45,8 @@ impl DeSmuMESavestate {
             if desmume_savestate_load(CString::new(file_name)?.as_ptr()) <= 0 {
                 Err(DeSmuMEError::LoadSavestateFailed)
             } else {
+                clear_shadow_stacks();
+                discard_history();
                 Ok(())
             }
         }
@@ -57,6 +63,21 @@ impl DeSmuMESavestate {
         }
     }
 
+    /// Save the current game state into a memory buffer.
+    ///
+    /// This does not touch the file system and is much faster than `save_file` if many states
+    /// need to be taken.
+    pub fn save_to_vec(&mut self) -> Result<Vec<u8>, DeSmuMEError> {
+        save_to_vec()
+    }
+
+    /// Load a savestate from a memory buffer previously created with `save_to_vec`.
+    pub fn load_from_slice(&mut self, data: &[u8]) -> Result<(), DeSmuMEError> {
+        load_from_slice(data)?;
+        discard_history();
+        Ok(())
+    }
+
     /// Return the date a savestate was saved at as a string.
     /// May panic if the date can not be represented as a Rust string.
     pub fn date(&self, slot_id: u8) -> String {
@@ -68,3 +89,27 @@ impl DeSmuMESavestate {
         }
     }
 }
+
+pub(crate) fn save_to_vec() -> Result<Vec<u8>, DeSmuMEError> {
+    unsafe {
+        let mut size: usize = 0;
+        let buffer = desmume_savestate_save_to_memory(&mut size);
+        if buffer.is_null() {
+            return Err(DeSmuMEError::SaveSavestateFailed);
+        }
+        let data = std::slice::from_raw_parts(buffer, size).to_vec();
+        desmume_savestate_free_memory(buffer);
+        Ok(data)
+    }
+}
+
+pub(crate) fn load_from_slice(data: &[u8]) -> Result<(), DeSmuMEError> {
+    unsafe {
+        if desmume_savestate_load_from_memory(data.as_ptr(), data.len()) <= 0 {
+            Err(DeSmuMEError::LoadSavestateFailed)
+        } else {
+            clear_shadow_stacks();
+            Ok(())
+        }
+    }
+}
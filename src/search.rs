@@ -0,0 +1,329 @@
+//! RAM search, for finding the addresses of game variables.
+//!
+//! A search starts with a snapshot of one or more memory regions, where every address is a
+//! candidate. Each [`RamSearch::filter`] compares the current values against the previous
+//! snapshot or a constant, drops the candidates that don't match and takes a new snapshot:
+//!
+//! ```rs
+//! use desmume_rs::search::{CompareTo, Comparison, RamSearch, SearchConfig};
+//!
+//! let mut search = RamSearch::new(emu.memory(), SearchConfig::default());
+//! // Lose a life...
+//! search.filter(emu.memory(), Comparison::DecreasedBy(1), CompareTo::Previous);
+//! // Don't lose a life...
+//! search.filter(emu.memory(), Comparison::Equal, CompareTo::Previous);
+//! for result in search.results() {
+//!     println!("{:#010x}: {} -> {}", result.address, result.previous, result.current);
+//! }
+//! ```
+
+use crate::mem::{read_block, MemoryRegion, Processor};
+use crate::DeSmuMEMemory;
+
+/// The size of the searched values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueSize {
+    Byte,
+    Halfword,
+    Word,
+}
+
+impl ValueSize {
+    /// The size in bytes.
+    pub fn bytes(self) -> usize {
+        match self {
+            Self::Byte => 1,
+            Self::Halfword => 2,
+            Self::Word => 4,
+        }
+    }
+}
+
+/// What is searched. See [`RamSearch::new`].
+#[derive(Debug, Clone)]
+pub struct SearchConfig {
+    /// The regions to search. Each is read as seen by the processor it belongs to, or the ARM9
+    /// for regions visible to both.
+    pub regions: Vec<MemoryRegion>,
+    pub size: ValueSize,
+    /// Whether values are signed.
+    pub signed: bool,
+    /// Only consider addresses that are a multiple of the value size.
+    pub aligned: bool,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            regions: vec![MemoryRegion::MainRam],
+            size: ValueSize::Word,
+            signed: false,
+            aligned: true,
+        }
+    }
+}
+
+/// How the current value of a candidate is compared. See [`RamSearch::filter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    /// Against [`CompareTo::Previous`] this finds the values that changed.
+    NotEqual,
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual,
+    /// The value is exactly this much larger.
+    IncreasedBy(i64),
+    /// The value is exactly this much smaller.
+    DecreasedBy(i64),
+    /// The value is exactly this much larger or smaller.
+    DifferentBy(i64),
+}
+
+impl Comparison {
+    fn matches(self, value: i64, reference: i64) -> bool {
+        match self {
+            Self::Equal => value == reference,
+            Self::NotEqual => value != reference,
+            Self::Less => value < reference,
+            Self::Greater => value > reference,
+            Self::LessOrEqual => value <= reference,
+            Self::GreaterOrEqual => value >= reference,
+            Self::IncreasedBy(n) => value - reference == n,
+            Self::DecreasedBy(n) => reference - value == n,
+            Self::DifferentBy(n) => (value - reference).abs() == n,
+        }
+    }
+}
+
+/// What the current value of a candidate is compared against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareTo {
+    /// The value in the previous snapshot.
+    Previous,
+    /// A constant.
+    Value(i64),
+}
+
+/// A remaining candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchResult {
+    pub address: u32,
+    /// The value in the snapshot before the last one, which the last filter compared against.
+    pub previous: i64,
+    /// The value in the last snapshot.
+    pub current: i64,
+}
+
+struct RegionSnapshot {
+    region: MemoryRegion,
+    /// The snapshot before `current`.
+    previous: Vec<u8>,
+    current: Vec<u8>,
+    /// One bit per candidate, candidate `i` is at offset `i * step`.
+    candidates: Vec<u64>,
+}
+
+impl RegionSnapshot {
+    fn processor(&self) -> Processor {
+        self.region.processor().unwrap_or(Processor::Arm9)
+    }
+
+    fn read(&self) -> Vec<u8> {
+        let mut data = vec![0; self.region.size() as usize];
+        read_block(self.processor(), self.region.base(), &mut data);
+        data
+    }
+
+    /// Take a new snapshot, keeping the last one as the previous one.
+    fn update(&mut self) {
+        let data = self.read();
+        self.previous = std::mem::replace(&mut self.current, data);
+    }
+}
+
+/// A RAM search. See the [module docs](self).
+///
+/// The methods that take a snapshot borrow the [`DeSmuMEMemory`] they read from.
+pub struct RamSearch {
+    config: SearchConfig,
+    snapshots: Vec<RegionSnapshot>,
+    remaining: usize,
+}
+
+impl RamSearch {
+    /// Start a search by taking a snapshot of the regions. All addresses are candidates.
+    pub fn new(memory: &DeSmuMEMemory, config: SearchConfig) -> Self {
+        let mut search = Self {
+            config,
+            snapshots: Vec::new(),
+            remaining: 0,
+        };
+        search.reset(memory);
+        search
+    }
+
+    /// Start over, making all addresses candidates again and taking a new snapshot.
+    pub fn reset(&mut self, _memory: &DeSmuMEMemory) {
+        let step = self.step();
+        let value_size = self.config.size.bytes();
+        self.remaining = 0;
+        self.snapshots = self
+            .config
+            .regions
+            .iter()
+            .map(|&region| {
+                let size = region.size() as usize;
+                let count = if size < value_size {
+                    0
+                } else {
+                    (size - value_size) / step + 1
+                };
+                let mut candidates = vec![u64::MAX; count.div_ceil(64)];
+                if count % 64 != 0 {
+                    *candidates.last_mut().unwrap() = (1 << (count % 64)) - 1;
+                }
+                self.remaining += count;
+                let mut snapshot = RegionSnapshot {
+                    region,
+                    previous: Vec::new(),
+                    current: Vec::new(),
+                    candidates,
+                };
+                snapshot.current = snapshot.read();
+                snapshot.previous = snapshot.current.clone();
+                snapshot
+            })
+            .collect();
+    }
+
+    pub fn config(&self) -> &SearchConfig {
+        &self.config
+    }
+
+    /// Number of remaining candidates.
+    pub fn len(&self) -> usize {
+        self.remaining
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining == 0
+    }
+
+    /// Keep only the candidates whose current value matches, then take a new snapshot. Returns
+    /// the number of remaining candidates.
+    pub fn filter(
+        &mut self,
+        _memory: &DeSmuMEMemory,
+        comparison: Comparison,
+        compare_to: CompareTo,
+    ) -> usize {
+        let step = self.step();
+        let (size, signed) = (self.config.size, self.config.signed);
+        self.remaining = 0;
+        for snapshot in &mut self.snapshots {
+            snapshot.update();
+            for (word_index, word) in snapshot.candidates.iter_mut().enumerate() {
+                let mut bits = *word;
+                while bits != 0 {
+                    let bit = bits.trailing_zeros();
+                    bits &= bits - 1;
+                    let offset = (word_index * 64 + bit as usize) * step;
+                    let reference = match compare_to {
+                        CompareTo::Previous => value_at(&snapshot.previous, offset, size, signed),
+                        CompareTo::Value(value) => value,
+                    };
+                    let current = value_at(&snapshot.current, offset, size, signed);
+                    if !comparison.matches(current, reference) {
+                        *word &= !(1 << bit);
+                    }
+                }
+                self.remaining += word.count_ones() as usize;
+            }
+        }
+        self.remaining
+    }
+
+    /// Take a new snapshot without dropping any candidates, eg. to ignore changes since the last
+    /// filter.
+    pub fn update(&mut self, _memory: &DeSmuMEMemory) {
+        for snapshot in &mut self.snapshots {
+            snapshot.update();
+        }
+    }
+
+    /// Drop a candidate.
+    pub fn remove(&mut self, address: u32) {
+        let step = self.step();
+        for snapshot in &mut self.snapshots {
+            let Some(offset) = address.checked_sub(snapshot.region.base()) else {
+                continue;
+            };
+            let offset = offset as usize;
+            if !offset.is_multiple_of(step) || offset / step >= snapshot.candidates.len() * 64 {
+                continue;
+            }
+            let index = offset / step;
+            let word = &mut snapshot.candidates[index / 64];
+            if *word & (1 << (index % 64)) != 0 {
+                *word &= !(1 << (index % 64));
+                self.remaining -= 1;
+            }
+        }
+    }
+
+    /// The remaining candidates, by address, with their values in the last two snapshots.
+    pub fn results(&self) -> impl Iterator<Item = SearchResult> + '_ {
+        let step = self.step();
+        let (size, signed) = (self.config.size, self.config.signed);
+        self.snapshots.iter().flat_map(move |snapshot| {
+            snapshot
+                .candidates
+                .iter()
+                .enumerate()
+                .flat_map(move |(word_index, &word)| {
+                    (0..64)
+                        .filter(move |bit| word & (1 << bit) != 0)
+                        .map(move |bit| (word_index * 64 + bit) * step)
+                })
+                .map(move |offset| SearchResult {
+                    address: snapshot.region.base() + offset as u32,
+                    previous: value_at(&snapshot.previous, offset, size, signed),
+                    current: value_at(&snapshot.current, offset, size, signed),
+                })
+        })
+    }
+
+    /// The distance between candidates in bytes.
+    fn step(&self) -> usize {
+        if self.config.aligned {
+            self.config.size.bytes()
+        } else {
+            1
+        }
+    }
+}
+
+fn value_at(data: &[u8], offset: usize, size: ValueSize, signed: bool) -> i64 {
+    match (size, signed) {
+        (ValueSize::Byte, false) => data[offset] as i64,
+        (ValueSize::Byte, true) => data[offset] as i8 as i64,
+        (ValueSize::Halfword, _) => {
+            let value = u16::from_le_bytes([data[offset], data[offset + 1]]);
+            if signed {
+                value as i16 as i64
+            } else {
+                value as i64
+            }
+        }
+        (ValueSize::Word, _) => {
+            let value = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+            if signed {
+                value as i32 as i64
+            } else {
+                value as i64
+            }
+        }
+    }
+}
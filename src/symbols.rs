@@ -0,0 +1,472 @@
+//! Symbol tables for turning addresses into names and back.
+//!
+//! Symbols can be loaded from linker map files, linker scripts with symbol assignments,
+//! no$gba style `.sym` files and simple CSV or YAML lists:
+//!
+//! ```rs
+//! use desmume_rs::symbols::Symbols;
+//!
+//! let symbols = Symbols::load("game.sym")?;
+//! assert_eq!(symbols.lookup("GameMain"), Some(0x02000800));
+//! assert_eq!(symbols.resolve(0x02000810), Some(("GameMain", 0x10)));
+//! ```
+//!
+//! The CSV format has the columns `name,address,length,overlay`, where the last two are optional
+//! and a header line is allowed. The YAML format is a list of mappings with the same keys:
+//!
+//! ```yaml
+//! - name: GameMain
+//!   address: 0x02000800
+//!   length: 0x40
+//! - name: BattleInit
+//!   address: 0x022BC000
+//!   overlay: 11
+//! ```
+
+use crate::DeSmuMEError;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// A named address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    /// The address of the symbol. Bit 0 may be set for Thumb functions.
+    pub address: u32,
+    /// Size of the symbol in bytes, if known.
+    pub length: Option<u32>,
+    /// The overlay the symbol is in, `None` for symbols that are always loaded.
+    pub overlay: Option<u32>,
+}
+
+impl Symbol {
+    pub fn new(name: impl Into<String>, address: u32) -> Self {
+        Self {
+            name: name.into(),
+            address,
+            length: None,
+            overlay: None,
+        }
+    }
+
+    pub fn with_length(mut self, length: u32) -> Self {
+        self.length = Some(length);
+        self
+    }
+
+    pub fn with_overlay(mut self, overlay: u32) -> Self {
+        self.overlay = Some(overlay);
+        self
+    }
+
+    /// The first address covered by the symbol, with the Thumb bit cleared.
+    fn start(&self) -> u32 {
+        self.address & !1
+    }
+
+    fn contains(&self, address: u32) -> bool {
+        match self.length {
+            Some(length) if length > 0 => address.wrapping_sub(self.start()) < length,
+            _ => true,
+        }
+    }
+}
+
+/// The file formats [`Symbols`] can be loaded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolFormat {
+    /// A GNU ld map file (`.map`).
+    LinkerMap,
+    /// A linker script with symbol assignments, eg. `GameMain = 0x02000800;` (`.ld`).
+    LinkerScript,
+    /// A no$gba symbol file with lines like `02000800 GameMain` (`.sym`).
+    Sym,
+    /// `name,address,length,overlay` (`.csv`).
+    Csv,
+    /// A list of mappings with the keys `name`, `address`, `length` and `overlay` (`.yaml`).
+    Yaml,
+}
+
+impl SymbolFormat {
+    /// Guesses the format from the file extension.
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let extension = Path::new(file_name).extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "map" => Some(Self::LinkerMap),
+            "ld" | "x" => Some(Self::LinkerScript),
+            "sym" => Some(Self::Sym),
+            "csv" => Some(Self::Csv),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// A symbol table.
+///
+/// Symbols in overlays share their addresses with other overlays. By default symbols of all
+/// overlays are considered when resolving addresses; use [`Symbols::set_loaded_overlays`] to
+/// restrict this to the overlays that are actually loaded.
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    /// Sorted by start address.
+    symbols: Vec<Symbol>,
+    /// Index of the first symbol with each name.
+    names: HashMap<String, usize>,
+    loaded_overlays: Option<HashSet<u32>>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a symbol file. The format is chosen based on the file extension.
+    pub fn load(file_name: &str) -> Result<Self, DeSmuMEError> {
+        let format = SymbolFormat::from_file_name(file_name).ok_or_else(|| {
+            DeSmuMEError::InvalidSymbols {
+                line: 0,
+                reason: format!("unknown symbol file format: {file_name}"),
+            }
+        })?;
+        Self::load_as(file_name, format)
+    }
+
+    /// Loads a symbol file in the given format.
+    pub fn load_as(file_name: &str, format: SymbolFormat) -> Result<Self, DeSmuMEError> {
+        Self::parse(&std::fs::read_to_string(file_name)?, format)
+    }
+
+    /// Parses symbol definitions in the given format.
+    pub fn parse(text: &str, format: SymbolFormat) -> Result<Self, DeSmuMEError> {
+        let mut symbols = Self::new();
+        symbols.extend(match format {
+            SymbolFormat::LinkerMap => parse_linker_map(text),
+            SymbolFormat::LinkerScript => parse_linker_script(text),
+            SymbolFormat::Sym => parse_sym(text)?,
+            SymbolFormat::Csv => parse_csv(text)?,
+            SymbolFormat::Yaml => parse_yaml(text)?,
+        });
+        Ok(symbols)
+    }
+
+    pub fn insert(&mut self, symbol: Symbol) {
+        self.extend([symbol])
+    }
+
+    /// The number of symbols.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// All symbols, ordered by address.
+    pub fn iter(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter()
+    }
+
+    /// The symbol with the given name. If several symbols have the name, the first one added is
+    /// returned.
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.names.get(name).map(|&i| &self.symbols[i])
+    }
+
+    /// The address of the symbol with the given name.
+    pub fn lookup(&self, name: &str) -> Option<u32> {
+        self.get(name).map(|s| s.address)
+    }
+
+    /// The symbol containing `address`.
+    ///
+    /// This is the closest symbol at or below the address in a loaded overlay. If that symbol has
+    /// a known length that doesn't reach the address, there is no match.
+    pub fn symbol_at(&self, address: u32) -> Option<&Symbol> {
+        let end = self.symbols.partition_point(|s| s.start() <= address);
+        self.symbols[..end]
+            .iter()
+            .rev()
+            .find(|s| self.is_loaded(s))
+            .filter(|s| s.contains(address))
+    }
+
+    /// The name of the symbol containing `address` and the offset of the address into it.
+    pub fn resolve(&self, address: u32) -> Option<(&str, u32)> {
+        self.symbol_at(address)
+            .map(|s| (s.name.as_str(), address - s.start()))
+    }
+
+    /// Formats an address as `name+0x10`, or as a plain hex number if no symbol contains it.
+    pub fn symbolize(&self, address: u32) -> String {
+        match self.resolve(address) {
+            Some((name, 0)) => name.to_string(),
+            Some((name, offset)) => format!("{name}+0x{offset:x}"),
+            None => format!("0x{address:08x}"),
+        }
+    }
+
+    /// Only consider symbols in the given overlays (and those not in any overlay) when resolving
+    /// addresses.
+    pub fn set_loaded_overlays(&mut self, overlays: impl IntoIterator<Item = u32>) {
+        self.loaded_overlays = Some(overlays.into_iter().collect());
+    }
+
+    /// Consider symbols of all overlays when resolving addresses. This is the default.
+    pub fn clear_loaded_overlays(&mut self) {
+        self.loaded_overlays = None;
+    }
+
+    fn is_loaded(&self, symbol: &Symbol) -> bool {
+        match (symbol.overlay, &self.loaded_overlays) {
+            (Some(overlay), Some(loaded)) => loaded.contains(&overlay),
+            _ => true,
+        }
+    }
+}
+
+impl Extend<Symbol> for Symbols {
+    fn extend<T: IntoIterator<Item = Symbol>>(&mut self, iter: T) {
+        self.symbols.extend(iter);
+        // The sort is stable, so the first symbol with a name stays first.
+        self.symbols.sort_by_key(Symbol::start);
+        self.names.clear();
+        for (i, symbol) in self.symbols.iter().enumerate() {
+            self.names.entry(symbol.name.clone()).or_insert(i);
+        }
+    }
+}
+
+impl FromIterator<Symbol> for Symbols {
+    fn from_iter<T: IntoIterator<Item = Symbol>>(iter: T) -> Self {
+        let mut symbols = Self::new();
+        symbols.extend(iter);
+        symbols
+    }
+}
+
+/// Types that can be printed with addresses replaced by symbol names.
+pub trait Symbolize {
+    fn symbolize(&self, symbols: &Symbols) -> String;
+}
+
+impl Symbolize for u32 {
+    fn symbolize(&self, symbols: &Symbols) -> String {
+        symbols.symbolize(*self)
+    }
+}
+
+fn invalid(line: usize, reason: impl Into<String>) -> DeSmuMEError {
+    DeSmuMEError::InvalidSymbols {
+        line: line + 1,
+        reason: reason.into(),
+    }
+}
+
+/// Parses `0x` prefixed hex or decimal numbers.
+fn parse_number(value: &str) -> Option<u32> {
+    let value = value.trim();
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '$' | '.'))
+}
+
+/// Symbol lines in a GNU ld map file look like `0x02000800    GameMain`. Everything else
+/// (sections, input files, fill, assignments to `.`) is skipped.
+fn parse_linker_map(text: &str) -> Vec<Symbol> {
+    text.lines()
+        .filter_map(|line| {
+            let mut tokens = line.split_whitespace();
+            let address = tokens.next()?;
+            let name = tokens.next()?;
+            if !address.starts_with("0x") || !is_identifier(name) {
+                return None;
+            }
+            if tokens.next().is_some_and(|t| t != "=") {
+                return None;
+            }
+            Some(Symbol::new(name, parse_number(address)?))
+        })
+        .collect()
+}
+
+/// Picks up `name = 0x02000800;` and `PROVIDE(name = 0x02000800);`. Assignments of expressions
+/// are skipped.
+fn parse_linker_script(text: &str) -> Vec<Symbol> {
+    let text = strip_block_comments(text);
+    text.split(';')
+        .filter_map(|statement| {
+            let statement = statement
+                .lines()
+                .map(|l| l.split("//").next().unwrap_or_default())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut statement = statement.trim();
+            if let Some(inner) = statement
+                .strip_prefix("PROVIDE(")
+                .or_else(|| statement.strip_prefix("PROVIDE_HIDDEN("))
+            {
+                statement = inner.strip_suffix(')')?;
+            }
+            let (name, value) = statement.split_once('=')?;
+            let name = name.split_whitespace().last()?;
+            if !is_identifier(name) {
+                return None;
+            }
+            Some(Symbol::new(name, parse_number(value)?))
+        })
+        .collect()
+}
+
+fn strip_block_comments(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("/*") {
+        result.push_str(&rest[..start]);
+        rest = rest[start..]
+            .find("*/")
+            .map_or("", |end| &rest[start + end + 2..]);
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Lines are `02000800 GameMain`, comments start with `;`. no$gba directives like `.arm` or
+/// `.byt:0004` are skipped.
+fn parse_sym(text: &str) -> Result<Vec<Symbol>, DeSmuMEError> {
+    let mut symbols = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split(';').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (address, name) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| invalid(n, "expected an address and a name"))?;
+        let address = u32::from_str_radix(address, 16)
+            .map_err(|_| invalid(n, format!("invalid address: {address}")))?;
+        let name = name.trim();
+        if !name.starts_with('.') {
+            symbols.push(Symbol::new(name, address));
+        }
+    }
+    Ok(symbols)
+}
+
+fn parse_csv(text: &str) -> Result<Vec<Symbol>, DeSmuMEError> {
+    let mut symbols = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<_> = line.split(',').map(str::trim).collect();
+        if fields.len() < 2 || fields.len() > 4 {
+            return Err(invalid(n, "expected 2 to 4 fields"));
+        }
+        let Some(address) = parse_number(fields[1]) else {
+            if symbols.is_empty() && fields[1].eq_ignore_ascii_case("address") {
+                continue;
+            }
+            return Err(invalid(n, format!("invalid address: {}", fields[1])));
+        };
+        let mut symbol = Symbol::new(fields[0], address);
+        symbol.length = optional_number(n, fields.get(2).copied())?;
+        symbol.overlay = optional_number(n, fields.get(3).copied())?;
+        symbols.push(symbol);
+    }
+    Ok(symbols)
+}
+
+fn optional_number(line: usize, value: Option<&str>) -> Result<Option<u32>, DeSmuMEError> {
+    match value {
+        None | Some("") | Some("~") | Some("null") => Ok(None),
+        Some(value) => parse_number(value)
+            .map(Some)
+            .ok_or_else(|| invalid(line, format!("invalid number: {value}"))),
+    }
+}
+
+/// Supports the subset of YAML needed for a list of flat mappings.
+fn parse_yaml(text: &str) -> Result<Vec<Symbol>, DeSmuMEError> {
+    #[derive(Default)]
+    struct Entry {
+        line: usize,
+        name: Option<String>,
+        address: Option<u32>,
+        length: Option<u32>,
+        overlay: Option<u32>,
+    }
+
+    fn finish(entry: Entry) -> Result<Symbol, DeSmuMEError> {
+        Ok(Symbol {
+            name: entry
+                .name
+                .ok_or_else(|| invalid(entry.line, "missing name"))?,
+            address: entry
+                .address
+                .ok_or_else(|| invalid(entry.line, "missing address"))?,
+            length: entry.length,
+            overlay: entry.overlay,
+        })
+    }
+
+    let mut symbols = Vec::new();
+    let mut entry: Option<Entry> = None;
+    for (n, line) in text.lines().enumerate() {
+        let mut line = line.split(" #").next().unwrap_or_default().trim();
+        if line.is_empty() || line.starts_with('#') || line == "---" {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('-') {
+            if let Some(entry) = entry.take() {
+                symbols.push(finish(entry)?);
+            }
+            entry = Some(Entry {
+                line: n,
+                ..Entry::default()
+            });
+            line = rest.trim();
+            if line.is_empty() {
+                continue;
+            }
+        }
+        let current = entry
+            .as_mut()
+            .ok_or_else(|| invalid(n, "expected a list entry"))?;
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid(n, "expected `key: value`"))?;
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        match key.trim() {
+            "name" => current.name = Some(value.to_string()),
+            "address" => {
+                current.address = Some(
+                    parse_number(value)
+                        .ok_or_else(|| invalid(n, format!("invalid address: {value}")))?,
+                )
+            }
+            "length" => current.length = optional_number(n, Some(value))?,
+            "overlay" => current.overlay = optional_number(n, Some(value))?,
+            // Allow additional information, eg. descriptions.
+            _ => {}
+        }
+    }
+    if let Some(entry) = entry {
+        symbols.push(finish(entry)?);
+    }
+    Ok(symbols)
+}
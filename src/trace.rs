@@ -0,0 +1,456 @@
+use crate::disasm::{decode_arm, decode_thumb, Architecture};
+use crate::mem::{add_observer, InstructionEvent, ObserverHandle, Processor, Register};
+use crate::{DeSmuMEError, DeSmuMEMemory};
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+const MAGIC: &[u8; 4] = b"DSTR";
+const VERSION: u8 = 1;
+
+const FLAG_THUMB: u8 = 1 << 0;
+const FLAG_ARM7: u8 = 1 << 1;
+const FLAG_DELTAS: u8 = 1 << 2;
+
+/// Index of the CPSR in the register deltas. 0-14 are R0-R14.
+const CPSR_INDEX: u8 = 16;
+
+/// Configuration of an execution trace. See [`DeSmuMETrace::start`].
+#[derive(Debug, Clone)]
+pub struct TraceConfig {
+    /// The processor whose instructions are recorded.
+    pub processor: Processor,
+    /// Only record instructions that changed the program flow (taken branches, calls, returns
+    /// and exceptions), instead of every instruction.
+    pub branches_only: bool,
+    /// Record the registers changed by each instruction. This slows down emulation a lot more.
+    pub register_deltas: bool,
+    /// Stop recording once the trace takes up this many bytes.
+    pub max_size: usize,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            processor: Processor::Arm9,
+            branches_only: false,
+            register_deltas: false,
+            max_size: 256 * 1024 * 1024,
+        }
+    }
+}
+
+/// A register that was changed by an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterDelta {
+    pub register: Register,
+    /// The new value.
+    pub value: u32,
+}
+
+/// An executed instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub processor: Processor,
+    pub address: u32,
+    /// The instruction. Only the lower 16 bits are used in Thumb mode.
+    pub opcode: u32,
+    pub thumb: bool,
+    /// Emulated time since the last reset, in ARM9 cycles.
+    pub cycles: u64,
+    /// The registers changed by the instruction (not counting R15). Only recorded if
+    /// [`TraceConfig::register_deltas`] was set.
+    pub register_deltas: Vec<RegisterDelta>,
+}
+
+impl TraceEntry {
+    /// Length of the instruction in bytes.
+    fn length(&self) -> u32 {
+        if self.thumb {
+            2
+        } else {
+            4
+        }
+    }
+}
+
+/// A recorded execution trace in a compact binary format.
+///
+/// Use [`Trace::iter`] to read the entries, [`Trace::write_text`] to export a human-readable
+/// listing and [`Trace::as_bytes`] / [`Trace::from_bytes`] to store and load traces.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    data: Vec<u8>,
+    len: usize,
+    last_cycles: u64,
+    truncated: bool,
+}
+
+impl Trace {
+    fn new() -> Self {
+        let mut data = MAGIC.to_vec();
+        data.push(VERSION);
+        Self {
+            data,
+            len: 0,
+            last_cycles: 0,
+            truncated: false,
+        }
+    }
+
+    /// Load a trace previously stored with [`Trace::as_bytes`].
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, DeSmuMEError> {
+        if data.len() < 5 || &data[..4] != MAGIC || data[4] != VERSION {
+            return Err(DeSmuMEError::InvalidTrace);
+        }
+        let mut trace = Self {
+            data,
+            len: 0,
+            last_cycles: 0,
+            truncated: false,
+        };
+        let mut reader = EntryReader::new(&trace.data);
+        while !reader.is_at_end() {
+            let entry = reader.read().ok_or(DeSmuMEError::InvalidTrace)?;
+            trace.last_cycles = entry.cycles;
+            trace.len += 1;
+        }
+        Ok(trace)
+    }
+
+    /// The trace in its binary format.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The number of recorded instructions.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether recording stopped early because [`TraceConfig::max_size`] was reached.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn iter(&self) -> TraceIter<'_> {
+        TraceIter(EntryReader::new(&self.data))
+    }
+
+    /// Write the trace as text, one disassembled instruction per line.
+    pub fn write_text(&self, mut out: impl Write) -> io::Result<()> {
+        for entry in self.iter() {
+            let arch = Architecture::from(entry.processor);
+            let (opcode, instruction) = if entry.thumb {
+                let opcode = entry.opcode as u16;
+                let instruction = decode_thumb(entry.address, opcode, None, arch);
+                (format!("    {opcode:04x}"), instruction)
+            } else {
+                let instruction = decode_arm(entry.address, entry.opcode, arch);
+                (format!("{:08x}", entry.opcode), instruction)
+            };
+            write!(
+                out,
+                "{:>12} {:08x}: {opcode}  {:<32}",
+                entry.cycles,
+                entry.address,
+                instruction.to_string()
+            )?;
+            for delta in &entry.register_deltas {
+                write!(out, " {:?}={:08x}", delta.register, delta.value)?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, entry: &TraceEntry, max_size: usize) {
+        if self.truncated {
+            return;
+        }
+        let start = self.data.len();
+        let mut flags = 0;
+        if entry.thumb {
+            flags |= FLAG_THUMB;
+        }
+        if entry.processor == Processor::Arm7 {
+            flags |= FLAG_ARM7;
+        }
+        if !entry.register_deltas.is_empty() {
+            flags |= FLAG_DELTAS;
+        }
+        self.data.push(flags);
+        self.data.extend_from_slice(&entry.address.to_le_bytes());
+        if entry.thumb {
+            self.data
+                .extend_from_slice(&(entry.opcode as u16).to_le_bytes());
+        } else {
+            self.data.extend_from_slice(&entry.opcode.to_le_bytes());
+        }
+        write_varint(&mut self.data, entry.cycles.wrapping_sub(self.last_cycles));
+        if !entry.register_deltas.is_empty() {
+            self.data.push(entry.register_deltas.len() as u8);
+            for delta in &entry.register_deltas {
+                self.data.push(register_index(delta.register));
+                self.data.extend_from_slice(&delta.value.to_le_bytes());
+            }
+        }
+        if self.data.len() > max_size {
+            self.data.truncate(start);
+            self.truncated = true;
+            return;
+        }
+        self.last_cycles = entry.cycles;
+        self.len += 1;
+    }
+}
+
+impl<'a> IntoIterator for &'a Trace {
+    type Item = TraceEntry;
+    type IntoIter = TraceIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the entries of a [`Trace`].
+pub struct TraceIter<'a>(EntryReader<'a>);
+
+impl Iterator for TraceIter<'_> {
+    type Item = TraceEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.read()
+    }
+}
+
+struct EntryReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    last_cycles: u64,
+}
+
+impl<'a> EntryReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: MAGIC.len() + 1,
+            last_cycles: 0,
+        }
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let bytes = self.data.get(self.pos..self.pos + N)?.try_into().ok()?;
+        self.pos += N;
+        Some(bytes)
+    }
+
+    fn read(&mut self) -> Option<TraceEntry> {
+        let [flags] = self.bytes::<1>()?;
+        let thumb = flags & FLAG_THUMB != 0;
+        let address = u32::from_le_bytes(self.bytes()?);
+        let opcode = if thumb {
+            u16::from_le_bytes(self.bytes()?) as u32
+        } else {
+            u32::from_le_bytes(self.bytes()?)
+        };
+        let (delta, read) = read_varint(&self.data[self.pos..])?;
+        self.pos += read;
+        let cycles = self.last_cycles.wrapping_add(delta);
+        let mut register_deltas = Vec::new();
+        if flags & FLAG_DELTAS != 0 {
+            let [count] = self.bytes::<1>()?;
+            for _ in 0..count {
+                let [index] = self.bytes::<1>()?;
+                let value = u32::from_le_bytes(self.bytes()?);
+                register_deltas.push(RegisterDelta {
+                    register: register_from_index(index)?,
+                    value,
+                });
+            }
+        }
+        self.last_cycles = cycles;
+        Some(TraceEntry {
+            processor: if flags & FLAG_ARM7 != 0 {
+                Processor::Arm7
+            } else {
+                Processor::Arm9
+            },
+            address,
+            opcode,
+            thumb,
+            cycles,
+            register_deltas,
+        })
+    }
+}
+
+fn register_index(register: Register) -> u8 {
+    register.index() as u8
+}
+
+fn register_from_index(index: u8) -> Option<Register> {
+    match index {
+        CPSR_INDEX => Some(Register::CPSR),
+        index => Register::try_from(index as u32).ok(),
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, byte) in data.iter().enumerate().take(10) {
+        value |= ((byte & 0x7F) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Registers compared for deltas: R0-R14 and the CPSR.
+fn snapshot(processor: Processor) -> [u32; 16] {
+    let registers = DeSmuMEMemory(PhantomData).registers(processor);
+    let mut values = [0; 16];
+    values[..15].copy_from_slice(&registers.r[..15]);
+    values[15] = registers.cpsr;
+    values
+}
+
+struct Recorder {
+    config: TraceConfig,
+    trace: Trace,
+    /// The last instruction, which is recorded once the next one shows its effects.
+    pending: Option<InstructionEvent>,
+    registers: [u32; 16],
+}
+
+impl Recorder {
+    fn on_instruction(&mut self, event: Option<&InstructionEvent>) {
+        let Some(previous) = self.pending.take() else {
+            self.pending = event.copied();
+            return;
+        };
+        let mut entry = TraceEntry {
+            processor: previous.processor,
+            address: previous.address,
+            opcode: previous.opcode,
+            thumb: previous.thumb,
+            cycles: previous.cycles,
+            register_deltas: Vec::new(),
+        };
+        if self.config.register_deltas {
+            let registers = snapshot(self.config.processor);
+            for (i, (old, new)) in self.registers.iter().zip(registers).enumerate() {
+                if *old != new {
+                    let register = if i == 15 {
+                        Register::CPSR
+                    } else {
+                        Register::try_from(i as u32).unwrap()
+                    };
+                    entry.register_deltas.push(RegisterDelta {
+                        register,
+                        value: new,
+                    });
+                }
+            }
+            self.registers = registers;
+        }
+        let branched = match event {
+            Some(event) => event.address != entry.address.wrapping_add(entry.length()),
+            // The last instruction of the trace: whether it branched is unknown.
+            None => false,
+        };
+        if !self.config.branches_only || branched {
+            self.trace.push(&entry, self.config.max_size);
+        }
+        self.pending = event.copied();
+    }
+}
+
+/// Records the instructions executed by a processor.
+///
+/// Tracing uses a callback for every executed instruction and slows down emulation considerably.
+///
+/// # Usage example
+/// ```rs
+/// use rs_desmume::TraceConfig;
+///
+/// emu.trace_mut().start(TraceConfig {
+///     register_deltas: true,
+///     ..TraceConfig::default()
+/// });
+/// emu.cycle();
+/// let trace = emu.trace_mut().stop().unwrap();
+/// trace.write_text(std::fs::File::create("trace.txt")?)?;
+/// ```
+pub struct DeSmuMETrace {
+    recording: Option<(Arc<Mutex<Recorder>>, ObserverHandle)>,
+}
+
+impl DeSmuMETrace {
+    pub(crate) fn new() -> Self {
+        Self { recording: None }
+    }
+
+    /// Start recording a new trace. A trace that is currently being recorded is discarded.
+    pub fn start(&mut self, config: TraceConfig) {
+        self.recording = None;
+        let processor = config.processor;
+        let recorder = Arc::new(Mutex::new(Recorder {
+            registers: if config.register_deltas {
+                snapshot(processor)
+            } else {
+                [0; 16]
+            },
+            config,
+            trace: Trace::new(),
+            pending: None,
+        }));
+        let observer_recorder = recorder.clone();
+        let handle = add_observer(processor, move |event| {
+            observer_recorder
+                .lock()
+                .unwrap()
+                .on_instruction(Some(event));
+        });
+        self.recording = Some((recorder, handle));
+    }
+
+    /// Stop recording and return the trace. Returns `None` if no trace is being recorded.
+    pub fn stop(&mut self) -> Option<Trace> {
+        let (recorder, handle) = self.recording.take()?;
+        drop(handle);
+        let mut recorder = recorder.lock().unwrap();
+        recorder.on_instruction(None);
+        Some(std::mem::replace(&mut recorder.trace, Trace::new()))
+    }
+
+    /// Whether a trace is being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// A copy of the trace recorded so far, without stopping. Returns `None` if no trace is
+    /// being recorded.
+    pub fn snapshot(&self) -> Option<Trace> {
+        let (recorder, _) = self.recording.as_ref()?;
+        Some(recorder.lock().unwrap().trace.clone())
+    }
+}
@@ -0,0 +1,55 @@
+use desmume_rs::mem::{Processor, UnwindMethod};
+use desmume_rs::DeSmuME;
+use std::env::current_dir;
+
+#[test]
+fn test_backtrace() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..10 {
+        emu.cycle();
+    }
+
+    let scanned = emu.memory().backtrace(Processor::Arm9);
+    assert_eq!(scanned.method, UnwindMethod::StackScan);
+    assert!(scanned.frames.iter().all(|f| f.call_site.is_some()));
+
+    emu.memory_mut()
+        .set_call_stack_tracking(Processor::Arm9, true);
+    assert!(emu.memory().is_call_stack_tracking(Processor::Arm9));
+    assert!(!emu.memory().is_call_stack_tracking(Processor::Arm7));
+    for _ in 0..5 {
+        emu.cycle();
+    }
+    let tracked = emu.memory().backtrace(Processor::Arm9);
+    assert_eq!(tracked.method, UnwindMethod::ShadowStack);
+    for frame in &tracked.frames {
+        // Calls are 4 bytes long, except for Thumb BLX <reg>.
+        let call_site = frame.call_site.unwrap();
+        let return_address = frame.return_address & !1;
+        assert!(
+            call_site == return_address - 4
+                || (frame.return_address & 1 != 0 && call_site == return_address - 2)
+        );
+        assert!(frame.function.is_some());
+        assert!(frame.sp.is_some());
+    }
+
+    // Resetting the emulator forgets the tracked calls.
+    emu.reset();
+    let reset = emu.memory().backtrace(Processor::Arm9);
+    assert_eq!(reset.method, UnwindMethod::ShadowStack);
+    assert!(reset.frames.is_empty());
+
+    emu.memory_mut()
+        .set_call_stack_tracking(Processor::Arm9, false);
+    assert_eq!(
+        emu.memory().backtrace(Processor::Arm9).method,
+        UnwindMethod::StackScan
+    );
+}
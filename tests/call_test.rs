@@ -0,0 +1,62 @@
+use desmume_rs::mem::{IndexSet, Processor};
+use desmume_rs::DeSmuME;
+use std::env::current_dir;
+
+const SUM6: u32 = 0x02300000;
+const THUMB_ADD: u32 = 0x02300100;
+
+#[test]
+fn test_call_function() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..100 {
+        emu.cycle();
+    }
+
+    // Returns the sum of its six arguments.
+    let sum6 = [
+        0xE0800001, // add r0, r0, r1
+        0xE0800002, // add r0, r0, r2
+        0xE0800003, // add r0, r0, r3
+        0xE59DC000, // ldr r12, [sp]
+        0xE080000C, // add r0, r0, r12
+        0xE59DC004, // ldr r12, [sp, #4]
+        0xE080000C, // add r0, r0, r12
+        0xE12FFF1E, // bx lr
+    ];
+    for (i, opcode) in sum6.iter().enumerate() {
+        emu.memory_mut()
+            .u32_mut()
+            .index_set(SUM6 + i as u32 * 4, opcode);
+    }
+    // adds r0, r0, r1; bx lr
+    emu.memory_mut().u32_mut().index_set(THUMB_ADD, &0x47701840);
+
+    let before = emu.memory().cpu_state(Processor::Arm9);
+    let result = emu
+        .call_function(Processor::Arm9, SUM6, &[1, 2, 3, 4, 5, 6])
+        .unwrap();
+    assert_eq!(result, 21);
+    assert_eq!(
+        emu.memory().cpu_state(Processor::Arm9).registers,
+        before.registers
+    );
+
+    let result = emu
+        .call_function(Processor::Arm9, THUMB_ADD | 1, &[40, 2])
+        .unwrap();
+    assert_eq!(result, 42);
+    let after = emu.memory().cpu_state(Processor::Arm9);
+    assert_eq!(after.cpsr, before.cpsr);
+    assert_eq!(after.instruction_address, before.instruction_address);
+
+    // The game keeps running afterwards.
+    for _ in 0..10 {
+        emu.cycle();
+    }
+}
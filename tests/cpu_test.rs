@@ -0,0 +1,63 @@
+use desmume_rs::mem::{Cpsr, CpuMode, Processor, Register};
+use desmume_rs::DeSmuME;
+use std::env::current_dir;
+
+#[test]
+fn test_cpsr_decoding() {
+    let cpsr = Cpsr::from(0x6000_003F);
+    assert!(!cpsr.negative);
+    assert!(cpsr.zero);
+    assert!(cpsr.carry);
+    assert!(!cpsr.overflow);
+    assert!(cpsr.thumb);
+    assert!(!cpsr.irq_disabled);
+    assert_eq!(cpsr.mode, Some(CpuMode::System));
+
+    let cpsr = Cpsr::from(0x0000_00D2);
+    assert!(cpsr.irq_disabled);
+    assert!(cpsr.fiq_disabled);
+    assert!(!cpsr.thumb);
+    assert_eq!(cpsr.mode, Some(CpuMode::Irq));
+
+    assert_eq!(Cpsr::from(0x15).mode, None);
+    assert_eq!(
+        CpuMode::from_bits(CpuMode::Undefined.bits()),
+        Some(CpuMode::Undefined)
+    );
+}
+
+#[test]
+fn test_cpu_state() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..100 {
+        emu.cycle();
+    }
+
+    for processor in [Processor::Arm9, Processor::Arm7] {
+        let mem = emu.memory();
+        let state = mem.cpu_state(processor);
+        assert_eq!(state.processor, processor);
+        assert_eq!(state.registers, mem.registers(processor));
+        assert_eq!(state.cpsr, mem.cpsr(processor));
+        let mode = state.cpsr.mode.expect("valid mode");
+        assert_eq!(
+            state.register_in_mode(mode, 13),
+            mem.get_reg(processor, Register::SP)
+        );
+        assert_eq!(
+            state.register_in_mode(mode, 14),
+            mem.get_reg(processor, Register::LR)
+        );
+    }
+
+    let cp15 = emu.memory().cp15();
+    assert!(cp15.mpu_enabled);
+    assert!(cp15.dtcm_enabled);
+    assert!(cp15.regions.iter().any(|r| r.enabled));
+}
@@ -0,0 +1,60 @@
+use desmume_rs::mem::{IndexSet, Processor};
+use desmume_rs::{CrashConfig, CrashKind, DeSmuME};
+use std::env::current_dir;
+
+const JUMP: u32 = 0x02300000;
+const PALETTE: u32 = 0x05000000;
+
+#[test]
+fn test_crash_detector() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..10 {
+        emu.cycle();
+    }
+
+    emu.crash_detector_mut().start(CrashConfig {
+        tight_loop_frames: None,
+        unchanged_output_frames: Some(30),
+        ..CrashConfig::default()
+    });
+    assert!(emu.crash_detector().is_running());
+
+    // Jump to a `bx lr` in palette memory, which then returns to the caller.
+    let code = [
+        0xE59F1000, // ldr r1, [pc]
+        0xE12FFF11, // bx r1
+        PALETTE,
+    ];
+    for (i, word) in code.iter().enumerate() {
+        emu.memory_mut()
+            .u32_mut()
+            .index_set(JUMP + i as u32 * 4, word);
+    }
+    emu.memory_mut().u32_mut().index_set(PALETTE, &0xE12FFF1E);
+    emu.call_function(Processor::Arm9, JUMP, &[]).unwrap();
+
+    let reports = emu.crash_detector_mut().take_reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].kind, CrashKind::UnmappedJump { from: JUMP + 4 });
+    assert_eq!(reports[0].processor, Processor::Arm9);
+    assert_eq!(reports[0].pc, PALETTE);
+    assert_eq!(reports[0].recent.last().unwrap().address, PALETTE);
+
+    // The test ROM shows a static screen without input.
+    for _ in 0..60 {
+        emu.cycle();
+    }
+    let reports = emu.crash_detector_mut().take_reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].kind, CrashKind::FrozenOutput);
+
+    emu.crash_detector_mut().stop();
+    emu.call_function(Processor::Arm9, JUMP, &[]).unwrap();
+    assert!(emu.crash_detector_mut().take_reports().is_empty());
+}
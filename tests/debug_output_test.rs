@@ -0,0 +1,71 @@
+use desmume_rs::mem::{IndexSet, Processor};
+use desmume_rs::DeSmuME;
+use std::env::current_dir;
+use std::sync::{Arc, Mutex};
+
+const PRINT: u32 = 0x02300000;
+const TEXT: u32 = 0x02300100;
+
+/// Writes R0 to `port`.
+fn write_print_function(emu: &mut DeSmuME, port: u32) {
+    let code = [
+        0xE59F1004, // ldr r1, [pc, #4]
+        0xE5810000, // str r0, [r1]
+        0xE12FFF1E, // bx lr
+        port,
+    ];
+    for (i, word) in code.iter().enumerate() {
+        emu.memory_mut()
+            .u32_mut()
+            .index_set(PRINT + i as u32 * 4, word);
+    }
+}
+
+#[test]
+fn test_debug_output() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..10 {
+        emu.cycle();
+    }
+
+    for (i, byte) in b"answer: %r2%\0".iter().enumerate() {
+        emu.memory_mut().u8_mut().index_set(TEXT + i as u32, byte);
+    }
+
+    let printed = Arc::new(Mutex::new(Vec::new()));
+    let printed_clone = printed.clone();
+    emu.debug_output_mut().start(false);
+    emu.debug_output_mut().set_callback(move |message| {
+        printed_clone.lock().unwrap().push(message.text.clone());
+    });
+    assert!(emu.debug_output().is_capturing());
+
+    write_print_function(&mut emu, 0x04FFFA18);
+    emu.call_function(Processor::Arm9, PRINT, &[TEXT, 0, 42])
+        .unwrap();
+    write_print_function(&mut emu, 0x04FFFA1C);
+    for c in b"hi\n" {
+        emu.call_function(Processor::Arm9, PRINT, &[*c as u32])
+            .unwrap();
+    }
+
+    let messages = emu.debug_output_mut().take_messages();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].text, "answer: 0000002a");
+    assert_eq!(messages[0].processor, Processor::Arm9);
+    assert_eq!(messages[0].frame, emu.frame_count());
+    assert_eq!(messages[1].text, "hi");
+    assert_eq!(*printed.lock().unwrap(), vec!["answer: 0000002a", "hi"]);
+    assert!(emu.debug_output_mut().take_messages().is_empty());
+
+    emu.debug_output_mut().stop();
+    emu.call_function(Processor::Arm9, PRINT, &[b'\n' as u32])
+        .unwrap();
+    assert!(emu.debug_output_mut().take_messages().is_empty());
+}
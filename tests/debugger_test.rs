@@ -0,0 +1,66 @@
+use desmume_rs::debugger::{Breakpoint, Comparison, Condition, StopReason};
+use desmume_rs::mem::{HookKind, IndexMove, Processor, Register};
+use desmume_rs::DeSmuME;
+use std::env::current_dir;
+
+#[test]
+fn test_breakpoints() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    // The ARM9 entry point from the header copy in main RAM.
+    let entry = emu.memory().u32().index_move(0x27FFE24);
+
+    let mut debugger = emu.debugger();
+    let never = debugger.add_breakpoint(Breakpoint::exec(Processor::Arm9, entry).with_condition(
+        Condition::Register {
+            register: Register::R0,
+            comparison: Comparison::Equal,
+            value: 0xDEADBEEF,
+        },
+    ));
+    let entry_bp = debugger.add_breakpoint(Breakpoint::exec(Processor::Arm9, entry));
+    assert_eq!(debugger.breakpoints().count(), 2);
+
+    match debugger.run_until_break(10) {
+        StopReason::Breakpoint(hit) => {
+            assert_eq!(hit.id, entry_bp);
+            assert_eq!(hit.kind, HookKind::Exec);
+            assert_eq!(hit.processor, Processor::Arm9);
+            assert_eq!(hit.address, entry);
+            assert_eq!(hit.hits, 1);
+        }
+        other => panic!("entry point breakpoint was not hit: {other:?}"),
+    }
+    // Execution stops right at the breakpoint.
+    assert_eq!(debugger.instruction_address(Processor::Arm9), entry);
+    assert_eq!(debugger.hit_count(never), 0);
+
+    // Disabled breakpoints never stop the emulator. The game writes to main RAM every frame.
+    let ram_bp = debugger.add_breakpoint(Breakpoint::write(Processor::Arm9, 0x02000000, 0x400000));
+    debugger.set_enabled(entry_bp, false);
+    debugger.set_enabled(ram_bp, false);
+    assert!(matches!(
+        debugger.run_until_break(5),
+        StopReason::FrameLimit
+    ));
+    assert_eq!(debugger.hit_count(ram_bp), 0);
+    debugger.set_enabled(ram_bp, true);
+    match debugger.run_until_break(5) {
+        StopReason::Breakpoint(hit) => {
+            assert_eq!(hit.id, ram_bp);
+            assert_eq!(hit.kind, HookKind::Write);
+        }
+        other => panic!("main RAM breakpoint was not hit: {other:?}"),
+    }
+    assert_eq!(debugger.hit_count(entry_bp), 1);
+
+    assert!(debugger.remove_breakpoint(entry_bp));
+    assert!(!debugger.remove_breakpoint(entry_bp));
+    debugger.clear_breakpoints();
+    assert_eq!(debugger.breakpoints().count(), 0);
+}
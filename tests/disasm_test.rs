@@ -0,0 +1,93 @@
+use desmume_rs::disasm::{decode_arm, decode_thumb, disassemble, Architecture, Mode};
+
+fn arm(address: u32, opcode: u32) -> String {
+    decode_arm(address, opcode, Architecture::ArmV5TE).to_string()
+}
+
+fn thumb(address: u32, opcode: u16) -> String {
+    decode_thumb(address, opcode, None, Architecture::ArmV5TE).to_string()
+}
+
+#[test]
+fn test_arm() {
+    assert_eq!(arm(0, 0xE3A00001), "mov r0, #1");
+    assert_eq!(arm(0, 0xE0810002), "add r0, r1, r2");
+    assert_eq!(arm(0, 0xE0910102), "adds r0, r1, r2, lsl #2");
+    assert_eq!(arm(0, 0xE3500C01), "cmp r0, #0x100");
+    assert_eq!(arm(0, 0xE92D4010), "push {r4, lr}");
+    assert_eq!(arm(0, 0xE8BD80F0), "pop {r4-r7, pc}");
+    assert_eq!(arm(0, 0xE12FFF1E), "bx lr");
+    assert_eq!(arm(0, 0xE5910004), "ldr r0, [r1, #4]");
+    assert_eq!(arm(0, 0xE5310010), "ldr r0, [r1, #-0x10]!");
+    assert_eq!(arm(0, 0xE1C020D8), "ldrd r2, r3, [r0, #8]");
+    assert_eq!(arm(0, 0xE0100291), "muls r0, r1, r2");
+    assert_eq!(arm(0, 0xE16F0F11), "clz r0, r1");
+    assert_eq!(arm(0, 0xE10F0000), "mrs r0, cpsr");
+    assert_eq!(arm(0, 0xE121F000), "msr cpsr_c, r0");
+    assert_eq!(arm(0, 0xEE110F10), "mrc p15, 0, r0, c1, c0, 0");
+    assert_eq!(arm(0, 0xEF000005), "swi #5");
+
+    let bl = decode_arm(0x02000000, 0xEB000000, Architecture::ArmV5TE);
+    assert_eq!(bl.mnemonic, "bl");
+    assert_eq!(bl.branch_target, Some(0x02000008));
+    assert_eq!(bl.length, 4);
+    let beq = decode_arm(0x02000100, 0x0AFFFFFE, Architecture::ArmV5TE);
+    assert_eq!(beq.mnemonic, "beq");
+    assert_eq!(beq.branch_target, Some(0x02000100));
+    let blx = decode_arm(0x02000000, 0xFB000000, Architecture::ArmV5TE);
+    assert_eq!(blx.mnemonic, "blx");
+    assert_eq!(blx.branch_target, Some(0x0200000A));
+
+    // ARMv5TE only.
+    let clz = decode_arm(0, 0xE16F0F11, Architecture::ArmV4T);
+    assert_eq!(clz.mnemonic, "undefined");
+}
+
+#[test]
+fn test_thumb() {
+    assert_eq!(thumb(0, 0x2001), "mov r0, #1");
+    assert_eq!(thumb(0, 0x1888), "add r0, r1, r2");
+    assert_eq!(thumb(0, 0x0088), "lsl r0, r1, #2");
+    assert_eq!(thumb(0, 0xB510), "push {r4, lr}");
+    assert_eq!(thumb(0, 0xBD10), "pop {r4, pc}");
+    assert_eq!(thumb(0, 0x4770), "bx lr");
+    assert_eq!(thumb(0, 0x4680), "mov r8, r0");
+    assert_eq!(thumb(0, 0x6848), "ldr r0, [r1, #4]");
+    assert_eq!(thumb(0, 0x9801), "ldr r0, [sp, #4]");
+    assert_eq!(thumb(0, 0xC00C), "stmia r0!, {r2, r3}");
+    assert_eq!(thumb(0, 0xDF05), "swi #5");
+
+    let beq = decode_thumb(0x100, 0xD0FE, None, Architecture::ArmV5TE);
+    assert_eq!(beq.mnemonic, "beq");
+    assert_eq!(beq.branch_target, Some(0x100));
+
+    let bl = decode_thumb(0x02000000, 0xF000, Some(0xF802), Architecture::ArmV5TE);
+    assert_eq!(bl.mnemonic, "bl");
+    assert_eq!(bl.length, 4);
+    assert_eq!(bl.branch_target, Some(0x02000008));
+    let blx = decode_thumb(0x02000002, 0xF000, Some(0xE802), Architecture::ArmV5TE);
+    assert_eq!(blx.mnemonic, "blx");
+    assert_eq!(blx.branch_target, Some(0x02000008));
+
+    // ARMv5TE only.
+    let blx = decode_thumb(0, 0x47F0, None, Architecture::ArmV4T);
+    assert_eq!(blx.mnemonic, "undefined");
+}
+
+#[test]
+fn test_disassemble_buffer() {
+    // push {lr}; bl 0x0200000A; pop {pc}
+    let code = [0x00, 0xB5, 0x00, 0xF0, 0x01, 0xF8, 0x00, 0xBD];
+    let instructions = disassemble(&code, 0x02000000, Mode::Thumb, Architecture::ArmV5TE);
+    assert_eq!(instructions.len(), 3);
+    assert_eq!(instructions[0].to_string(), "push {lr}");
+    assert_eq!(instructions[1].address, 0x02000002);
+    assert_eq!(instructions[1].branch_target, Some(0x02000008));
+    assert_eq!(instructions[2].address, 0x02000006);
+    assert_eq!(instructions[2].to_string(), "pop {pc}");
+
+    let code = [0x01, 0x00, 0xA0, 0xE3, 0x1E, 0xFF, 0x2F, 0xE1, 0x00];
+    let instructions = disassemble(&code, 0, Mode::Arm, Architecture::ArmV4T);
+    assert_eq!(instructions.len(), 2);
+    assert_eq!(instructions[1].to_string(), "bx lr");
+}
@@ -0,0 +1,148 @@
+use desmume_rs::mem::{
+    DmaTiming, HookAction, IndexMove, IndexSet, IrqEventKind, IrqSource, Processor,
+};
+use desmume_rs::DeSmuME;
+use std::env::current_dir;
+use std::sync::{Arc, Mutex};
+
+const DMA_SOURCE: u32 = 0x02300000;
+const DMA_DESTINATION: u32 = 0x02300100;
+
+#[test]
+fn test_event_hooks() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..100 {
+        emu.cycle();
+    }
+
+    let irqs = Arc::new(Mutex::new(Vec::new()));
+    let irqs_clone = irqs.clone();
+    let irq_handle = emu.memory_mut().hook_irq(Processor::Arm9, move |event| {
+        assert_eq!(event.processor, Processor::Arm9);
+        irqs_clone.lock().unwrap().push((event.kind, event.source));
+        HookAction::Continue
+    });
+    let once = Arc::new(Mutex::new(0));
+    let once_clone = once.clone();
+    let _once_handle = emu.memory_mut().hook_irq(Processor::Arm9, move |_| {
+        *once_clone.lock().unwrap() += 1;
+        HookAction::Unregister
+    });
+    let ipc = Arc::new(Mutex::new(Vec::new()));
+    let ipc_clone = ipc.clone();
+    let _ipc_handle = emu
+        .memory_mut()
+        .hook_ipc_fifo(Processor::Arm7, move |event| {
+            ipc_clone.lock().unwrap().push(event.processor);
+            HookAction::Continue
+        });
+
+    for _ in 0..10 {
+        emu.cycle();
+    }
+
+    {
+        let irqs = irqs.lock().unwrap();
+        let vblanks = irqs
+            .iter()
+            .filter(|&&(kind, source)| kind == IrqEventKind::Raised && source == IrqSource::VBlank)
+            .count();
+        assert!(vblanks >= 9, "{vblanks} VBlank IRQs");
+        assert!(irqs
+            .iter()
+            .any(|&(kind, _)| kind == IrqEventKind::Acknowledged));
+    }
+    assert_eq!(*once.lock().unwrap(), 1);
+    // The ARM7 of the test ROM talks to the ARM9 through the FIFO every frame.
+    {
+        let ipc = ipc.lock().unwrap();
+        assert!(!ipc.is_empty());
+        assert!(ipc.iter().all(|&p| p == Processor::Arm7));
+    }
+
+    // Start an immediate DMA transfer of 4 words on channel 3.
+    let dmas = Arc::new(Mutex::new(Vec::new()));
+    let dmas_clone = dmas.clone();
+    let _dma_handle = emu.memory_mut().hook_dma(Processor::Arm9, move |event| {
+        if event.channel == 3 {
+            dmas_clone.lock().unwrap().push(event.clone());
+        }
+        HookAction::Continue
+    });
+    for i in 0..4 {
+        emu.memory_mut()
+            .u32_mut()
+            .index_set(DMA_SOURCE + i * 4, &(0x11111111 * (i + 1)));
+    }
+    emu.memory_mut()
+        .u32_mut()
+        .index_set(0x040000D4, &DMA_SOURCE);
+    emu.memory_mut()
+        .u32_mut()
+        .index_set(0x040000D8, &DMA_DESTINATION);
+    emu.memory_mut()
+        .u32_mut()
+        .index_set(0x040000DC, &0x84000004);
+    emu.cycle();
+    {
+        let dmas = dmas.lock().unwrap();
+        let dma = dmas
+            .iter()
+            .find(|d| d.source == DMA_SOURCE)
+            .expect("DMA transfer was not reported");
+        assert_eq!(dma.processor, Processor::Arm9);
+        assert_eq!(dma.destination, DMA_DESTINATION);
+        assert_eq!(dma.length, 4);
+        assert_eq!(dma.unit_size, 4);
+        assert!(matches!(dma.timing, DmaTiming::Immediate));
+        assert!(!dma.repeat);
+        assert_eq!(dma.control, 0x84000004);
+    }
+    assert_eq!(
+        emu.memory().u32().index_move(DMA_DESTINATION + 12),
+        0x44444444
+    );
+
+    // Let timer 3 overflow every 256 cycles.
+    let timers = Arc::new(Mutex::new(Vec::new()));
+    let timers_clone = timers.clone();
+    let timer_handle = emu
+        .memory_mut()
+        .hook_timer_overflow(Processor::Arm9, move |event| {
+            timers_clone
+                .lock()
+                .unwrap()
+                .push((event.processor, event.timer));
+            HookAction::Continue
+        });
+    emu.memory_mut().u16_mut().index_set(0x0400010C, &0xFF00);
+    emu.memory_mut().u16_mut().index_set(0x0400010E, &0x0080);
+    emu.cycle();
+    emu.memory_mut().u16_mut().index_set(0x0400010E, &0);
+    drop(timer_handle);
+    {
+        let timers = timers.lock().unwrap();
+        assert!(timers.contains(&(Processor::Arm9, 3)));
+        assert!(timers.iter().all(|&(p, _)| p == Processor::Arm9));
+    }
+
+    drop(irq_handle);
+    let count = irqs.lock().unwrap().len();
+    emu.cycle();
+    assert_eq!(irqs.lock().unwrap().len(), count);
+}
+
+#[test]
+fn test_irq_source_bits() {
+    for bit in 0..32 {
+        assert_eq!(IrqSource::from_bit(bit).bit(), bit);
+    }
+    assert_eq!(IrqSource::from_bit(4), IrqSource::Timer(1));
+    assert_eq!(IrqSource::from_bit(18), IrqSource::IpcReceiveFifoNotEmpty);
+}
@@ -0,0 +1,95 @@
+use desmume_rs::gdbstub::GdbStub;
+use desmume_rs::mem::Processor;
+use desmume_rs::DeSmuME;
+use std::env::current_dir;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread;
+
+/// A minimal scripted GDB client.
+struct Client(TcpStream);
+
+impl Client {
+    fn request(&mut self, data: &str) -> String {
+        let checksum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        write!(self.0, "${data}#{checksum:02x}").unwrap();
+        assert_eq!(self.read_byte(), b'+');
+        while self.read_byte() != b'$' {}
+        let mut reply = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => reply.push(byte),
+            }
+        }
+        self.read_byte();
+        self.read_byte();
+        self.0.write_all(b"+").unwrap();
+        String::from_utf8(reply).unwrap()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.0.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+}
+
+#[test]
+fn test_gdbstub() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..10 {
+        emu.cycle();
+    }
+
+    let stub = GdbStub::bind("127.0.0.1:0", Processor::Arm9).unwrap();
+    let addr = stub.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let mut client = Client(TcpStream::connect(addr).unwrap());
+        assert!(client
+            .request("qSupported:swbreak+")
+            .contains("qXfer:features:read+"));
+        assert!(client
+            .request("qXfer:features:read:target.xml:0,1000")
+            .starts_with("l<?xml"));
+        assert_eq!(client.request("?"), "S05");
+
+        let registers = client.request("g");
+        assert_eq!(registers.len(), 17 * 8);
+        assert_eq!(client.request("P0=78563412"), "OK");
+        assert_eq!(client.request("p0"), "78563412");
+        // Writing the PC redirects execution there.
+        let pc = client.request("pf");
+        assert_eq!(client.request("Pf=00000002"), "OK");
+        assert_eq!(client.request("pf"), "00000002");
+        assert_eq!(client.request(&format!("Pf={pc}")), "OK");
+        assert_eq!(client.request("pf"), pc);
+
+        assert_eq!(client.request("M2000000,4:efbeadde"), "OK");
+        assert_eq!(client.request("m2000000,4"), "efbeadde");
+        assert_eq!(client.request("m2000000,2000").len(), 0x4000);
+        assert_eq!(client.request("m2000000,2001"), "E01");
+        assert_eq!(client.request("m2000000,ffffffff"), "E01");
+
+        assert_eq!(client.request("s"), "S05");
+
+        // The game writes to main RAM all the time.
+        assert_eq!(client.request("Z2,2000000,400000"), "OK");
+        let stop = client.request("c");
+        assert!(stop.starts_with("T05watch:"), "{stop}");
+        assert_eq!(client.request("z2,2000000,400000"), "OK");
+
+        assert_eq!(client.request("D"), "OK");
+    });
+
+    stub.serve(&mut emu).unwrap();
+    client.join().unwrap();
+    assert_eq!(emu.debugger().breakpoints().count(), 0);
+}
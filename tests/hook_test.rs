@@ -0,0 +1,80 @@
+use desmume_rs::mem::{HookAction, HookKind, IndexMove, IndexSet, Processor};
+use desmume_rs::DeSmuME;
+use std::env::current_dir;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::sync::Mutex;
+
+#[test]
+fn test_closure_hooks() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..100 {
+        emu.cycle();
+    }
+
+    let first = Arc::new(AtomicUsize::new(0));
+    let second = Arc::new(AtomicUsize::new(0));
+    let once = Arc::new(AtomicUsize::new(0));
+
+    let first_clone = first.clone();
+    let first_handle = emu.memory_mut().hook_read(0x200000E, 2, move |ctx| {
+        assert_eq!(ctx.kind, HookKind::Read);
+        assert_eq!(ctx.processor, Processor::Arm9);
+        assert_eq!(ctx.address, 0x200000E);
+        first_clone.fetch_add(1, Ordering::Relaxed);
+        HookAction::Continue
+    });
+    let second_clone = second.clone();
+    let _second_handle = emu.memory_mut().hook_read(0x200000E, 2, move |_| {
+        second_clone.fetch_add(1, Ordering::Relaxed);
+        HookAction::Continue
+    });
+    let once_clone = once.clone();
+    let _once_handle = emu.memory_mut().hook_read(0x200000E, 2, move |_| {
+        once_clone.fetch_add(1, Ordering::Relaxed);
+        HookAction::Unregister
+    });
+
+    let _ = emu.memory().u16().index_move(0x200000E);
+    assert_eq!(first.load(Ordering::Relaxed), 1);
+    assert_eq!(second.load(Ordering::Relaxed), 1);
+    assert_eq!(once.load(Ordering::Relaxed), 1);
+
+    drop(first_handle);
+    let _ = emu.memory().u16().index_move(0x200000E);
+    assert_eq!(first.load(Ordering::Relaxed), 1);
+    assert_eq!(second.load(Ordering::Relaxed), 2);
+    assert_eq!(once.load(Ordering::Relaxed), 1);
+
+    // Write hooks see the old and the new value.
+    let original = emu.memory().u32().index_move(0x2000010);
+    let seen = Arc::new(Mutex::new(None));
+    let seen_clone = seen.clone();
+    let _write_handle = emu.memory_mut().hook_write(0x2000010, 4, move |ctx| {
+        *seen_clone.lock().unwrap() = Some((ctx.size, ctx.old_value, ctx.new_value));
+        HookAction::Continue
+    });
+    emu.memory_mut().u32_mut().index_set(0x2000010, &0x12345678);
+    assert_eq!(*seen.lock().unwrap(), Some((4, original, 0x12345678)));
+
+    // Exec hooks fire for every instruction in the range.
+    let executed = Arc::new(AtomicUsize::new(0));
+    let executed_clone = executed.clone();
+    let exec_handle = emu
+        .memory_mut()
+        .hook_exec_range(0x2000000, 0x10000, move |ctx| {
+            assert_eq!(ctx.kind, HookKind::Exec);
+            assert!((0x2000000..0x2010000).contains(&ctx.address));
+            executed_clone.fetch_add(1, Ordering::Relaxed);
+            HookAction::Continue
+        });
+    emu.cycle();
+    assert!(executed.load(Ordering::Relaxed) > 1);
+    drop(exec_handle);
+}
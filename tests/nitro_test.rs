@@ -0,0 +1,147 @@
+use desmume_rs::mem::{IndexSet, Processor};
+use desmume_rs::nitro::{Arena, OverlayTable, ThreadState};
+use desmume_rs::{DeSmuME, DeSmuMEError};
+use std::env::current_dir;
+
+const OVERLAY_ADDRESS: u32 = 0x02300000;
+const THREAD_INFO: u32 = 0x02301000;
+const MAIN_THREAD: u32 = 0x02302000;
+const IDLE_THREAD: u32 = 0x02303000;
+
+/// A ROM with two ARM9 overlays at the same address, the second one compressed.
+fn test_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x200];
+    let mut put = |offset: usize, value: u32| {
+        rom[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    };
+    put(0x48, 0x100); // FAT
+    put(0x50, 0x80); // ARM9 overlay table
+    put(0x54, 0x40);
+    // id, RAM address, RAM size, .bss size, static initializers, file id, flags
+    for (i, value) in [0, OVERLAY_ADDRESS, 8, 0x10, 0, 0, 0, 0].iter().enumerate() {
+        put(0x80 + i * 4, *value);
+    }
+    let compressed = [1, OVERLAY_ADDRESS, 21, 0, 0, 0, 1, (1 << 24) | 14];
+    for (i, value) in compressed.iter().enumerate() {
+        put(0xA0 + i * 4, *value);
+    }
+    put(0x100, 0x140);
+    put(0x104, 0x148);
+    put(0x108, 0x160);
+    put(0x10C, 0x16E);
+    put(0x166, 0x0800000E);
+    put(0x16A, 7);
+    rom[0x140..0x148].copy_from_slice(b"overlay0");
+    // "XYZ", then copy 18 bytes from 3 bytes further.
+    rom[0x160..0x166].copy_from_slice(&[0x00, 0xF0, b'X', b'Y', b'Z', 0x10]);
+    rom
+}
+
+#[test]
+fn test_overlay_table() {
+    let table = OverlayTable::parse(&test_rom()).unwrap();
+    assert_eq!(table.overlays().len(), 2);
+
+    let plain = table.get(Processor::Arm9, 0).unwrap();
+    assert!(!plain.compressed);
+    assert_eq!(plain.range(), OVERLAY_ADDRESS..OVERLAY_ADDRESS + 0x18);
+    assert!(plain.contains(OVERLAY_ADDRESS + 0x10));
+    assert_eq!(table.contents(plain), Some(&b"overlay0"[..]));
+
+    let compressed = table.get(Processor::Arm9, 1).unwrap();
+    assert!(compressed.compressed);
+    assert_eq!(compressed.file_id, 1);
+    assert_eq!(
+        table.contents(compressed),
+        Some(&b"XYZXYZXYZXYZXYZXYZXYZ"[..])
+    );
+    assert!(table.get(Processor::Arm7, 0).is_none());
+
+    assert!(matches!(
+        OverlayTable::parse(&test_rom()[..0x90]),
+        Err(DeSmuMEError::InvalidRom(_))
+    ));
+}
+
+fn write_words(emu: &mut DeSmuME, address: u32, words: &[u32]) {
+    for (i, word) in words.iter().enumerate() {
+        emu.memory_mut()
+            .u32_mut()
+            .index_set(address + i as u32 * 4, word);
+    }
+}
+
+fn write_thread(emu: &mut DeSmuME, address: u32, fields: &[u32]) {
+    let mut context = vec![0x1F];
+    context.extend(0..13);
+    context.extend([0x02380000, 0x02000100, 0x02000204, 0x023F0000]);
+    write_words(emu, address, &context);
+    write_words(emu, address + 0x64, fields);
+}
+
+#[test]
+fn test_nitro_runtime() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..10 {
+        emu.cycle();
+    }
+
+    write_words(&mut emu, 0x027FFFA0, &[THREAD_INFO]);
+    write_words(&mut emu, THREAD_INFO, &[0, MAIN_THREAD, MAIN_THREAD, 0]);
+    // state, next, id, priority, 7 unused words, stack top, stack bottom
+    let mut main = vec![1, IDLE_THREAD, 1, 10, 0, 0, 0, 0, 0, 0, 0];
+    main.extend([0x02370000, 0x02380000]);
+    write_thread(&mut emu, MAIN_THREAD, &main);
+    write_thread(&mut emu, IDLE_THREAD, &[0, 0, 0, 31]);
+
+    let nitro = emu.memory().nitro();
+    let threads = nitro.threads(Processor::Arm9);
+    assert_eq!(threads.len(), 2);
+    assert_eq!(threads[0].address, MAIN_THREAD);
+    assert_eq!(threads[0].state, ThreadState::Ready);
+    assert_eq!(threads[0].priority, 10);
+    assert!(threads[0].current);
+    assert_eq!(threads[0].context.r[12], 12);
+    assert_eq!(threads[0].context.sp, 0x02380000);
+    assert_eq!(threads[0].context.pc, 0x02000200);
+    assert_eq!(threads[0].stack_top, 0x02370000);
+    assert_eq!(threads[0].stack_bottom, 0x02380000);
+    assert_eq!(threads[1].state, ThreadState::Waiting);
+    assert_eq!(threads[1].priority, 31);
+    assert!(!threads[1].current);
+    assert_eq!(nitro.current_thread(Processor::Arm9).unwrap().id, 1);
+
+    let mut arena_info = vec![0; 19];
+    arena_info[0] = 0x02100000;
+    arena_info[9] = 0x02200000;
+    arena_info[18] = 1;
+    write_words(&mut emu, 0x027FFDA0, &arena_info);
+    let arenas = emu.memory().nitro().arenas();
+    assert_eq!(arenas.len(), 9);
+    assert_eq!(arenas[0].arena, Arena::Main);
+    assert_eq!((arenas[0].lo, arenas[0].hi), (0x02100000, 0x02200000));
+
+    let table = OverlayTable::parse(&test_rom()).unwrap();
+    let contents = table.contents(table.get(Processor::Arm9, 1).unwrap());
+    for (i, byte) in contents.unwrap().iter().enumerate() {
+        emu.memory_mut()
+            .u8_mut()
+            .index_set(OVERLAY_ADDRESS + i as u32, byte);
+    }
+    let nitro = emu.memory().nitro();
+    let loaded = nitro.loaded_overlays(&table, Processor::Arm9);
+    assert_eq!(loaded.len(), 1);
+    assert_eq!(loaded[0].id, 1);
+    assert_eq!(
+        nitro
+            .overlay_at(&table, Processor::Arm9, OVERLAY_ADDRESS + 4)
+            .map(|o| o.id),
+        Some(1)
+    );
+}
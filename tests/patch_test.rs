@@ -0,0 +1,61 @@
+use desmume_rs::mem::{HookAction, IndexSet, Processor};
+use desmume_rs::symbols::{Symbol, Symbolize, Symbols};
+use desmume_rs::DeSmuME;
+use std::env::current_dir;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+const ZERO: u32 = 0x02300200;
+
+#[test]
+fn test_patch_function() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..100 {
+        emu.cycle();
+    }
+
+    // mov r0, #0; bx lr
+    emu.memory_mut().u32_mut().index_set(ZERO, &0xE3A00000);
+    emu.memory_mut().u32_mut().index_set(ZERO + 4, &0xE12FFF1E);
+    assert_eq!(emu.call_function(Processor::Arm9, ZERO, &[7]).unwrap(), 0);
+
+    let mut symbols = Symbols::new();
+    symbols.insert(Symbol::new("Zero", ZERO));
+    let patch = emu
+        .memory_mut()
+        .patch_function(Processor::Arm9, ZERO, move |ctx| {
+            assert_eq!(ctx.address, ZERO);
+            assert!(ctx
+                .symbolize(&symbols)
+                .starts_with("Zero(0x00000007, 0x00000000, 0x00000000, 0x00000000) returning to "));
+            let result = ctx.args[0] * 2 + ctx.arg(4);
+            ctx.set_return(result);
+            HookAction::Continue
+        });
+    assert_eq!(
+        emu.call_function(Processor::Arm9, ZERO, &[7, 0, 0, 0, 100])
+            .unwrap(),
+        114
+    );
+    drop(patch);
+    assert_eq!(emu.call_function(Processor::Arm9, ZERO, &[7]).unwrap(), 0);
+
+    // Instrument without replacing.
+    let calls = Arc::new(AtomicUsize::new(0));
+    let calls_clone = calls.clone();
+    let _patch = emu
+        .memory_mut()
+        .patch_function(Processor::Arm9, ZERO, move |ctx| {
+            calls_clone.fetch_add(1, Ordering::Relaxed);
+            ctx.fall_through();
+            HookAction::Continue
+        });
+    assert_eq!(emu.call_function(Processor::Arm9, ZERO, &[7]).unwrap(), 0);
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+}
@@ -0,0 +1,81 @@
+use desmume_rs::mem::Processor;
+use desmume_rs::symbols::Symbol;
+use desmume_rs::{DeSmuME, ProfilerConfig};
+use std::env::current_dir;
+
+#[test]
+fn test_profiler() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..10 {
+        emu.cycle();
+    }
+
+    emu.profiler_mut().start(ProfilerConfig::default());
+    assert!(emu.profiler().is_running());
+    for _ in 0..5 {
+        emu.cycle();
+    }
+    let profile = emu.profiler_mut().stop().unwrap();
+    assert!(!emu.profiler().is_running());
+    assert!(emu.profiler_mut().stop().is_none());
+
+    for processor in [Processor::Arm9, Processor::Arm7] {
+        let p = profile.get(processor).unwrap();
+        assert!(p.total_instructions() > 0);
+        assert!(p.total_cycles() > 0);
+        assert!(!p.blocks.is_empty());
+        let (hottest, count) = p.hot_spots(1)[0];
+        assert_eq!(p.instructions[&hottest], count);
+    }
+
+    // A symbol covering all of main RAM collects everything the ARM9 ran there.
+    let arm9 = profile.get(Processor::Arm9).unwrap();
+    let mut symbols = emu.symbols().clone();
+    symbols.insert(Symbol::new("MainRam", 0x02000000).with_length(0x400000));
+    let in_main_ram: u64 = arm9
+        .instructions
+        .iter()
+        .filter(|(&a, _)| (0x02000000..0x02400000).contains(&a))
+        .map(|(_, &n)| n)
+        .sum();
+    let functions = arm9.functions(&symbols);
+    if in_main_ram > 0 {
+        assert_eq!(functions[0].name, "MainRam");
+        assert_eq!(functions[0].instructions, in_main_ram);
+    }
+
+    let mut lcov = Vec::new();
+    profile.write_lcov(&mut lcov, &symbols).unwrap();
+    let lcov = String::from_utf8(lcov).unwrap();
+    assert!(lcov.starts_with("TN:arm9\n"));
+    assert_eq!(lcov.matches("end_of_record").count(), 2);
+    // Only functions that were called count as hit.
+    let called = functions.iter().filter(|f| f.calls > 0).count();
+    assert!(lcov.contains(&format!("FNF:{}\nFNH:{called}\n", functions.len())));
+    // Instructions of the main RAM symbol that never ran are listed as not hit.
+    let arm9_record = lcov.split("end_of_record").next().unwrap();
+    let summary = |key: &str| -> usize {
+        let line = arm9_record.lines().find(|l| l.starts_with(key)).unwrap();
+        line[key.len()..].parse().unwrap()
+    };
+    assert_eq!(summary("LH:"), arm9.instructions.len());
+    if in_main_ram > 0 {
+        assert!(summary("LF:") >= 0x400000 / 4);
+    }
+
+    let mut collapsed = Vec::new();
+    profile.write_collapsed(&mut collapsed, &symbols).unwrap();
+    let collapsed = String::from_utf8(collapsed).unwrap();
+    let total: u64 = collapsed
+        .lines()
+        .filter(|l| l.starts_with("arm9"))
+        .map(|l| l.rsplit(' ').next().unwrap().parse::<u64>().unwrap())
+        .sum();
+    assert_eq!(total, arm9.total_cycles());
+}
@@ -0,0 +1,9 @@
+use desmume_rs::mem::IndexMove;
+use desmume_rs::DeSmuME;
+
+#[test]
+#[should_panic(expected = "needs an accessor scoped to a memory region")]
+fn test_unscoped_full_range() {
+    let emu = DeSmuME::init().unwrap();
+    emu.memory().u8().index_move(..);
+}
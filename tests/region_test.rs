@@ -0,0 +1,66 @@
+use desmume_rs::mem::{MemoryRegion, Processor};
+
+#[test]
+fn test_memory_regions() {
+    assert_eq!(
+        MemoryRegion::containing(0x02001234),
+        Some(MemoryRegion::MainRam)
+    );
+    assert_eq!(
+        MemoryRegion::containing(0x027E0010),
+        Some(MemoryRegion::Dtcm)
+    );
+    assert_eq!(
+        MemoryRegion::containing(0x01000000),
+        Some(MemoryRegion::Itcm)
+    );
+    assert_eq!(MemoryRegion::containing(0x0B000000), None);
+
+    assert_eq!(MemoryRegion::MainRam.range(), 0x02000000..=0x023FFFFF);
+    assert_eq!(
+        MemoryRegion::MainRam.canonicalize(0x02401234),
+        Some(0x02001234)
+    );
+    assert_eq!(
+        MemoryRegion::MainRam.mirror_bounds(0x02401234),
+        Some(0x02400000..=0x027FFFFF)
+    );
+    assert_eq!(
+        MemoryRegion::Itcm.canonicalize(0x00000010),
+        Some(0x01FF8010)
+    );
+    assert_eq!(
+        MemoryRegion::Palette.canonicalize(0x05000C00),
+        Some(0x05000400)
+    );
+    assert_eq!(MemoryRegion::Oam.canonicalize(0x06000000), None);
+    assert_eq!(
+        MemoryRegion::Arm9Bios.mirror_bounds(0xFFFF9000),
+        Some(0xFFFF8000..=0xFFFFFFFF)
+    );
+
+    assert_eq!(
+        MemoryRegion::containing(0x03000010),
+        Some(MemoryRegion::SharedWram)
+    );
+    assert_eq!(
+        MemoryRegion::containing_on(Processor::Arm7, 0x03800000),
+        Some(MemoryRegion::Arm7Wram)
+    );
+    assert_eq!(
+        MemoryRegion::containing_on(Processor::Arm7, 0x00000010),
+        Some(MemoryRegion::Arm7Bios)
+    );
+    assert_eq!(
+        MemoryRegion::containing_on(Processor::Arm7, 0x04808000),
+        Some(MemoryRegion::Io)
+    );
+    assert_eq!(
+        MemoryRegion::containing(0x068A4000),
+        Some(MemoryRegion::VramLcdc)
+    );
+    assert_eq!(MemoryRegion::VramE.range(), 0x06880000..=0x0688FFFF);
+    assert_eq!(MemoryRegion::VramI.size(), 0x4000);
+    assert!(MemoryRegion::MainRam.is_visible_to(Processor::Arm7));
+    assert!(!MemoryRegion::Itcm.is_visible_to(Processor::Arm7));
+}
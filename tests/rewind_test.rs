@@ -0,0 +1,53 @@
+use desmume_rs::{DeSmuME, RewindConfig};
+use std::env::current_dir;
+
+#[test]
+fn test_rewind() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    assert!(emu.rewind_mut().rewind(10).is_err());
+
+    emu.rewind_mut().enable(RewindConfig {
+        interval: 10,
+        capacity: 5,
+        ..RewindConfig::default()
+    });
+    for _ in 0..120 {
+        emu.cycle();
+    }
+    assert_eq!(emu.rewind().frame(), 120);
+    assert_eq!(emu.rewind().len(), 5);
+
+    // The newest snapshot at or before frame 95 was taken at frame 90.
+    assert_eq!(emu.rewind_mut().rewind(25).unwrap(), 30);
+    assert_eq!(emu.rewind().frame(), 90);
+    assert_eq!(emu.rewind().len(), 2);
+
+    // The history only reaches back to frame 80.
+    assert_eq!(emu.rewind_mut().rewind(1000).unwrap(), 10);
+    assert_eq!(emu.rewind().len(), 1);
+
+    // Loading a savestate starts a new timeline.
+    let state = emu.savestate_mut().save_to_vec().unwrap();
+    for _ in 0..20 {
+        emu.cycle();
+    }
+    assert_eq!(emu.rewind().len(), 3);
+    emu.savestate_mut().load_from_slice(&state).unwrap();
+    assert!(emu.rewind().is_empty());
+    assert!(emu.rewind_mut().rewind(10).is_err());
+
+    // So does resetting the emulator.
+    for _ in 0..10 {
+        emu.cycle();
+    }
+    assert_eq!(emu.rewind().len(), 1);
+    emu.reset();
+    assert!(emu.rewind().is_empty());
+    assert_eq!(emu.rewind().frame(), 0);
+}
@@ -0,0 +1,31 @@
+use desmume_rs::mem::{IndexMove, IndexSet};
+use desmume_rs::DeSmuME;
+use std::env::current_dir;
+
+#[test]
+fn test_savestate_in_memory() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..100 {
+        emu.cycle();
+    }
+
+    let state = emu.savestate_mut().save_to_vec().unwrap();
+    assert!(!state.is_empty());
+
+    let before = emu.memory().u8().index_move(0x2000000);
+    emu.memory()
+        .u8()
+        .index_set(0x2000000, &before.wrapping_add(1));
+    assert_ne!(emu.memory().u8().index_move(0x2000000), before);
+
+    emu.savestate_mut().load_from_slice(&state).unwrap();
+    assert_eq!(emu.memory().u8().index_move(0x2000000), before);
+
+    assert!(emu.savestate_mut().load_from_slice(&[1, 2, 3]).is_err());
+}
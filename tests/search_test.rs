@@ -0,0 +1,84 @@
+use desmume_rs::mem::{IndexSet, MemoryRegion};
+use desmume_rs::search::{CompareTo, Comparison, RamSearch, SearchConfig, ValueSize};
+use desmume_rs::DeSmuME;
+use std::env::current_dir;
+
+const SCORE: u32 = 0x02300000;
+const SPEED: u32 = 0x02300011;
+
+/// Halfword writes are aligned by the bus, so unaligned values are written bytewise.
+fn write_i16(emu: &mut DeSmuME, address: u32, value: i16) {
+    for (i, byte) in value.to_le_bytes().iter().enumerate() {
+        emu.memory_mut()
+            .u8_mut()
+            .index_set(address + i as u32, byte);
+    }
+}
+
+#[test]
+fn test_ram_search() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..10 {
+        emu.cycle();
+    }
+
+    emu.memory_mut().u32_mut().index_set(SCORE, &0x12345678);
+    let mut search = RamSearch::new(emu.memory(), SearchConfig::default());
+    assert_eq!(search.len(), 0x400000 / 4);
+    let remaining = search.filter(
+        emu.memory(),
+        Comparison::Equal,
+        CompareTo::Value(0x12345678),
+    );
+    assert!(remaining >= 1);
+    assert!(search.results().any(|r| r.address == SCORE));
+
+    emu.memory_mut().u32_mut().index_set(SCORE, &0x1234567D);
+    search.filter(
+        emu.memory(),
+        Comparison::IncreasedBy(5),
+        CompareTo::Previous,
+    );
+    let results: Vec<_> = search.results().collect();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].address, SCORE);
+    assert_eq!(results[0].previous, 0x12345678);
+    assert_eq!(results[0].current, 0x1234567D);
+    search.remove(SCORE);
+    assert!(search.is_empty());
+
+    // Signed, unaligned halfwords.
+    write_i16(&mut emu, SPEED, -2);
+    let mut search = RamSearch::new(
+        emu.memory(),
+        SearchConfig {
+            regions: vec![MemoryRegion::MainRam],
+            size: ValueSize::Halfword,
+            signed: true,
+            aligned: false,
+        },
+    );
+    assert_eq!(search.len(), 0x400000 - 1);
+    search.filter(emu.memory(), Comparison::Less, CompareTo::Value(0));
+    assert!(search
+        .results()
+        .any(|r| r.address == SPEED && r.current == -2));
+    write_i16(&mut emu, SPEED, -1000);
+    search.filter(
+        emu.memory(),
+        Comparison::DecreasedBy(998),
+        CompareTo::Previous,
+    );
+    assert!(search
+        .results()
+        .any(|r| r.address == SPEED && r.previous == -2 && r.current == -1000));
+    search.update(emu.memory());
+    search.filter(emu.memory(), Comparison::Equal, CompareTo::Previous);
+    assert!(search.results().any(|r| r.address == SPEED));
+}
@@ -0,0 +1,78 @@
+use desmume_rs::debugger::{Breakpoint, StopReason};
+use desmume_rs::mem::Processor;
+use desmume_rs::DeSmuME;
+use std::env::current_dir;
+
+/// Step until the next instruction is an unconditional `bl`. Returns the instruction's address,
+/// length and target.
+fn step_to_call(emu: &mut DeSmuME, processor: Processor) -> (u32, u32, u32) {
+    for _ in 0..100_000 {
+        let pc = emu.debugger().instruction_address(processor);
+        let instruction = emu.memory().disassemble(processor, pc, 1).remove(0);
+        if instruction.mnemonic == "bl" {
+            let target = instruction.branch_target.unwrap();
+            return (pc, instruction.length, target);
+        }
+        emu.debugger().step_instruction(processor);
+    }
+    panic!("no call found");
+}
+
+#[test]
+fn test_stepping() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..10 {
+        emu.cycle();
+    }
+
+    let mut debugger = emu.debugger();
+    for processor in [Processor::Arm9, Processor::Arm7] {
+        let pc = debugger.step_instruction(processor);
+        assert_eq!(pc, debugger.instruction_address(processor));
+        let pc = debugger.run_instructions(processor, 100);
+        assert_eq!(pc, debugger.instruction_address(processor));
+        assert!(matches!(
+            debugger.step_over(processor, 100_000),
+            StopReason::Stepped(_) | StopReason::InstructionLimit
+        ));
+    }
+
+    // Stepping over a call stops right behind it.
+    let (call_site, length, _) = step_to_call(&mut emu, Processor::Arm9);
+    assert!(matches!(
+        emu.debugger().step_over(Processor::Arm9, 1_000_000),
+        StopReason::Stepped(pc) if pc == call_site + length
+    ));
+
+    // Stepping out of a function stops at the return address.
+    let (call_site, length, target) = step_to_call(&mut emu, Processor::Arm9);
+    let mut debugger = emu.debugger();
+    // Thumb BL pairs are executed as two instructions.
+    while debugger.instruction_address(Processor::Arm9) != target {
+        let pc = debugger.step_instruction(Processor::Arm9);
+        assert!(pc == target || pc == call_site + 2, "{pc:#x}");
+    }
+    assert!(matches!(
+        debugger.step_out(Processor::Arm9, 1_000_000),
+        StopReason::Stepped(pc) if pc == call_site + length
+    ));
+
+    // The execution breakpoint at the current instruction is stepped over, but still counted.
+    let pc = debugger.instruction_address(Processor::Arm9);
+    let bp = debugger.add_breakpoint(Breakpoint::exec(Processor::Arm9, pc));
+    assert!(matches!(
+        debugger.run_instructions_until_break(Processor::Arm9, 1),
+        StopReason::InstructionLimit
+    ));
+    assert_eq!(debugger.hit_count(bp), 1);
+    debugger.clear_breakpoints();
+
+    // Frames continue normally after stepping.
+    emu.cycle();
+}
@@ -0,0 +1,55 @@
+use desmume_rs::mem::{swi_name, HookAction, Processor};
+use desmume_rs::symbols::{Symbolize, Symbols};
+use desmume_rs::DeSmuME;
+use std::env::current_dir;
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn test_swi_hooks() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..100 {
+        emu.cycle();
+    }
+
+    // The test ROM waits for VBlank with the BIOS every frame.
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let calls_clone = calls.clone();
+    let handle = emu
+        .memory_mut()
+        .hook_swi(Processor::Arm9, 0x05, move |ctx| {
+            assert_eq!(ctx.number, 0x05);
+            assert_eq!(ctx.name(), Some("VBlankIntrWait"));
+            assert!(ctx
+                .symbolize(&Symbols::new())
+                .starts_with("swi 0x05 VBlankIntrWait at 0x"));
+            assert!(!ctx.is_replaced());
+            calls_clone.lock().unwrap().push(ctx.address);
+            HookAction::Continue
+        });
+
+    for _ in 0..10 {
+        emu.cycle();
+    }
+    let count = calls.lock().unwrap().len();
+    assert!(count > 0);
+
+    drop(handle);
+    emu.cycle();
+    assert_eq!(calls.lock().unwrap().len(), count);
+}
+
+#[test]
+fn test_swi_names() {
+    assert_eq!(swi_name(Processor::Arm9, 0x09), Some("Div"));
+    assert_eq!(swi_name(Processor::Arm7, 0x0B), Some("CpuSet"));
+    assert_eq!(swi_name(Processor::Arm9, 0x1A), None);
+    assert_eq!(swi_name(Processor::Arm7, 0x1A), Some("GetSineTable"));
+    assert_eq!(swi_name(Processor::Arm7, 0x1F), Some("CustomHalt"));
+    assert_eq!(swi_name(Processor::Arm9, 0x1F), None);
+}
@@ -0,0 +1,112 @@
+use desmume_rs::disasm::{decode_arm, Architecture};
+use desmume_rs::symbols::{Symbol, SymbolFormat, Symbolize, Symbols};
+use desmume_rs::DeSmuMEError;
+
+#[test]
+fn test_linker_map() {
+    let map = "
+ .text          0x02000800      0x120 main.o
+                0x02000800                GameMain
+                0x02000860                UpdateScore
+ *fill*         0x02000920        0x4
+                0x02000924                . = ALIGN (0x4)
+";
+    let symbols = Symbols::parse(map, SymbolFormat::LinkerMap).unwrap();
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(symbols.lookup("UpdateScore"), Some(0x02000860));
+}
+
+#[test]
+fn test_linker_script() {
+    let ld = "
+/* Functions */
+GameMain = 0x02000800;
+PROVIDE(UpdateScore = 0x02000861); // Thumb
+. = ALIGN(4);
+gScore = ADDR(.bss) + 4;
+";
+    let symbols = Symbols::parse(ld, SymbolFormat::LinkerScript).unwrap();
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(symbols.lookup("GameMain"), Some(0x02000800));
+    // The Thumb bit is ignored when resolving.
+    assert_eq!(symbols.resolve(0x02000860), Some(("UpdateScore", 0)));
+    assert_eq!(symbols.resolve(0x02000864), Some(("UpdateScore", 4)));
+}
+
+#[test]
+fn test_sym() {
+    let sym = "
+; no$gba symbols
+02000800 GameMain
+02000900 .arm
+02000a00 gScore
+";
+    let symbols = Symbols::parse(sym, SymbolFormat::Sym).unwrap();
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(symbols.resolve(0x02000a10), Some(("gScore", 0x10)));
+    assert_eq!(symbols.resolve(0x020007ff), None);
+
+    let err = Symbols::parse("02000800 GameMain\nxyz Broken", SymbolFormat::Sym).unwrap_err();
+    assert!(matches!(err, DeSmuMEError::InvalidSymbols { line: 2, .. }));
+}
+
+#[test]
+fn test_csv_and_overlays() {
+    let csv = "\
+name,address,length,overlay
+GameMain,0x02000800,0x100,
+BattleInit,0x022bc000,0x40,11
+MenuInit,0x022bc000,0x80,12
+";
+    let mut symbols = Symbols::parse(csv, SymbolFormat::Csv).unwrap();
+    assert_eq!(symbols.len(), 3);
+    assert_eq!(symbols.resolve(0x02000810), Some(("GameMain", 0x10)));
+    // Past the end of the symbol.
+    assert_eq!(symbols.resolve(0x02000900), None);
+
+    symbols.set_loaded_overlays([11]);
+    assert_eq!(symbols.resolve(0x022bc010), Some(("BattleInit", 0x10)));
+    assert_eq!(symbols.resolve(0x022bc060), None);
+    symbols.set_loaded_overlays([12]);
+    assert_eq!(symbols.resolve(0x022bc060), Some(("MenuInit", 0x60)));
+}
+
+#[test]
+fn test_yaml() {
+    let yaml = "
+# Symbols
+- name: GameMain
+  address: 0x02000800
+  length: 0x100
+- name: \"gScore\"
+  address: 0x020a0000
+  overlay: ~
+  description: The current score
+";
+    let symbols = Symbols::parse(yaml, SymbolFormat::Yaml).unwrap();
+    assert_eq!(
+        symbols.get("GameMain"),
+        Some(&Symbol::new("GameMain", 0x02000800).with_length(0x100))
+    );
+    assert_eq!(symbols.lookup("gScore"), Some(0x020a0000));
+
+    let err = Symbols::parse("- name: GameMain\n", SymbolFormat::Yaml).unwrap_err();
+    assert!(matches!(err, DeSmuMEError::InvalidSymbols { line: 1, .. }));
+}
+
+#[test]
+fn test_symbolize() {
+    let symbols: Symbols = [
+        Symbol::new("GameMain", 0x02000800),
+        Symbol::new("UpdateScore", 0x02000860),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(symbols.symbolize(0x02000800), "GameMain");
+    assert_eq!(symbols.symbolize(0x02000844), "GameMain+0x44");
+    assert_eq!(symbols.symbolize(0x01000000), "0x01000000");
+    assert_eq!(0x02000864.symbolize(&symbols), "UpdateScore+0x4");
+
+    let bl = decode_arm(0x02000844, 0xEB000005, Architecture::ArmV5TE);
+    assert_eq!(bl.symbolize(&symbols), "bl UpdateScore");
+}
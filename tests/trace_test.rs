@@ -0,0 +1,65 @@
+use desmume_rs::mem::Processor;
+use desmume_rs::{DeSmuME, Trace, TraceConfig};
+use std::env::current_dir;
+
+#[test]
+fn test_trace() {
+    let mut emu = DeSmuME::init().unwrap();
+
+    let rom_path = current_dir().unwrap().join("tests/touchtest.nds");
+
+    emu.open(rom_path.to_str().unwrap(), false).unwrap();
+    emu.resume(false);
+
+    for _ in 0..10 {
+        emu.cycle();
+    }
+
+    emu.trace_mut().start(TraceConfig {
+        register_deltas: true,
+        ..TraceConfig::default()
+    });
+    assert!(emu.trace().is_recording());
+    emu.cycle();
+    let trace = emu.trace_mut().stop().unwrap();
+    assert!(!emu.trace().is_recording());
+    assert!(!trace.is_empty());
+    assert!(!trace.is_truncated());
+    assert_eq!(trace.iter().count(), trace.len());
+    assert!(trace.iter().all(|e| e.processor == Processor::Arm9));
+    assert!(trace.iter().any(|e| !e.register_deltas.is_empty()));
+    let cycles: Vec<u64> = trace.iter().map(|e| e.cycles).collect();
+    assert!(cycles.windows(2).all(|w| w[0] <= w[1]));
+
+    let mut text = Vec::new();
+    trace.write_text(&mut text).unwrap();
+    assert_eq!(
+        String::from_utf8(text).unwrap().lines().count(),
+        trace.len()
+    );
+
+    let loaded = Trace::from_bytes(trace.as_bytes().to_vec()).unwrap();
+    assert_eq!(loaded.len(), trace.len());
+    assert!(loaded.iter().eq(trace.iter()));
+    assert!(Trace::from_bytes(vec![1, 2, 3]).is_err());
+
+    emu.trace_mut().start(TraceConfig {
+        processor: Processor::Arm7,
+        branches_only: true,
+        ..TraceConfig::default()
+    });
+    emu.cycle();
+    let branches = emu.trace_mut().stop().unwrap();
+    assert!(branches.iter().all(|e| e.processor == Processor::Arm7));
+    assert!(branches.iter().all(|e| e.register_deltas.is_empty()));
+
+    // Size limit.
+    emu.trace_mut().start(TraceConfig {
+        max_size: 1000,
+        ..TraceConfig::default()
+    });
+    emu.cycle();
+    let truncated = emu.trace_mut().stop().unwrap();
+    assert!(truncated.is_truncated());
+    assert!(truncated.as_bytes().len() <= 1000);
+}